@@ -0,0 +1,87 @@
+use epub::archive::ArchiveError;
+use epub::doc::DocError;
+
+/// Why a book could not be converted.
+#[derive(Debug)]
+pub enum EpubError {
+  Io(std::io::Error),
+  /// The file is not a zip container, or has no EPUB package in it
+  NotEpub,
+  /// The content documents are DRM protected
+  Encrypted,
+  /// The spine references `id`, but the archive does not contain it
+  MissingResource {
+    id: String,
+  },
+  /// A file name or the package document is not valid UTF-8
+  InvalidUtf8,
+  /// The files of the archive unpack to more than `limit` bytes, like a
+  /// zip bomb. Unpacking stopped after `size` bytes
+  TooLarge {
+    size: u64,
+    limit: u64,
+  },
+  /// The archive or package, or the 1-based spine `chapter` when set, could
+  /// not be parsed
+  Corrupt {
+    chapter: Option<usize>,
+    reason: String,
+  },
+}
+
+impl std::fmt::Display for EpubError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      EpubError::Io(e) => write!(f, "{e}"),
+      EpubError::NotEpub => write!(f, "not an EPUB document"),
+      EpubError::Encrypted => {
+        write!(f, "this EPUB is DRM protected and can not be read")
+      }
+      EpubError::MissingResource { id } => {
+        write!(f, "the EPUB is missing the content document '{id}'")
+      }
+      EpubError::InvalidUtf8 => write!(f, "the EPUB contains invalid UTF-8"),
+      EpubError::TooLarge { limit, .. } => write!(
+        f,
+        "the EPUB unpacks to more than the limit of {} MiB",
+        limit / (1024 * 1024)
+      ),
+      EpubError::Corrupt { chapter: Some(chapter), reason } => {
+        write!(f, "chapter {chapter} of the EPUB is corrupt: {reason}")
+      }
+      EpubError::Corrupt { chapter: None, reason } => {
+        write!(f, "the EPUB is corrupt: {reason}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for EpubError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      EpubError::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for EpubError {
+  fn from(e: std::io::Error) -> Self {
+    EpubError::Io(e)
+  }
+}
+
+impl From<DocError> for EpubError {
+  fn from(e: DocError) -> Self {
+    match e {
+      DocError::IOError(e) | DocError::ArchiveError(ArchiveError::IO(e)) => {
+        EpubError::Io(e)
+      }
+      DocError::ArchiveError(
+        ArchiveError::Utf8(_) | ArchiveError::PathUtf8,
+      ) => EpubError::InvalidUtf8,
+      DocError::InvalidEpub => EpubError::NotEpub,
+      e => EpubError::Corrupt { chapter: None, reason: e.to_string() },
+    }
+  }
+}
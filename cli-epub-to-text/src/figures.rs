@@ -0,0 +1,403 @@
+use epub::doc::EpubDoc;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+
+/// An image of a chapter, the text shows a placeholder for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Figure {
+  /// The `alt` text of the image, `None` when it is empty
+  pub alt: Option<String>,
+  /// The size of the image in pixels, from its `width` and `height`
+  /// attributes or else the image file
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  /// The image file and its extension like `png`, `None` when the archive
+  /// does not contain it
+  pub image: Option<(Vec<u8>, String)>,
+}
+
+impl Figure {
+  /// `[Figure: alt text, 640×480]`, the text that stands in for the image.
+  pub fn placeholder(&self) -> String {
+    let size = match (self.width, self.height) {
+      (Some(width), Some(height)) => Some(format!("{width}×{height}")),
+      _ => None,
+    };
+
+    match (&self.alt, size) {
+      (Some(alt), Some(size)) => format!("[Figure: {alt}, {size}]"),
+      (Some(alt), None) => format!("[Figure: {alt}]"),
+      (None, Some(size)) => format!("[Figure: {size}]"),
+      (None, None) => "[Figure]".to_string(),
+    }
+  }
+}
+
+/// Replaces every `<img>` of the chapter at `chapter_path` in the archive
+/// with a paragraph holding the placeholder of its image.
+pub(crate) fn replace_images(
+  epub: &mut EpubDoc<Cursor<Vec<u8>>>,
+  chapter_path: &Path,
+  xhtml: &str,
+) -> (String, Vec<Figure>) {
+  // Lowercasing ASCII keeps the byte offsets
+  let lower = xhtml.to_ascii_lowercase();
+  let mut replaced = String::new();
+  let mut figures = Vec::new();
+  let mut copied = 0;
+
+  let mut from = 0;
+  while let Some(found) = lower[from..].find("<img") {
+    let start = from + found;
+    from = start + 4;
+
+    let after = lower.as_bytes().get(start + 4).copied();
+    if !after.is_some_and(|x| x.is_ascii_whitespace() || x == b'/' || x == b'>')
+    {
+      continue;
+    }
+    let Some(len) = tag_len(&xhtml[start..]) else {
+      break;
+    };
+
+    let attributes = attributes(&xhtml[start + 4..start + len - 1]);
+    let figure = figure(epub, chapter_path, &attributes);
+
+    replaced.push_str(&xhtml[copied..start]);
+    replaced.push_str(&format!("<p>{}</p>", escape(&figure.placeholder())));
+    figures.push(figure);
+
+    copied = start + len;
+    from = copied;
+  }
+  replaced.push_str(&xhtml[copied..]);
+
+  (replaced, figures)
+}
+
+fn figure(
+  epub: &mut EpubDoc<Cursor<Vec<u8>>>,
+  chapter_path: &Path,
+  attributes: &[(String, String)],
+) -> Figure {
+  let attribute = |name: &str| {
+    attributes.iter().find(|(x, _)| x == name).map(|(_, x)| decode(x))
+  };
+  // `width="50%"` is no size in pixels
+  let pixels = |name: &str| {
+    attribute(name).and_then(|x| x.trim().trim_end_matches("px").parse().ok())
+  };
+
+  let image = attribute("src").and_then(|src| {
+    let path = resolve(chapter_path, &src)?;
+    let data = epub.get_resource_by_path(&path)?;
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+    Some((data, extension))
+  });
+  let size = image.as_ref().and_then(|(data, _)| image_size(data));
+
+  Figure {
+    alt: attribute("alt")
+      .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "))
+      .filter(|x| !x.is_empty()),
+    width: pixels("width").or(size.map(|x| x.0)),
+    height: pixels("height").or(size.map(|x| x.1)),
+    image,
+  }
+}
+
+/// The length of the tag at the start of `html`, up to and including its
+/// `>`, which may also appear in quoted attribute values.
+fn tag_len(html: &str) -> Option<usize> {
+  let mut quote = None;
+
+  for (i, c) in html.char_indices() {
+    match (quote, c) {
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(q), c) if q == c => quote = None,
+      (None, '>') => return Some(i + 1),
+      _ => {}
+    }
+  }
+
+  None
+}
+
+/// The `name="value"` pairs of a tag, names are lowercased.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+  let mut attributes = Vec::new();
+  let mut rest = tag;
+
+  loop {
+    rest = rest.trim_start_matches(|x: char| x.is_whitespace() || x == '/');
+    let name_len = rest
+      .find(|x: char| x.is_whitespace() || x == '=' || x == '/')
+      .unwrap_or(rest.len());
+    if name_len == 0 {
+      return attributes;
+    }
+    let name = rest[..name_len].to_lowercase();
+    rest = rest[name_len..].trim_start();
+
+    let Some(after) = rest.strip_prefix('=') else {
+      attributes.push((name, String::new()));
+      continue;
+    };
+    let after = after.trim_start();
+
+    let (value, len) = match after.chars().next() {
+      Some(quote @ ('"' | '\'')) => {
+        let end = after[1..].find(quote).map_or(after.len(), |x| x + 1);
+        (&after[1..end], (end + 1).min(after.len()))
+      }
+      _ => {
+        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+        (&after[..end], end)
+      }
+    };
+    attributes.push((name, value.to_string()));
+    rest = &after[len..];
+  }
+}
+
+/// The path in the archive of `src` relative to the chapter, `None` for
+/// images outside of the archive.
+fn resolve(chapter_path: &Path, src: &str) -> Option<PathBuf> {
+  if src.contains("://") || src.starts_with("data:") {
+    return None;
+  }
+
+  let src = percent_decode(src.split(['#', '?']).next()?);
+  let mut path = PathBuf::new();
+  for component in chapter_path.parent()?.join(src).components() {
+    match component {
+      Component::ParentDir => {
+        path.pop();
+      }
+      Component::Normal(x) => path.push(x),
+      _ => {}
+    }
+  }
+
+  Some(path)
+}
+
+fn percent_decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut decoded = Vec::new();
+
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = bytes
+      .get(i + 1..i + 3)
+      .and_then(|x| u8::from_str_radix(std::str::from_utf8(x).ok()?, 16).ok());
+
+    match (bytes[i], hex) {
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        i += 3;
+      }
+      (byte, _) => {
+        decoded.push(byte);
+        i += 1;
+      }
+    }
+  }
+
+  String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Decodes the character references of an attribute value.
+fn decode(text: &str) -> String {
+  let mut decoded = String::new();
+  let mut rest = text;
+
+  while let Some(at) = rest.find('&') {
+    decoded.push_str(&rest[..at]);
+    rest = &rest[at..];
+
+    let end = rest.find(';').filter(|&x| x <= 10);
+    let entity = end.map(|x| &rest[1..x]);
+    let c = match entity {
+      Some("amp") => Some('&'),
+      Some("lt") => Some('<'),
+      Some("gt") => Some('>'),
+      Some("quot") => Some('"'),
+      Some("apos") => Some('\''),
+      Some("nbsp") => Some(' '),
+      Some(x) if x.starts_with("#x") || x.starts_with("#X") => {
+        u32::from_str_radix(&x[2..], 16).ok().and_then(char::from_u32)
+      }
+      Some(x) if x.starts_with('#') => {
+        x[1..].parse().ok().and_then(char::from_u32)
+      }
+      _ => None,
+    };
+
+    match (c, end) {
+      (Some(c), Some(end)) => {
+        decoded.push(c);
+        rest = &rest[end + 1..];
+      }
+      _ => {
+        decoded.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  decoded.push_str(rest);
+
+  decoded
+}
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The width and height of a PNG, GIF or JPEG image.
+fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+  let be16 = |at: usize| {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+  };
+  let be32 = |at: usize| {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+  };
+  let le16 = |at: usize| {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+  };
+
+  if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+    return Some((be32(16)?, be32(20)?));
+  }
+  if data.starts_with(b"GIF8") {
+    return Some((le16(6)?, le16(8)?));
+  }
+  if !data.starts_with(&[0xFF, 0xD8]) {
+    return None;
+  }
+
+  // The size is in the first start of frame segment
+  let mut at = 2;
+  while *data.get(at)? == 0xFF {
+    let marker = *data.get(at + 1)?;
+    let is_frame =
+      matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+    if is_frame {
+      return Some((be16(at + 7)?, be16(at + 5)?));
+    }
+    at += 2 + be16(at + 2)? as usize;
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(x, y)| (x.to_string(), y.to_string())).collect()
+  }
+
+  #[test]
+  fn test_placeholder() {
+    let figure = |alt: Option<&str>, size: Option<(u32, u32)>| Figure {
+      alt: alt.map(str::to_string),
+      width: size.map(|x| x.0),
+      height: size.map(|x| x.1),
+      image: None,
+    };
+
+    assert_eq!(
+      figure(Some("A map"), Some((640, 480))).placeholder(),
+      "[Figure: A map, 640×480]"
+    );
+    assert_eq!(figure(Some("A map"), None).placeholder(), "[Figure: A map]");
+    assert_eq!(figure(None, Some((1, 2))).placeholder(), "[Figure: 1×2]");
+    assert_eq!(figure(None, None).placeholder(), "[Figure]");
+  }
+
+  #[test]
+  fn test_tag_len() {
+    assert_eq!(tag_len("<img src=\"a.png\"/> text"), Some(18));
+    assert_eq!(tag_len("<img alt='a > b' src=a.png>"), Some(27));
+    assert_eq!(tag_len("<img alt=\"it's\">"), Some(16));
+    assert_eq!(tag_len("<img alt=\"unclosed >"), None);
+  }
+
+  #[test]
+  fn test_attributes() {
+    assert_eq!(
+      attributes(" SRC=\"a b.png\" alt='it \"is\"' width=50 hidden /"),
+      pairs(&[
+        ("src", "a b.png"),
+        ("alt", "it \"is\""),
+        ("width", "50"),
+        ("hidden", ""),
+      ])
+    );
+    assert_eq!(attributes(" src = a.png/"), pairs(&[("src", "a.png/")]));
+    assert_eq!(attributes(" alt=\"unclosed"), pairs(&[("alt", "unclosed")]));
+    assert_eq!(attributes(""), pairs(&[]));
+  }
+
+  #[test]
+  fn test_resolve() {
+    let chapter = Path::new("OEBPS/text/chapter1.xhtml");
+
+    assert_eq!(
+      resolve(chapter, "../images/map.png"),
+      Some(PathBuf::from("OEBPS/images/map.png"))
+    );
+    assert_eq!(
+      resolve(chapter, "./a%20map.png#top"),
+      Some(PathBuf::from("OEBPS/text/a map.png"))
+    );
+    assert_eq!(
+      resolve(chapter, "../../../map.png?v=1"),
+      Some(PathBuf::from("map.png"))
+    );
+    assert_eq!(resolve(chapter, "https://example.com/map.png"), None);
+    assert_eq!(resolve(chapter, "data:image/png;base64,AAAA"), None);
+  }
+
+  #[test]
+  fn test_percent_decode() {
+    assert_eq!(percent_decode("a%20map.png"), "a map.png");
+    assert_eq!(percent_decode("caf%C3%A9"), "café");
+    assert_eq!(percent_decode("100%"), "100%");
+    assert_eq!(percent_decode("%zz%4"), "%zz%4");
+  }
+
+  #[test]
+  fn test_decode() {
+    assert_eq!(decode("Tom &amp; Jerry"), "Tom & Jerry");
+    assert_eq!(decode("&lt;&gt;&quot;&apos;"), "<>\"'");
+    assert_eq!(decode("&#65;&#x42;&#X43;"), "ABC");
+    assert_eq!(decode("&unknown; & &#xZZ; &"), "&unknown; & &#xZZ; &");
+    assert_eq!(decode("&averylongname;"), "&averylongname;");
+  }
+
+  #[test]
+  fn test_image_size() {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend(640u32.to_be_bytes());
+    png.extend(480u32.to_be_bytes());
+    assert_eq!(image_size(&png), Some((640, 480)));
+
+    let gif = b"GIF89a\x80\x02\xe0\x01";
+    assert_eq!(image_size(gif), Some((640, 480)));
+
+    // An APP0 segment before the start of frame
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0];
+    jpeg.extend([0xFF, 0xC0, 0, 11, 8]);
+    jpeg.extend(480u16.to_be_bytes());
+    jpeg.extend(640u16.to_be_bytes());
+    assert_eq!(image_size(&jpeg), Some((640, 480)));
+
+    assert_eq!(image_size(&png[..20]), None);
+    assert_eq!(image_size(&jpeg[..6]), None);
+    assert_eq!(image_size(b"not an image"), None);
+  }
+}
@@ -1,17 +1,189 @@
+mod error;
+mod figures;
+
+pub use error::EpubError;
+pub use figures::Figure;
+
 use epub::doc::EpubDoc;
 use html2text;
+use std::io::{Cursor, Read};
+use std::sync::mpsc;
+
+/// Raised whenever a release converts the same book to different text, so
+/// applications that cache the text convert it again.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Every EPUB is a zip container, which starts with a local file header.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Encryption algorithms that only obfuscate embedded fonts, the content
+/// documents of books using them are still readable.
+const FONT_OBFUSCATION: [&str; 2] =
+  ["http://www.idpf.org/2008/embedding", "http://ns.adobe.com/pdf/enc#RC"];
+
+/// Whether `bytes` start like an EPUB document.
+pub fn is_epub(bytes: &[u8]) -> bool {
+  bytes.starts_with(ZIP_MAGIC)
+}
+
+fn is_drm_protected(epub: &mut EpubDoc<Cursor<Vec<u8>>>) -> bool {
+  let Some(encryption) = epub.get_resource_by_path("META-INF/encryption.xml")
+  else {
+    return false;
+  };
+  let encryption = String::from_utf8_lossy(&encryption);
+
+  encryption
+    .split("Algorithm=\"")
+    .skip(1)
+    .filter_map(|x| x.split('"').next())
+    .any(|x| !FONT_OBFUSCATION.contains(&x))
+}
+
+/// Unpacks the archive's files without keeping them, to check they are no
+/// larger than `max_size` bytes. The sizes recorded in the archive are not
+/// trusted, a zip bomb can claim its files are small.
+fn check_size(bytes: &[u8], max_size: u64) -> Result<(), EpubError> {
+  let corrupt = |e: &dyn std::error::Error| EpubError::Corrupt {
+    chapter: None,
+    reason: e.to_string(),
+  };
+
+  let mut archive =
+    zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| corrupt(&e))?;
+  let mut size: u64 = 0;
+  for i in 0..archive.len() {
+    let file = archive.by_index(i).map_err(|e| corrupt(&e))?;
+    // One byte more than is left tells a file that is too large
+    let left = max_size - size + 1;
+    size += std::io::copy(&mut file.take(left), &mut std::io::sink())
+      .map_err(|e| corrupt(&e))?;
 
-pub fn epub_to_text(
+    if (size > max_size) {
+      return Err(EpubError::TooLarge { size, limit: max_size });
+    }
+  }
+
+  Ok(())
+}
+
+fn open_epub(
   file_path: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-  let mut epub = EpubDoc::new(file_path)?;
+  max_size: u64,
+) -> Result<EpubDoc<Cursor<Vec<u8>>>, EpubError> {
+  let bytes = std::fs::read(file_path)?;
+  if !is_epub(&bytes) {
+    return Err(EpubError::NotEpub);
+  }
+
+  if (max_size < u64::MAX) {
+    check_size(&bytes, max_size)?;
+  }
+
+  let mut epub = EpubDoc::from_reader(Cursor::new(bytes))?;
+  if is_drm_protected(&mut epub) {
+    return Err(EpubError::Encrypted);
+  }
+
+  Ok(epub)
+}
+
+/// A converted chapter of a book.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chapter {
+  pub text: String,
+  /// The images of the chapter, their placeholders are in the text
+  pub figures: Vec<Figure>,
+}
+
+/// Converts the 0-based `index` of the spine, `Ok(None)` when the archive
+/// does not contain it. Images are replaced with a placeholder.
+fn chapter_to_text(
+  epub: &mut EpubDoc<Cursor<Vec<u8>>>,
+  index: usize,
+) -> Result<Option<Chapter>, EpubError> {
+  let id = epub.spine[index].clone();
+  let Some((xhtml, _)) = epub.get_resource(&id) else {
+    return Ok(None);
+  };
+  let path = epub.resources.get(&id).map(|x| x.0.clone()).unwrap_or_default();
+
+  let xhtml = String::from_utf8_lossy(&xhtml).to_string();
+  let (xhtml, figures) = figures::replace_images(epub, &path, &xhtml);
+
+  let text = html2text::from_read(xhtml.as_bytes(), 110).map_err(|e| {
+    EpubError::Corrupt { chapter: Some(index + 1), reason: e.to_string() }
+  })?;
+
+  Ok(Some(Chapter { text, figures }))
+}
+
+/// Converts every chapter of the spine to text. Chapters missing from the
+/// archive are skipped, it is only an error when none of them can be read.
+pub fn epub_to_text(file_path: &str) -> Result<String, EpubError> {
+  let mut epub = open_epub(file_path, u64::MAX)?;
 
   let mut string_builder = vec![];
-  for spine_item in epub.spine.clone() {
-    let xhtml = epub.get_resource(&spine_item).unwrap().0;
-    let text = html2text::from_read(&*xhtml, 110)?;
-    string_builder.push(text);
+  let mut missing = None;
+  for i in 0..epub.spine.len() {
+    match chapter_to_text(&mut epub, i)? {
+      Some(chapter) => string_builder.push(chapter.text),
+      None => {
+        missing.get_or_insert(epub.spine[i].clone());
+      }
+    }
+  }
+
+  if let (true, Some(id)) = (string_builder.is_empty(), missing) {
+    return Err(EpubError::MissingResource { id });
   }
 
   Ok(string_builder.join("\n"))
 }
+
+/// Chapters that are converted in the background and arrive in spine order.
+pub struct ChapterStream {
+  pub chapter_count: usize,
+  pub chapters: mpsc::Receiver<Result<Chapter, EpubError>>,
+}
+
+/// Opens the book and converts its chapters on a background thread, errors
+/// opening the book are returned right away. Chapters missing from the
+/// archive arrive as `EpubError::MissingResource`, the chapters after them
+/// are still converted. Dropping the receiver stops the conversion.
+pub fn epub_to_chapter_stream(
+  file_path: &str,
+) -> Result<ChapterStream, EpubError> {
+  epub_to_chapter_stream_with_limit(file_path, u64::MAX)
+}
+
+/// Like `epub_to_chapter_stream`, but books whose files unpack to more than
+/// `max_size` bytes are rejected with `EpubError::TooLarge`. They are
+/// unpacked no further than that before any chapter is converted.
+pub fn epub_to_chapter_stream_with_limit(
+  file_path: &str,
+  max_size: u64,
+) -> Result<ChapterStream, EpubError> {
+  let mut epub = open_epub(file_path, max_size)?;
+  let chapter_count = epub.spine.len();
+
+  let (sender, receiver) = mpsc::channel();
+
+  std::thread::spawn(move || {
+    for i in 0..chapter_count {
+      let chapter = match chapter_to_text(&mut epub, i) {
+        Ok(Some(chapter)) => Ok(chapter),
+        Ok(None) => {
+          Err(EpubError::MissingResource { id: epub.spine[i].clone() })
+        }
+        Err(e) => Err(e),
+      };
+
+      if sender.send(chapter).is_err() {
+        return;
+      }
+    }
+  });
+
+  Ok(ChapterStream { chapter_count, chapters: receiver })
+}
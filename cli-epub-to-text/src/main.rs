@@ -1,9 +1,23 @@
 use cli_epub_to_text;
 use std;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let file_path = std::env::args().nth(1).unwrap();
-  println!("{}", cli_epub_to_text::epub_to_text(&file_path)?);
+fn main() {
+  let Some(file_path) = std::env::args().nth(1) else {
+    eprintln!("Usage: cli-epub-to-text FILE");
+    std::process::exit(2);
+  };
 
-  return Ok(());
+  match cli_epub_to_text::epub_to_text(&file_path) {
+    Ok(text) => println!(
+      "{}",
+      normalize_text::normalize(
+        &text,
+        &normalize_text::Normalization::default()
+      )
+    ),
+    Err(e) => {
+      eprintln!("error: {e}");
+      std::process::exit(1);
+    }
+  }
 }
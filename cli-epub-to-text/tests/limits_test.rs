@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+  use cli_epub_to_text::EpubError;
+  use std::io::Write;
+
+  fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir()
+      .join(format!("cli-epub-to-text-{}-{name}", std::process::id()));
+
+    return path.to_string_lossy().to_string();
+  }
+
+  /// A zip with a single stored file of `len` bytes.
+  fn zip_of(len: usize) -> Vec<u8> {
+    zip_with(len, zip::CompressionMethod::Stored)
+  }
+
+  fn zip_with(len: usize, method: zip::CompressionMethod) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options =
+      zip::write::SimpleFileOptions::default().compression_method(method);
+    zip.start_file("OEBPS/chapter.xhtml", options).unwrap();
+    zip.write_all(&vec![b'a'; len]).unwrap();
+
+    zip.finish().unwrap().into_inner()
+  }
+
+  /// Makes the central directory of the zip claim its file unpacks to
+  /// `size` bytes.
+  fn claim_size(zip: &mut [u8], size: u32) {
+    let header = zip.windows(4).position(|x| x == b"PK\x01\x02").unwrap();
+    zip[header + 24..header + 28].copy_from_slice(&size.to_le_bytes());
+  }
+
+  fn open(name: &str, zip: &[u8], limit: u64) -> Result<(), EpubError> {
+    let path = temp_path(name);
+    std::fs::write(&path, zip).unwrap();
+
+    let result =
+      cli_epub_to_text::epub_to_chapter_stream_with_limit(&path, limit);
+    std::fs::remove_file(path).unwrap();
+
+    result.map(|_| ())
+  }
+
+  #[test]
+  fn test_zip_bomb_claiming_small_files_is_too_large() {
+    let mut zip = zip_with(16 * 1024 * 1024, zip::CompressionMethod::Deflated);
+    claim_size(&mut zip, 16);
+    assert!(zip.len() < 64 * 1024);
+
+    match open("bomb.epub", &zip, 1024 * 1024) {
+      Err(EpubError::TooLarge { size, limit }) => {
+        assert_eq!(size, 1024 * 1024 + 1);
+        assert_eq!(limit, 1024 * 1024);
+      }
+      x => panic!("expected TooLarge, got {x:?}"),
+    }
+  }
+
+  #[test]
+  fn test_zip_claiming_large_files_is_not_too_large() {
+    let mut zip = zip_of(16);
+    claim_size(&mut zip, u32::MAX - 1);
+
+    assert!(!matches!(
+      open("claims.epub", &zip, 1024 * 1024),
+      Err(EpubError::TooLarge { .. })
+    ));
+  }
+
+  #[test]
+  fn test_size_limit_counts_the_unpacked_files() {
+    let zip = zip_of(2048);
+
+    assert!(matches!(
+      open("large.epub", &zip, 2047),
+      Err(EpubError::TooLarge { size: 2048, limit: 2047 })
+    ));
+    // Within the limit the archive is opened, it is not a book though
+    assert!(!matches!(
+      open("small.epub", &zip, 2048),
+      Err(EpubError::TooLarge { .. })
+    ));
+  }
+}
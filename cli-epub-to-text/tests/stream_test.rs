@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+  use cli_epub_to_text::EpubError;
+  use std::io::Write;
+
+  fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir()
+      .join(format!("cli-epub-to-text-{}-{name}", std::process::id()));
+
+    return path.to_string_lossy().to_string();
+  }
+
+  /// A book whose spine lists `one` and `two`, the archive only contains
+  /// `one`.
+  fn book_missing_a_chapter() -> Vec<u8> {
+    let files = [
+      ("mimetype", "application/epub+zip"),
+      (
+        "META-INF/container.xml",
+        r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#,
+      ),
+      (
+        "OEBPS/content.opf",
+        r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">missing-chapter</dc:identifier>
+    <dc:title>Missing chapter</dc:title>
+  </metadata>
+  <manifest>
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="two"/>
+    <itemref idref="one"/>
+  </spine>
+</package>"#,
+      ),
+      (
+        "OEBPS/one.xhtml",
+        r#"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Chapter one</p></body></html>"#,
+      ),
+    ];
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+      .compression_method(zip::CompressionMethod::Stored);
+    for (name, content) in files {
+      zip.start_file(name, options).unwrap();
+      zip.write_all(content.as_bytes()).unwrap();
+    }
+
+    zip.finish().unwrap().into_inner()
+  }
+
+  #[test]
+  fn test_missing_chapters_are_reported_in_the_stream() {
+    let path = temp_path("missing-chapter.epub");
+    std::fs::write(&path, book_missing_a_chapter()).unwrap();
+
+    let stream = cli_epub_to_text::epub_to_chapter_stream(&path).unwrap();
+    let chapters: Vec<_> = stream.chapters.iter().collect();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(stream.chapter_count, 2);
+    assert_eq!(chapters.len(), 2);
+    assert!(matches!(
+      &chapters[0],
+      Err(EpubError::MissingResource { id }) if id == "two"
+    ));
+    assert!(chapters[1].as_ref().unwrap().text.contains("Chapter one"));
+  }
+}
@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn test_data_txts() -> Vec<std::path::PathBuf> {
+  let dir =
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-data/txt");
+
+  let mut txts: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+    .unwrap()
+    .map_while(Result::ok)
+    .map(|x| x.path())
+    .filter(|x| x.extension().is_some_and(|ext| ext == "txt"))
+    .collect();
+  txts.sort();
+
+  return txts;
+}
+
+fn bench_justify(c: &mut Criterion) {
+  let mut group = c.benchmark_group("justify");
+  group.sample_size(10);
+
+  let hyphenation = cli_justify::Hyphenation::new("en").unwrap();
+
+  for txt in test_data_txts() {
+    let name = txt.file_name().unwrap().to_string_lossy().to_string();
+    let text = std::fs::read_to_string(&txt).unwrap();
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    for (algorithm, label) in [
+      (cli_justify::Algorithm::Greedy, "greedy"),
+      (cli_justify::Algorithm::Optimal, "optimal"),
+    ] {
+      for hyphenation in [None, Some(hyphenation)] {
+        let settings = cli_justify::Settings {
+          hyphenation,
+          algorithm,
+          ..Default::default()
+        };
+        let label = match hyphenation {
+          Some(_) => format!("{label}-hyphenated"),
+          None => label.to_string(),
+        };
+
+        group.bench_function(format!("{label}/{name}"), |b| {
+          b.iter(|| cli_justify::justify_with(&text, 80, &settings))
+        });
+      }
+    }
+  }
+
+  group.finish();
+}
+
+criterion_group!(benches, bench_justify);
+criterion_main!(benches);
@@ -0,0 +1,125 @@
+use crate::segments;
+
+/// How the lines of a paragraph are set between the margins. Lines are
+/// kept in logical order, so for right-to-left paragraphs left and right
+/// are swapped, `Left` is flush with the start of the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Alignment {
+  /// Flush with both margins, the last line of a paragraph like `Left`
+  #[default]
+  Justified,
+  /// Ragged right
+  Left,
+  /// Ragged left
+  Right,
+  Center,
+}
+
+/// What a line of the text is, which tells how it is aligned.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineKind {
+  /// A line of a paragraph, other than its last
+  #[default]
+  Fill,
+  /// The last line of a paragraph, never stretched
+  Last,
+  /// A row of a table, a line of code or the blank line between
+  /// paragraphs, drawn as it is
+  Fixed,
+}
+
+/// A line broken from the text before it is aligned.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Line {
+  /// Drawn before the text and never stretched, the bullet of a list item
+  /// or the indentation of the lines after it, and `> ` in quotes
+  pub prefix: String,
+  /// The words of the line with a single space between them, or the line
+  /// as it is drawn for `LineKind::Fixed`
+  pub text: String,
+  pub kind: LineKind,
+  /// The line is in a right-to-left paragraph
+  pub rtl: bool,
+  /// Where the text comes from, the byte offset in `text` each word or part
+  /// of a word starts at with the byte offset in the source text it starts
+  /// at there. Empty for the blank lines between paragraphs.
+  pub spans: Vec<(usize, usize)>,
+  /// The paragraph of the source text the line belongs to, counted from 0
+  pub paragraph: usize,
+}
+
+/// The characters of the line aligned to `line_width` columns, each with
+/// the byte offset in `line.text` it comes from. The spaces added between
+/// words have the offset of the space they widen, the prefix and the spaces
+/// added around the text the length of the text.
+pub fn align_order(
+  line: &Line,
+  line_width: usize,
+  alignment: Alignment,
+) -> Vec<(usize, char)> {
+  let text = &line.text;
+  let prefix = line.prefix.chars().map(|c| (text.len(), c));
+  let line_width = line_width.saturating_sub(segments::width(&line.prefix));
+  prefix.chain(align_text(line, line_width, alignment)).collect()
+}
+
+fn align_text(
+  line: &Line,
+  line_width: usize,
+  alignment: Alignment,
+) -> Vec<(usize, char)> {
+  let text = &line.text;
+  let chars = text.char_indices();
+  let padding = line_width.saturating_sub(segments::width(text));
+  let pad = |count: usize| std::iter::repeat_n((text.len(), ' '), count);
+
+  // Trailing spaces are only needed to push right-to-left text over
+  let end = |count: usize| match line.rtl {
+    true => count,
+    false => 0,
+  };
+
+  match (line.kind, alignment) {
+    (LineKind::Fixed, _) => chars.collect(),
+    (LineKind::Fill, Alignment::Justified) => justify_order(text, padding),
+    (LineKind::Last, Alignment::Justified) | (_, Alignment::Left) => {
+      chars.chain(pad(end(padding))).collect()
+    }
+    (_, Alignment::Right) => pad(padding).chain(chars).collect(),
+    (_, Alignment::Center) => pad(padding / 2)
+      .chain(chars)
+      .chain(pad(end(padding - padding / 2)))
+      .collect(),
+  }
+}
+
+/// The line aligned to `line_width` columns, see `align_order`.
+pub fn align(line: &Line, line_width: usize, alignment: Alignment) -> String {
+  align_order(line, line_width, alignment).into_iter().map(|x| x.1).collect()
+}
+
+/// Widens the spaces between the words by `padding` columns, the first
+/// ones by a column more than the rest. Lines of CJK text without spaces
+/// are left as they are.
+fn justify_order(text: &str, padding: usize) -> Vec<(usize, char)> {
+  let gaps = text.matches(' ').count();
+  if (gaps == 0) {
+    return text.char_indices().collect();
+  }
+
+  let each_space = padding / gaps;
+  let extra_space = padding % gaps;
+
+  let mut order = Vec::with_capacity(text.len() + padding);
+  let mut gap = 0;
+  for (i, c) in text.char_indices() {
+    order.push((i, c));
+    if (c == ' ') {
+      let count = each_space + (gap < extra_space) as usize;
+      order.extend(std::iter::repeat_n((i, ' '), count));
+      gap += 1;
+    }
+  }
+
+  order
+}
@@ -0,0 +1,79 @@
+use unicode_bidi::{bidi_class, BidiClass, Level, ParagraphBidiInfo};
+
+/// Whether the text is right-to-left, like Arabic or Hebrew, decided by its
+/// first letter with a strong direction as the Unicode bidi algorithm does.
+pub fn is_rtl(text: &str) -> bool {
+  text
+    .chars()
+    .map(bidi_class)
+    .find(|x| matches!(x, BidiClass::L | BidiClass::R | BidiClass::AL))
+    .is_some_and(|x| x != BidiClass::L)
+}
+
+/// Whether the line has characters that are not drawn left to right.
+pub fn has_rtl(line: &str) -> bool {
+  line.chars().any(|x| {
+    matches!(
+      bidi_class(x),
+      BidiClass::R
+        | BidiClass::AL
+        | BidiClass::AN
+        | BidiClass::RLE
+        | BidiClass::RLO
+        | BidiClass::RLI
+    )
+  })
+}
+
+/// The characters of a line in the order they are drawn from left to right,
+/// each with its byte offset in the line. `rtl` is the direction of the
+/// paragraph of the line, brackets in right-to-left runs are mirrored.
+pub fn visual_order(line: &str, rtl: bool) -> Vec<(usize, char)> {
+  if line.is_empty() {
+    return Vec::new();
+  }
+
+  let level = match rtl {
+    true => Level::rtl(),
+    false => Level::ltr(),
+  };
+  let info = ParagraphBidiInfo::new(line, Some(level));
+  let (levels, runs) = info.visual_runs(0..line.len());
+
+  let mut order = Vec::with_capacity(line.len());
+  for run in runs {
+    let chars =
+      line[run.clone()].char_indices().map(|(i, c)| (run.start + i, c));
+
+    match levels[run.start].is_rtl() {
+      true => order.extend(chars.rev().map(|(i, c)| (i, mirror(c)))),
+      false => order.extend(chars),
+    }
+  }
+
+  order
+}
+
+/// The line as it is drawn from left to right.
+pub fn to_visual(line: &str, rtl: bool) -> String {
+  visual_order(line, rtl).into_iter().map(|x| x.1).collect()
+}
+
+/// The mirrored glyph of brackets, drawn in a right-to-left run.
+fn mirror(c: char) -> char {
+  match c {
+    '(' => ')',
+    ')' => '(',
+    '[' => ']',
+    ']' => '[',
+    '{' => '}',
+    '}' => '{',
+    '<' => '>',
+    '>' => '<',
+    '«' => '»',
+    '»' => '«',
+    '‹' => '›',
+    '›' => '‹',
+    c => c,
+  }
+}
@@ -0,0 +1,263 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::{segments, table};
+
+/// Lines of verse are at most this many columns wide, prose broken into
+/// narrow columns is wider.
+const VERSE_MAX_WIDTH: usize = 40;
+
+/// A few short lines are as likely a narrow column of prose, verse has a
+/// stanza of at least this many.
+const VERSE_MIN_LINES: usize = 4;
+
+/// Marks of code that prose has no use for.
+const CODE_MARKS: [&str; 7] = ["{", "}", "=", "//", "::", "->", "</"];
+
+/// Columns a tab in code is expanded to.
+const TAB_WIDTH: usize = 4;
+
+/// Abbreviations that start sentences and look like the number of a list.
+const ABBREVIATIONS: [&str; 12] = [
+  "e.g.", "i.e.", "cf.", "vs.", "etc.", "ca.", "p.", "pp.", "no.", "vol.",
+  "fig.", "u.s.",
+];
+
+/// The digits of lowercase roman numerals below 400, hundreds first, each
+/// tried longest first.
+const ROMAN_DIGITS: [&[&str]; 3] = [
+  &["ccc", "cc", "c"],
+  &["xc", "xl", "lxxx", "lxx", "lx", "l", "xxx", "xx", "x"],
+  &["ix", "iv", "viii", "vii", "vi", "v", "iii", "ii", "i"],
+];
+
+/// What a paragraph is, which tells how it is laid out.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Block<'a> {
+  /// Lines like `| a | b |`, drawn as a grid
+  Table(Vec<Vec<String>>),
+  /// Indented or fenced lines, kept as they are
+  Code(Vec<&'a str>),
+  /// Short lines that each start a line of their own
+  Verse(Vec<&'a str>),
+  /// Items that start with a bullet or a number
+  List(Vec<Item<'a>>),
+  /// Lines that start with `>`, the text inside without them
+  Quote(Vec<&'a str>),
+  Prose(&'a str),
+}
+
+/// An item of a list.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Item<'a> {
+  /// The columns the item is indented by, for nested lists
+  pub indent: usize,
+  /// The bullet or number
+  pub label: &'a str,
+  /// The text of each line of the item without the label and indentation
+  pub lines: Vec<&'a str>,
+}
+
+pub(crate) fn classify(paragraph: &str) -> Block<'_> {
+  let lines: Vec<&str> =
+    paragraph.lines().map(str::trim_end).skip_while(|x| x.is_empty()).collect();
+  let content = || lines.iter().filter(|x| !x.is_empty());
+
+  if let Some(rows) = table::parse_table(paragraph) {
+    return Block::Table(rows);
+  }
+
+  if lines.first().is_some_and(|x| x.trim_start().starts_with("```")) {
+    return Block::Code(lines);
+  }
+  if is_indented_code(&content().copied().collect::<Vec<_>>()) {
+    return Block::Code(content().copied().collect());
+  }
+
+  if (content().count() > 0 && content().all(|x| quote_text(x).is_some())) {
+    return Block::Quote(lines.iter().filter_map(|x| quote_text(x)).collect());
+  }
+
+  if is_list(&lines) {
+    return Block::List(items(&lines));
+  }
+
+  if is_verse(&lines) {
+    return Block::Verse(lines.into_iter().filter(|x| !x.is_empty()).collect());
+  }
+
+  Block::Prose(paragraph)
+}
+
+/// Every line is indented, and either indented further than the others or
+/// has marks of code. The paragraphs of PDFs are often indented as a whole.
+fn is_indented_code(lines: &[&str]) -> bool {
+  let indented = |x: &&str| x.starts_with('\t') || x.starts_with("    ");
+  if (lines.is_empty() || !lines.iter().all(indented)) {
+    return false;
+  }
+
+  let indents: Vec<usize> = lines
+    .iter()
+    .map(|x| {
+      segments::width(&expand_tabs(&x[..x.len() - x.trim_start().len()]))
+    })
+    .collect();
+  let nested = indents.iter().any(|&x| x != indents[0]);
+
+  nested || lines.iter().any(|x| CODE_MARKS.iter().any(|&m| x.contains(m)))
+}
+
+/// The line of code with its tabs expanded to spaces.
+pub(crate) fn expand_tabs(line: &str) -> String {
+  let mut expanded = String::with_capacity(line.len());
+
+  for c in line.chars() {
+    match c {
+      '\t' => {
+        let width = segments::width(&expanded);
+        expanded.push_str(&" ".repeat(TAB_WIDTH - width % TAB_WIDTH));
+      }
+      c => expanded.push(c),
+    }
+  }
+
+  expanded
+}
+
+/// The byte offset in `line` of byte `at` of the line with its tabs
+/// expanded, the spaces of a tab are all at the tab.
+pub(crate) fn unexpanded_offset(line: &str, at: usize) -> usize {
+  let mut width = 0;
+  let mut expanded = 0;
+
+  for (i, c) in line.char_indices() {
+    let (columns, len) = match c {
+      '\t' => (TAB_WIDTH - width % TAB_WIDTH, TAB_WIDTH - width % TAB_WIDTH),
+      c => (c.width().unwrap_or(0), c.len_utf8()),
+    };
+    if (expanded + len > at) {
+      return i;
+    }
+    width += columns;
+    expanded += len;
+  }
+
+  line.len()
+}
+
+/// The text of a quoted line without its `>`, `None` for other lines.
+fn quote_text(line: &str) -> Option<&str> {
+  let text = line.trim_start().strip_prefix('>')?;
+
+  Some(text.strip_prefix(' ').unwrap_or(text))
+}
+
+/// A stanza of short lines that each start with a capital, or that start
+/// a sentence or follow the end of one and are ragged. Prose broken into
+/// lines continues in the middle of its sentences, and a narrow column of
+/// it fills every line but the last.
+fn is_verse(lines: &[&str]) -> bool {
+  let lines: Vec<&str> =
+    lines.iter().map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+  if (lines.len() < VERSE_MIN_LINES) {
+    return false;
+  }
+
+  let widths: Vec<usize> = lines.iter().map(|x| segments::width(x)).collect();
+  let widest = widths.iter().copied().max().unwrap_or(0);
+  if (widest > VERSE_MAX_WIDTH || lines.iter().any(|x| x.ends_with('-'))) {
+    return false;
+  }
+
+  let capital = |x: &str| x.chars().next().is_some_and(char::is_uppercase);
+  let ragged = widths[..widths.len() - 1].iter().any(|&x| x * 4 < widest * 3);
+  let starts = lines.windows(2).all(|pair| {
+    capital(pair[1]) || pair[0].ends_with([',', '.', ';', ':', '!', '?'])
+  });
+
+  lines.iter().all(|x| capital(x)) || ragged && starts
+}
+
+/// The first line starts with a label. A letter and a dot is an initial as
+/// well, like `A. Smith`, a list numbered with them has more than one item.
+fn is_list(lines: &[&str]) -> bool {
+  let Some((_, first)) = lines.first().and_then(|x| label(x)) else {
+    return false;
+  };
+  let lettered = first.ends_with('.') && first.chars().any(char::is_alphabetic);
+
+  !lettered || lines[1..].iter().any(|x| label(x).is_some())
+}
+
+/// Whether `x` is a lowercase roman numeral below 400, like `xiv`.
+fn is_roman(x: &str) -> bool {
+  let mut rest = x;
+  for digits in ROMAN_DIGITS {
+    if let Some(digit) = digits.iter().find(|&&d| rest.starts_with(d)) {
+      rest = &rest[digit.len()..];
+    }
+  }
+
+  !x.is_empty() && rest.is_empty()
+}
+
+/// The bullet or number a list item starts with and the columns it is
+/// indented by.
+fn label(line: &str) -> Option<(usize, &str)> {
+  let text = line.trim_start();
+  let indent = segments::width(&line[..line.len() - text.len()]);
+  let (label, rest) = text.split_once(' ')?;
+  if rest.trim().is_empty() {
+    return None;
+  }
+
+  if matches!(label, "•" | "◦" | "▪" | "‣" | "·" | "–" | "-" | "*" | "+")
+  {
+    return Some((indent, label));
+  }
+
+  // Numbers need their punctuation, a line may start with a year
+  let inner = match label.strip_prefix('(') {
+    Some(x) => x.strip_suffix(')')?,
+    None => label.strip_suffix(['.', ')'])?,
+  };
+  if (inner.is_empty()
+    || inner.len() > 8
+    || ABBREVIATIONS.contains(&label.to_lowercase().as_str()))
+  {
+    return None;
+  }
+
+  // Like `2.1` or `a`, one part with letters at most: `e.g.` and `U.S.`
+  // have two
+  let digits =
+    |x: &&str| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit());
+  let letters: Vec<&str> = inner.split('.').filter(|x| !digits(x)).collect();
+  let is_label = match letters[..] {
+    [] => true,
+    [x] => {
+      x.chars().count() == 1 && x.chars().all(char::is_alphabetic)
+        || is_roman(x)
+    }
+    _ => false,
+  };
+  is_label.then_some((indent, label))
+}
+
+/// The items of a list, a line without a label continues the item before.
+fn items<'a>(lines: &[&'a str]) -> Vec<Item<'a>> {
+  let mut items: Vec<Item> = Vec::new();
+
+  for &line in lines {
+    match (label(line), items.last_mut()) {
+      (Some((indent, label)), _) => {
+        let text = line.trim_start()[label.len()..].trim();
+        items.push(Item { indent, label, lines: vec![text] });
+      }
+      (None, Some(item)) => item.lines.push(line.trim()),
+      (None, None) => {}
+    }
+  }
+
+  items
+}
@@ -0,0 +1,159 @@
+use std::ops::Range;
+
+use crate::segments::Segment;
+
+/// How the segments of a paragraph are broken into lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Algorithm {
+  /// Fills each line with as many segments as fit
+  #[default]
+  Greedy,
+  /// Chooses the breaks that space the whole paragraph most evenly, after
+  /// the total fit algorithm of Knuth and Plass
+  Optimal,
+}
+
+/// Demerits of every line, keeps the number of lines down.
+const LINE_PENALTY: f64 = 10.0;
+
+/// Penalty of breaking a word with a hyphen.
+const HYPHEN_PENALTY: f64 = 50.0;
+
+/// Demerits of two hyphenated lines in a row.
+const DOUBLE_HYPHEN_DEMERITS: f64 = 3000.0;
+
+/// Demerits of a line whose spacing is very different from the line before.
+const ADJACENT_DEMERITS: f64 = 10000.0;
+
+/// The segments of each line.
+pub(crate) fn break_lines(
+  segments: &[Segment],
+  line_width: usize,
+  algorithm: Algorithm,
+) -> Vec<Range<usize>> {
+  match algorithm {
+    Algorithm::Greedy => greedy(segments, line_width),
+    Algorithm::Optimal => optimal(segments, line_width),
+  }
+}
+
+fn greedy(segments: &[Segment], line_width: usize) -> Vec<Range<usize>> {
+  let mut lines = Vec::new();
+  let mut start = 0;
+  let mut len = 0;
+
+  for (i, segment) in segments.iter().enumerate() {
+    let space = (segment.space && i > start) as usize;
+    let hyphen = segment.hyphen as usize;
+    if (len + space + segment.width + hyphen > line_width && i > start) {
+      lines.push(start..i);
+      start = i;
+      len = 0;
+    }
+    len += (segment.space && i > start) as usize + segment.width;
+  }
+
+  if (start < segments.len()) {
+    lines.push(start..segments.len());
+  }
+
+  lines
+}
+
+/// How loose a line is, lines can only be stretched in a terminal.
+fn fitness(ratio: f64) -> usize {
+  match ratio {
+    x if x <= 0.5 => 0,
+    x if x <= 1.0 => 1,
+    _ => 2,
+  }
+}
+
+/// The best way found to break the paragraph before a segment, with a line
+/// of one fitness class ending there.
+#[derive(Debug, Clone, Copy)]
+struct Breakpoint {
+  demerits: f64,
+  /// The segment the line ending here starts at
+  start: usize,
+  /// The fitness class of the line before
+  previous: usize,
+}
+
+/// Every gap between segments is a possible break, the breaks are chosen so
+/// the sum of the demerits of the lines is the least. The spaces of a line
+/// stretch by one column each before the line counts as loose, the last
+/// line is never stretched.
+fn optimal(segments: &[Segment], line_width: usize) -> Vec<Range<usize>> {
+  let count = segments.len();
+  if (count == 0) {
+    return Vec::new();
+  }
+
+  // The best breakpoint before each segment for each fitness class
+  let mut best: Vec<[Option<Breakpoint>; 3]> = vec![[None; 3]; count + 1];
+  best[0][0] = Some(Breakpoint { demerits: 0.0, start: 0, previous: 0 });
+
+  for end in 1..=count {
+    let last = end == count;
+    let hyphen = segments[end - 1].hyphen && !last;
+    let mut width = hyphen as usize;
+    let mut gaps = 0;
+
+    for start in (0..end).rev() {
+      width += segments[start].width;
+      if (start + 1 < end && segments[start + 1].space) {
+        width += 1;
+        gaps += 1;
+      }
+      // A single segment wider than the line still needs a line
+      if (width > line_width && start + 1 < end) {
+        break;
+      }
+
+      let slack = line_width.saturating_sub(width) as f64;
+      let ratio = match last {
+        true => 0.0,
+        false => slack / gaps.max(1) as f64,
+      };
+      // Unlike in TeX the badness is not capped, very loose lines can not
+      // always be avoided in a terminal and still differ in how bad they are
+      let badness = 100.0 * ratio.powi(3);
+      let fit = fitness(ratio);
+      let mut demerits = (LINE_PENALTY + badness).powi(2);
+      if hyphen {
+        demerits += HYPHEN_PENALTY.powi(2);
+        if (start > 0 && segments[start - 1].hyphen) {
+          demerits += DOUBLE_HYPHEN_DEMERITS;
+        }
+      }
+
+      for previous in 0..3 {
+        let Some(before) = best[start][previous] else { continue };
+        let mut total = before.demerits + demerits;
+        if (start > 0 && previous.abs_diff(fit) > 1) {
+          total += ADJACENT_DEMERITS;
+        }
+
+        let slot = &mut best[end][fit];
+        if slot.is_none_or(|x| total < x.demerits) {
+          *slot = Some(Breakpoint { demerits: total, start, previous });
+        }
+      }
+    }
+  }
+
+  let demerits = |i: usize| best[count][i].map_or(f64::MAX, |x| x.demerits);
+  let mut fit = (0..3).min_by(|&a, &b| demerits(a).total_cmp(&demerits(b)));
+
+  let mut lines = Vec::new();
+  let mut end = count;
+  while let Some(breakpoint) = fit.and_then(|x| best[end][x]) {
+    lines.push(breakpoint.start..end);
+    end = breakpoint.start;
+    fit = (end > 0).then_some(breakpoint.previous);
+  }
+  lines.reverse();
+
+  lines
+}
@@ -0,0 +1,64 @@
+/// Why the justify CLI could not run.
+#[derive(Debug)]
+pub enum JustifyError {
+  Io(std::io::Error),
+  InvalidArguments(getopts::Fail),
+  /// The column is not a number greater than zero
+  InvalidWidth(String),
+  /// The line breaking algorithm is neither greedy nor optimal
+  InvalidAlgorithm(String),
+  /// The alignment is not one of justified, left, right or center
+  InvalidAlignment(String),
+  /// There are no hyphenation patterns for the language
+  UnknownLanguage(String),
+}
+
+impl std::fmt::Display for JustifyError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      JustifyError::Io(e) => write!(f, "{e}"),
+      JustifyError::InvalidArguments(e) => write!(f, "{e}"),
+      JustifyError::InvalidWidth(width) => {
+        write!(f, "invalid column '{width}', expected a number greater than 0")
+      }
+      JustifyError::InvalidAlgorithm(algorithm) => {
+        write!(f, "invalid algorithm '{algorithm}', expected greedy or optimal")
+      }
+      JustifyError::InvalidAlignment(alignment) => write!(
+        f,
+        "invalid alignment '{alignment}', expected justified, left, right or center"
+      ),
+      JustifyError::UnknownLanguage(lang) => {
+        write!(
+          f,
+          "no hyphenation patterns for '{lang}', expected a code like en"
+        )
+      }
+    }
+  }
+}
+
+impl std::error::Error for JustifyError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      JustifyError::Io(e) => Some(e),
+      JustifyError::InvalidArguments(e) => Some(e),
+      JustifyError::InvalidWidth(_) => None,
+      JustifyError::InvalidAlgorithm(_) => None,
+      JustifyError::InvalidAlignment(_) => None,
+      JustifyError::UnknownLanguage(_) => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for JustifyError {
+  fn from(e: std::io::Error) -> Self {
+    JustifyError::Io(e)
+  }
+}
+
+impl From<getopts::Fail> for JustifyError {
+  fn from(e: getopts::Fail) -> Self {
+    JustifyError::InvalidArguments(e)
+  }
+}
@@ -0,0 +1,64 @@
+use hypher::Lang;
+
+use crate::JustifyError;
+
+/// Breaks words between their syllables, found with the TeX hyphenation
+/// patterns of a language after Liang's algorithm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hyphenation {
+  lang: Lang,
+  /// The fewest letters left before a hyphen
+  pub left_min: usize,
+  /// The fewest letters carried over to the next line
+  pub right_min: usize,
+}
+
+impl Hyphenation {
+  /// Hyphenation for the language given as its ISO 639-1 code, like `en`,
+  /// `de` or `pt-BR`, with the usual fragment lengths of the language.
+  pub fn new(lang: &str) -> Result<Self, JustifyError> {
+    let code = lang.trim().split(['-', '_']).next().unwrap_or_default();
+    let code: [u8; 2] = code
+      .to_ascii_lowercase()
+      .into_bytes()
+      .try_into()
+      .map_err(|_| JustifyError::UnknownLanguage(lang.to_string()))?;
+    let lang = Lang::from_iso(code)
+      .ok_or_else(|| JustifyError::UnknownLanguage(lang.to_string()))?;
+    let (left_min, right_min) = lang.bounds();
+
+    Ok(Self { lang, left_min: left_min.max(2), right_min: right_min.max(2) })
+  }
+
+  /// The byte offsets in `word` where it may be broken with a hyphen.
+  /// Punctuation around the word is kept with its first and last syllable,
+  /// words with digits or other signs in them are not broken.
+  pub fn breaks(&self, word: &str) -> Vec<usize> {
+    let start = word.find(char::is_alphabetic).unwrap_or(word.len());
+    let end = word.rfind(char::is_alphabetic).map_or(start, |i| {
+      i + word[i..].chars().next().map_or(0, char::len_utf8)
+    });
+    let letters = &word[start..end.max(start)];
+
+    if (letters.is_empty() || !letters.chars().all(char::is_alphabetic)) {
+      return Vec::new();
+    }
+
+    let syllables = hypher::hyphenate_bounded(
+      letters,
+      self.lang,
+      self.left_min,
+      self.right_min,
+    );
+    let mut breaks: Vec<usize> = syllables
+      .scan(start, |at, syllable| {
+        *at += syllable.len();
+        Some(*at)
+      })
+      .collect();
+    // The end of the last syllable is not a break
+    breaks.pop();
+
+    breaks
+  }
+}
@@ -1,78 +1,385 @@
-fn split_at_char(s: &str, n: usize) -> (&str, Option<&str>) {
-  for (char_index, (i, _)) in s.char_indices().enumerate() {
-    if char_index == n {
-      let (w1, w2) = s.split_at(i);
-      return (w1, Some(w2));
-    }
+mod align;
+mod bidi;
+mod blocks;
+mod breaking;
+mod error;
+mod hyphenate;
+mod position;
+mod segments;
+mod table;
+
+pub use align::{align, align_order, Alignment, Line, LineKind};
+pub use bidi::{has_rtl, is_rtl, to_visual, visual_order};
+pub use breaking::Algorithm;
+pub use error::JustifyError;
+pub use hyphenate::Hyphenation;
+pub use position::{find_line, source_position};
+
+use blocks::Block;
+use segments::Segment;
+
+/// Parses a column given on the command line, it has to be greater than 0.
+pub fn parse_width(width: &str) -> Result<usize, JustifyError> {
+  match width.parse() {
+    Ok(width) if width > 0 => Ok(width),
+    _ => Err(JustifyError::InvalidWidth(width.to_string())),
   }
+}
+
+/// Parses the line breaking algorithm given on the command line, `greedy`
+/// or `optimal`.
+pub fn parse_algorithm(algorithm: &str) -> Result<Algorithm, JustifyError> {
+  match algorithm {
+    "greedy" => Ok(Algorithm::Greedy),
+    "optimal" => Ok(Algorithm::Optimal),
+    _ => Err(JustifyError::InvalidAlgorithm(algorithm.to_string())),
+  }
+}
+
+/// Parses the alignment given on the command line, `justified`, `left`,
+/// `right` or `center`.
+pub fn parse_alignment(alignment: &str) -> Result<Alignment, JustifyError> {
+  match alignment {
+    "justified" => Ok(Alignment::Justified),
+    "left" => Ok(Alignment::Left),
+    "right" => Ok(Alignment::Right),
+    "center" => Ok(Alignment::Center),
+    _ => Err(JustifyError::InvalidAlignment(alignment.to_string())),
+  }
+}
 
-  (s, None)
+/// How `justify_with` lays out the lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Settings {
+  /// Words are also broken between their syllables when set
+  pub hyphenation: Option<Hyphenation>,
+  pub algorithm: Algorithm,
+  pub alignment: Alignment,
 }
 
+/// Justifies `text` to `line_width` terminal columns, a width of 0 is
+/// treated as 1. Wide characters take two columns and combining marks none,
+/// lines are also broken between CJK characters, but not before closing or
+/// after opening punctuation. Paragraphs that are tables, lines like
+/// `| a | b |`, are drawn as a grid that fits the width instead. List items
+/// get a hanging indent and quotes keep their `>`, indented or fenced code
+/// is kept as it is and each line of verse starts a line of its own. The
+/// lines stay in logical order, the last line of a right-to-left paragraph
+/// is padded at its end so it is drawn flush right.
 pub fn justify(text: &str, line_width: usize) -> Vec<String> {
-  let paragraphs: Vec<&str> = text.split("\n\n").collect();
-  let mut lines: Vec<String> = Vec::new();
+  justify_with(text, line_width, &Settings::default())
+}
 
-  for paragraph in paragraphs {
-    let raw_words: Vec<&str> = paragraph.split_whitespace().collect();
-    let mut words = vec![];
+/// Justifies `text` like `justify` with the given settings.
+pub fn justify_with(
+  text: &str,
+  line_width: usize,
+  settings: &Settings,
+) -> Vec<String> {
+  layout(text, line_width, settings)
+    .iter()
+    .map(|x| align(x, line_width.max(1), settings.alignment))
+    .collect()
+}
 
-    for mut word in raw_words {
-      while let (w1, Some(w2)) = split_at_char(word, line_width) {
-        words.push(w1);
-        word = w2;
-      }
+/// Breaks `text` into lines like `justify_with` does, but leaves them to be
+/// aligned, so they can be aligned differently later. Each line knows where
+/// its text is in `text` and which paragraph it belongs to.
+pub fn layout(text: &str, line_width: usize, settings: &Settings) -> Vec<Line> {
+  layout_at(Source { text, offset: 0 }, line_width, settings, 0)
+}
+
+/// The text being laid out and where it starts in the source text.
+#[derive(Clone, Copy)]
+struct Source<'a> {
+  text: &'a str,
+  offset: usize,
+}
+
+impl Source<'_> {
+  /// The byte offset in the source text of `part`, a slice of the text.
+  fn offset_of(&self, part: &str) -> usize {
+    let start = self.text.as_ptr() as usize;
+    let at = part.as_ptr() as usize;
+    debug_assert!(
+      (start..=start + self.text.len()).contains(&at)
+        && at + part.len() <= start + self.text.len(),
+      "not a slice of the text being laid out"
+    );
+
+    self.offset + (at - start)
+  }
+}
+
+/// Lays out `source.text`, counting its paragraphs from `first_paragraph`.
+fn layout_at(
+  source: Source,
+  line_width: usize,
+  settings: &Settings,
+  first_paragraph: usize,
+) -> Vec<Line> {
+  let line_width = line_width.max(1);
+  let paragraphs: Vec<&str> = source.text.split("\n\n").collect();
+  let mut lines: Vec<Line> = Vec::new();
 
-      words.push(word);
+  for (i, paragraph) in paragraphs.into_iter().enumerate() {
+    let start = lines.len();
+    let rtl = bidi::is_rtl(paragraph);
+    layout_paragraph(paragraph, line_width, settings, rtl, source, &mut lines);
+
+    // Add a blank line after each paragraph to preserve paragraph breaks
+    lines.push(Line { kind: LineKind::Fixed, rtl, ..Line::default() });
+    for line in &mut lines[start..] {
+      line.paragraph = first_paragraph + i;
     }
+  }
+
+  lines
+}
 
-    let mut line: Vec<&str> = Vec::new();
-    let mut len = 0;
+fn layout_paragraph(
+  paragraph: &str,
+  line_width: usize,
+  settings: &Settings,
+  rtl: bool,
+  source: Source,
+  lines: &mut Vec<Line>,
+) {
+  let fixed = |text: String, from: usize| Line {
+    text,
+    kind: LineKind::Fixed,
+    rtl,
+    spans: vec![(0, from)],
+    ..Line::default()
+  };
 
-    for word in words {
-      if len + word.len() > line_width {
-        lines.push(justify_line(&line, line_width));
-        line.clear();
-        len = 0;
+  match blocks::classify(paragraph) {
+    Block::Table(rows) => {
+      // The grid is drawn anew, its rows all map to the start of the table
+      let from = source.offset_of(paragraph);
+      let rows = table::render_table(&rows, line_width);
+      lines.extend(rows.into_iter().map(|x| fixed(x, from)));
+    }
+    Block::Code(code) => {
+      for line in code {
+        let from = source.offset_of(line);
+        let expanded = blocks::expand_tabs(line);
+        let mut rest = expanded.as_str();
+
+        // Code is cut where it overflows the width, never reflowed
+        loop {
+          let at = expanded.len() - rest.len();
+          let start = blocks::unexpanded_offset(line, at);
+          let (part, next) = segments::split_at_width(rest, line_width);
+          lines.push(fixed(part.to_string(), from + start));
+          match next {
+            Some(next) => rest = next,
+            None => break,
+          }
+        }
+      }
+    }
+    Block::Verse(verse) => {
+      for line in verse {
+        let fill =
+          Fill { first: String::new(), rest: "  ".to_string(), stretch: false };
+        fill.lay_out(&[line], line_width, settings, rtl, source, lines);
+      }
+    }
+    Block::List(items) => {
+      for item in items {
+        let indent = " ".repeat(item.indent.min(line_width / 2));
+        let first = format!("{indent}{} ", item.label);
+        let rest = " ".repeat(segments::width(&first));
+        let fill = Fill { first, rest, stretch: true };
+        fill.lay_out(&item.lines, line_width, settings, rtl, source, lines);
       }
-      line.push(word);
-      len += word.len() + 1; // +1 for space
     }
+    Block::Quote(quote) => {
+      let inner = quote.join("\n");
+      let line_width = line_width.saturating_sub(2).max(1);
+      let mut quoted = layout(&inner, line_width, settings);
+      // The blank line after the last paragraph is added for the quote
+      quoted.pop();
+
+      // Where each line of the quote starts in `inner`
+      let starts: Vec<usize> = quote
+        .iter()
+        .scan(0, |at, line| {
+          let start = *at;
+          *at += line.len() + 1;
+          Some(start)
+        })
+        .collect();
 
-    // Add the last line of the paragraph
-    if !line.is_empty() {
-      lines.push(line.join(" "));
+      for mut line in quoted {
+        // The words of the quote never span lines of it
+        for span in &mut line.spans {
+          let i = starts.partition_point(|&x| x <= span.1) - 1;
+          span.1 = source.offset_of(quote[i]) + span.1 - starts[i];
+        }
+        line.prefix.insert_str(0, "> ");
+        lines.push(line);
+      }
     }
+    Block::Prose(text) => {
+      let fill =
+        Fill { first: String::new(), rest: String::new(), stretch: true };
+      fill.lay_out(&[text], line_width, settings, rtl, source, lines);
+    }
+  }
+}
 
-    // Add a blank line after each paragraph to preserve paragraph breaks
-    lines.push(String::new());
+/// How the words of a paragraph are filled into lines.
+struct Fill {
+  /// The prefix of the first line
+  first: String,
+  /// The prefix of the other lines
+  rest: String,
+  /// Whether the lines are stretched to the width when justified
+  stretch: bool,
+}
+
+impl Fill {
+  /// Fills the words of `parts`, slices of `source.text`, into lines.
+  fn lay_out(
+    &self,
+    parts: &[&str],
+    line_width: usize,
+    settings: &Settings,
+    rtl: bool,
+    source: Source,
+    lines: &mut Vec<Line>,
+  ) {
+    let prefix_width =
+      segments::width(&self.first).max(segments::width(&self.rest));
+    let line_width = line_width.saturating_sub(prefix_width).max(1);
+    let hyphenation = settings.hyphenation.as_ref();
+
+    let segments: Vec<Segment> = parts
+      .iter()
+      .flat_map(|x| segments::segments(x, line_width, hyphenation))
+      .collect();
+    let ranges =
+      breaking::break_lines(&segments, line_width, settings.algorithm);
+    let count = ranges.len();
+
+    for (i, range) in ranges.into_iter().enumerate() {
+      let kind = match self.stretch && i + 1 < count {
+        true => LineKind::Fill,
+        false => LineKind::Last,
+      };
+      let prefix = match i {
+        0 => self.first.clone(),
+        _ => self.rest.clone(),
+      };
+      let (text, spans) = join(&segments[range], source);
+      let line = Line { prefix, text, kind, rtl, spans, ..Line::default() };
+      lines.push(line);
+    }
   }
+}
 
-  lines
+/// Justifies text that arrives in pieces, e.g. page by page. Lines are
+/// returned as soon as their paragraph is complete, and all of them together
+/// are exactly the lines `justify` returns for the concatenated text. Their
+/// source offsets and paragraphs count from the start of the first piece.
+pub struct Justifier {
+  line_width: usize,
+  settings: Settings,
+  pending: String,
+  /// Where `pending` starts in the text pushed so far
+  offset: usize,
+  /// The paragraphs before `pending`
+  paragraphs: usize,
 }
 
-fn justify_line(line: &[&str], line_width: usize) -> String {
-  let word_len: usize = line.iter().map(|s| s.len()).sum();
-  let spaces = line_width - word_len;
+impl Justifier {
+  pub fn new(line_width: usize) -> Self {
+    Self::with_settings(line_width, Settings::default())
+  }
 
-  let line_len_div = if (line.len() > 1) { (line.len() - 1) } else { 1 };
+  pub fn with_settings(line_width: usize, settings: Settings) -> Self {
+    Self {
+      line_width,
+      settings,
+      pending: String::new(),
+      offset: 0,
+      paragraphs: 0,
+    }
+  }
 
-  let each_space = spaces / line_len_div;
-  let extra_space = spaces % line_len_div;
+  /// Appends `text` and returns the lines of the paragraphs it completed.
+  pub fn push(&mut self, text: &str) -> Vec<String> {
+    let lines = self.push_lines(text);
+    self.align(lines)
+  }
 
-  let mut justified = String::new();
-  for (i, word) in line.iter().enumerate() {
-    justified.push_str(word);
-    if i < line.len() - 1 {
-      let mut space = " ".repeat(each_space);
-      if i < extra_space {
-        space.push(' ');
-      }
-      justified.push_str(&space);
+  /// Returns the lines of the last paragraph.
+  pub fn finish(self) -> Vec<String> {
+    let lines = self.layout(&self.pending);
+    self.align(lines)
+  }
+
+  /// Like `push`, but the lines are left to be aligned.
+  pub fn push_lines(&mut self, text: &str) -> Vec<Line> {
+    self.pending.push_str(text);
+
+    // The separators are matched left to right like `justify` splits, so the
+    // ones found so far stay the same however the text continues
+    let Some((end, separator)) = self.pending.match_indices("\n\n").last()
+    else {
+      return Vec::new();
+    };
+
+    let done = &self.pending[..end];
+    let lines = self.layout(done);
+    self.paragraphs += done.split("\n\n").count();
+    self.offset += end + separator.len();
+    self.pending.drain(..end + separator.len());
+
+    lines
+  }
+
+  /// Like `finish`, but the lines are left to be aligned.
+  pub fn finish_lines(self) -> Vec<Line> {
+    self.layout(&self.pending)
+  }
+
+  /// Lays out `text`, the start of `pending`.
+  fn layout(&self, text: &str) -> Vec<Line> {
+    let source = Source { text, offset: self.offset };
+    layout_at(source, self.line_width, &self.settings, self.paragraphs)
+  }
+
+  fn align(&self, lines: Vec<Line>) -> Vec<String> {
+    let line_width = self.line_width.max(1);
+    lines
+      .iter()
+      .map(|x| align(x, line_width, self.settings.alignment))
+      .collect()
+  }
+}
+
+/// The segments of a line with a single space where they had one, and a
+/// hyphen when it ends in the middle of a word, with where each segment
+/// starts in the joined text and in the source text.
+fn join(line: &[Segment], source: Source) -> (String, Vec<(usize, usize)>) {
+  let mut joined = String::new();
+  let mut spans = Vec::with_capacity(line.len());
+
+  for (i, segment) in line.iter().enumerate() {
+    if (i > 0 && segment.space) {
+      joined.push(' ');
     }
+    spans.push((joined.len(), source.offset_of(segment.text)));
+    joined.push_str(segment.text);
+  }
+  if line.last().is_some_and(|x| x.hyphen) {
+    joined.push('-');
   }
 
-  justified
+  (joined, spans)
 }
 
 #[cfg(test)]
@@ -85,4 +392,435 @@ mod tests {
     let pretty_short_line_width = 10;
     justify(input_text, pretty_short_line_width);
   }
+
+  #[test]
+  fn test_handles_zero_width_and_multibyte_words() {
+    justify("some text", 0);
+    justify("ééééé ééééé ééééé", 6);
+    assert!(parse_width("0").is_err());
+    assert!(parse_width("abc").is_err());
+    assert_eq!(parse_width("80").unwrap(), 80);
+  }
+
+  #[test]
+  fn test_tables_are_kept_as_a_grid() {
+    let text = "Some text\n\n| a | bb |\n| ccc | d\\|e |\n\nmore text";
+
+    assert_eq!(
+      justify(text, 40),
+      vec![
+        "Some text",
+        "",
+        "| a   | bb  |",
+        "| ccc | d|e |",
+        "",
+        "more text",
+        ""
+      ]
+    );
+  }
+
+  #[test]
+  fn test_wide_tables_are_wrapped_in_their_cells() {
+    let text = "| Name | A description that is much too long |\n| x | y |";
+    let lines = justify(text, 30);
+
+    assert!(lines.iter().all(|x| x.chars().count() <= 30));
+    assert_eq!(
+      lines,
+      vec![
+        "| Name | A description that  |",
+        "|      | is much too long    |",
+        "| x    | y                   |",
+        "",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_lines_are_measured_in_columns() {
+    assert_eq!(
+      justify("Привет мир как дела", 10),
+      vec!["Привет мир", "как дела", ""]
+    );
+    assert_eq!(
+      justify("cafe\u{301} au lait", 10),
+      vec!["cafe\u{301}    au", "lait", ""]
+    );
+  }
+
+  #[test]
+  fn test_cjk_is_broken_between_characters_with_kinsoku() {
+    assert_eq!(
+      justify("日本語の文章です。とても長い", 10),
+      vec!["日本語の文", "章です。と", "ても長い", ""]
+    );
+    // An opening bracket does not end a line
+    assert_eq!(justify("あいう「えお」", 8), vec!["あいう", "「えお」", ""]);
+  }
+
+  #[test]
+  fn test_words_are_hyphenated_between_syllables() {
+    let settings = Settings {
+      hyphenation: Some(Hyphenation::new("en-US").unwrap()),
+      ..Settings::default()
+    };
+    let lines = justify_with("a comprehensive hyphenation", 12, &settings);
+
+    assert_eq!(lines, vec!["a comprehen-", "sive hyphen-", "ation", ""]);
+    assert!(lines.iter().all(|x| segments::width(x) <= 12));
+    assert!(Hyphenation::new("xx").is_err());
+  }
+
+  #[test]
+  fn test_hyphenation_keeps_minimum_fragments_and_punctuation() {
+    let mut hyphenation = Hyphenation::new("en").unwrap();
+    assert_eq!(hyphenation.breaks("(hyphenation),"), vec![3, 7]);
+    assert!(hyphenation.breaks("abc123def").is_empty());
+
+    hyphenation.left_min = 4;
+    hyphenation.right_min = 5;
+    assert_eq!(hyphenation.breaks("hyphenation"), vec![6]);
+  }
+
+  #[test]
+  fn test_optimal_breaking_spreads_the_spaces_over_the_paragraph() {
+    let text = "one two three four five six seven eight nine ten eleven twelve";
+    let optimal =
+      Settings { algorithm: Algorithm::Optimal, ..Settings::default() };
+
+    assert_eq!(justify(text, 16)[3], "ten       eleven");
+    assert_eq!(
+      justify_with(text, 16, &optimal),
+      vec![
+        "one   two  three",
+        "four   five  six",
+        "seven      eight",
+        "nine  ten eleven",
+        "twelve",
+        "",
+      ]
+    );
+    assert!(parse_algorithm("fast").is_err());
+  }
+
+  #[test]
+  fn test_optimal_breaking_takes_hyphenation_points() {
+    let settings = Settings {
+      hyphenation: Some(Hyphenation::new("en").unwrap()),
+      algorithm: Algorithm::Optimal,
+      ..Settings::default()
+    };
+    let lines =
+      justify_with("a comprehensive hyphenation of words", 12, &settings);
+
+    assert!(lines.iter().any(|x| x.ends_with('-')));
+    assert!(lines.iter().all(|x| segments::width(x) <= 12));
+    let text: String = lines.concat().replace(['-', ' '], "");
+    assert_eq!(text, "acomprehensivehyphenationofwords");
+  }
+
+  #[test]
+  fn test_lines_are_aligned() {
+    let text = "one two three four five\n\n| a | b |";
+    let aligned = |alignment| {
+      let settings = Settings { alignment, ..Settings::default() };
+      justify_with(text, 14, &settings)
+    };
+
+    assert_eq!(
+      aligned(Alignment::Left),
+      vec!["one two three", "four five", "", "| a | b |", ""]
+    );
+    assert_eq!(
+      aligned(Alignment::Right),
+      vec![" one two three", "     four five", "", "| a | b |", ""]
+    );
+    assert_eq!(
+      aligned(Alignment::Center),
+      vec!["one two three", "  four five", "", "| a | b |", ""]
+    );
+    assert_eq!(aligned(Alignment::Justified), justify(text, 14));
+    assert!(parse_alignment("middle").is_err());
+  }
+
+  #[test]
+  fn test_aligned_characters_keep_their_offsets() {
+    let line = Line {
+      text: "ab cd".to_string(),
+      kind: LineKind::Fill,
+      ..Line::default()
+    };
+
+    let order = align_order(&line, 7, Alignment::Justified);
+    let offsets: Vec<usize> = order.iter().map(|x| x.0).collect();
+    assert_eq!(offsets, vec![0, 1, 2, 2, 2, 3, 4]);
+
+    let rtl = Line { kind: LineKind::Last, rtl: true, ..line };
+    assert_eq!(align(&rtl, 8, Alignment::Center), " ab cd  ");
+    assert_eq!(align(&rtl, 8, Alignment::Left), "ab cd   ");
+  }
+
+  #[test]
+  fn test_justifier_lines_are_aligned_later() {
+    let mut justifier = Justifier::new(10);
+    let mut lines = justifier.push_lines("some text across\n\n");
+    lines.extend(justifier.finish_lines());
+
+    let aligned: Vec<String> =
+      lines.iter().map(|x| align(x, 10, Alignment::Justified)).collect();
+    assert_eq!(aligned, justify("some text across\n\n", 10));
+    assert_eq!(
+      lines[0],
+      Line {
+        text: "some text".to_string(),
+        kind: LineKind::Fill,
+        spans: vec![(0, 0), (5, 5)],
+        ..Line::default()
+      }
+    );
+  }
+
+  #[test]
+  fn test_list_items_get_a_hanging_indent() {
+    let text = "• first item that wraps\naround\n  - nested\n\n12. numbered";
+
+    assert_eq!(
+      justify(text, 16),
+      vec![
+        "• first     item",
+        "  that     wraps",
+        "  around",
+        "  - nested",
+        "",
+        "12. numbered",
+        ""
+      ]
+    );
+    // A year is not a number of a list
+    assert_eq!(justify("1984 was a year", 20), vec!["1984 was a year", ""]);
+  }
+
+  #[test]
+  fn test_abbreviations_and_initials_are_not_numbers() {
+    for text in [
+      "e.g. this is prose\nthat goes on",
+      "i.e. this is prose\nthat goes on",
+      "U.S. forces were\nthere as well",
+      "A. Smith wrote a\nbook about lists",
+      "p. 12 shows the\nsame figure",
+      "ill. and more\nprose as well",
+    ] {
+      assert_eq!(justify(text, 40), vec![text.replace('\n', " "), "".into()]);
+    }
+
+    assert_eq!(
+      justify("a. first\nb. second", 40),
+      vec!["a. first", "b. second", ""]
+    );
+    assert_eq!(
+      justify("iv. fourth\nv. fifth\n\n(a) alone", 40),
+      vec!["iv. fourth", "v. fifth", "", "(a) alone", ""]
+    );
+    assert_eq!(justify("2.1. Section", 40), vec!["2.1. Section", ""]);
+  }
+
+  #[test]
+  fn test_code_and_quotes_are_kept() {
+    let code = "    let x = 1;\n    \tif x {  y  }";
+    assert_eq!(
+      justify(code, 40),
+      vec!["    let x = 1;", "        if x {  y  }", ""]
+    );
+    assert_eq!(justify("```\na   b\n```", 40), vec!["```", "a   b", "```", ""]);
+
+    let quote = "> a quoted line\n> that wraps\n>\n> - item";
+    assert_eq!(
+      justify(quote, 12),
+      vec!["> a   quoted", "> line  that", "> wraps", "> ", "> - item", ""]
+    );
+  }
+
+  #[test]
+  fn test_verse_keeps_its_lines() {
+    let verse =
+      "Roses are red,\nViolets are blue,\nSugar is sweet,\nAnd so are you.";
+    let settings =
+      Settings { alignment: Alignment::Center, ..Settings::default() };
+
+    assert_eq!(
+      justify(verse, 20),
+      vec![
+        "Roses are red,",
+        "Violets are blue,",
+        "Sugar is sweet,",
+        "And so are you.",
+        ""
+      ]
+    );
+    let ragged =
+      "I wandered lonely as a cloud\nthat floats on high o'er vale and hill,\n\
+      when all at once I saw a crowd,\na host of golden daffodils;";
+    assert_eq!(justify(ragged, 40).len(), 5);
+    assert_eq!(justify_with(verse, 20, &settings)[0], "   Roses are red,");
+
+    // Prose broken into short lines continues mid sentence
+    let prose = "some prose broken\ninto short lines";
+    assert_eq!(
+      justify(prose, 40),
+      vec!["some prose broken into short lines", ""]
+    );
+  }
+
+  /// Two columns of a PDF page as cli-pdf-to-text extracts them, the
+  /// paragraphs of the narrow right column break after punctuation.
+  const COLUMNS: &str = "The committee met in the spring of that\n\
+    year. Its members agreed, after some\n\
+    debate, that the old bridge could not be\n\
+    saved. A new one would be built instead.\n\n\
+    The bridge opened in May,\n\
+    after two years of work.\n\
+    Crowds lined the banks,\n\
+    and a band played.\n\n\
+    Traffic was light at first.\n\
+    Within a decade, however,\n\
+    it had doubled.";
+
+  #[test]
+  fn test_columns_of_prose_are_not_verse_or_code() {
+    let paragraphs = |text: &str| {
+      text
+        .split("\n\n")
+        .map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+    };
+    let reflowed = |text: &str| {
+      justify(text, 200)
+        .into_iter()
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(reflowed(COLUMNS), paragraphs(COLUMNS));
+
+    // Paragraphs indented as a whole, as PDFs often lay them out
+    let indented: String = COLUMNS
+      .split('\n')
+      .map(|x| if x.is_empty() { String::new() } else { format!("    {x}") })
+      .collect::<Vec<_>>()
+      .join("\n");
+    assert_eq!(reflowed(&indented), paragraphs(COLUMNS));
+  }
+
+  #[test]
+  fn test_rtl_paragraphs_are_flush_right() {
+    let lines = justify("שלום עולם\n\nhello world", 20);
+
+    assert_eq!(lines, vec!["שלום עולם           ", "", "hello world", ""]);
+    assert!(is_rtl("123 שלום world"));
+    assert!(!is_rtl("world שלום"));
+  }
+
+  #[test]
+  fn test_visual_order_reverses_rtl_runs() {
+    assert_eq!(to_visual("abc אבג def", false), "abc גבא def");
+    assert_eq!(to_visual("abc אבג (1)", false), "abc (1) גבא");
+    assert_eq!(to_visual("אבג abc (דה)", true), "(הד) abc גבא");
+    assert_eq!(to_visual("שלום   ", true), "   םולש");
+    assert_eq!(to_visual("", true), "");
+
+    let order = visual_order("a אב", false);
+    assert_eq!(order, vec![(0, 'a'), (1, ' '), (4, 'ב'), (2, 'א')]);
+  }
+
+  #[test]
+  fn test_justifier_matches_justify() {
+    let pieces =
+      ["Some text\n", "\n\nacross pa", "ges\n\n", "\n", "\nand the end\n\n"];
+
+    let mut justifier = Justifier::new(10);
+    let mut lines = vec![];
+    for piece in pieces {
+      lines.extend(justifier.push(piece));
+    }
+    lines.extend(justifier.finish());
+
+    assert_eq!(lines, justify(&pieces.concat(), 10));
+  }
+
+  #[test]
+  fn test_lines_map_back_to_the_source() {
+    let text = "A  paragraph of\nprose here\n\n- an item\n  going on\n\n\
+                > quoted words\n> and more\n\n    x = a\tline";
+    let lines = layout(text, 12, &Settings::default());
+
+    for line in &lines {
+      for (at, c) in line.text.char_indices().filter(|x| x.1 != ' ') {
+        let source = line.source_offset(at).unwrap();
+        assert!(text[source..].starts_with(c), "{line:?} at {at}");
+      }
+    }
+
+    let paragraphs: Vec<usize> = lines.iter().map(|x| x.paragraph).collect();
+    assert_eq!(paragraphs, vec![0, 0, 0, 0, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3]);
+    assert_eq!(lines[3].source_offset(0), None);
+    assert_eq!(lines[8].text, "words and");
+    assert_eq!(lines[8].source_offset(6), text.find("and"));
+    assert_eq!(lines[12].text, "line");
+    assert_eq!(lines[12].source_offset(0), text.find("line"));
+  }
+
+  #[test]
+  fn test_positions_survive_justifying_anew() {
+    let text = "Some words to find again after the text is justified to \
+                another width.\n\nAnd a second paragraph.";
+    let source = text.find("justified").unwrap();
+
+    let narrow = layout(text, 16, &Settings::default());
+    let i = find_line(&narrow, source).unwrap();
+    let at = narrow[i].text_offset(source).unwrap();
+    assert!(narrow[i].text[at..].starts_with("justified"));
+
+    // Columns count the spaces the line is stretched by
+    let column = align(&narrow[i], 16, Alignment::Justified).find('j');
+    let found =
+      source_position(&narrow[i], 16, Alignment::Justified, column.unwrap());
+    assert_eq!(found, Some(source));
+    assert_eq!(source_position(&narrow[i], 40, Alignment::Right, 0), None);
+
+    let wide = layout(text, 30, &Settings::default());
+    let i = find_line(&wide, source).unwrap();
+    let at = wide[i].text_offset(source).unwrap();
+    assert!(wide[i].text[at..].starts_with("justified"));
+  }
+
+  #[test]
+  fn test_hyphenated_words_map_to_their_syllables() {
+    let settings = Settings {
+      hyphenation: Some(Hyphenation::new("en").unwrap()),
+      ..Settings::default()
+    };
+    let text = "a comprehensive hyphenation";
+    let lines = layout(text, 12, &settings);
+
+    assert_eq!(lines[1].text, "sive hyphen-");
+    assert_eq!(lines[1].source_offset(0), text.find("sive"));
+    assert_eq!(lines[1].source_offset(11), text.find("ation"));
+    assert_eq!(find_line(&lines, text.find("ation").unwrap()), Some(2));
+  }
+
+  #[test]
+  fn test_justifier_counts_from_the_first_piece() {
+    let pieces =
+      ["Some text\n", "\n\nacross pa", "ges\n\n", "\n", "\nand the end"];
+
+    let mut justifier = Justifier::new(10);
+    let mut lines = vec![];
+    for piece in pieces {
+      lines.extend(justifier.push_lines(piece));
+    }
+    lines.extend(justifier.finish_lines());
+
+    assert_eq!(lines, layout(&pieces.concat(), 10, &Settings::default()));
+  }
 }
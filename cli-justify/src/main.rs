@@ -1,16 +1,44 @@
 use std::{
   env,
-  io::{self, BufRead},
+  io::{self, Read},
 };
 
-use cli_justify::justify;
+use cli_justify::{
+  justify_with, parse_algorithm, parse_alignment, parse_width, Algorithm,
+  Alignment, Hyphenation, JustifyError, Settings,
+};
 use getopts::Options;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+  if let Err(e) = run() {
+    eprintln!("error: {e}");
+    std::process::exit(1);
+  }
+}
+
+fn run() -> Result<(), JustifyError> {
   let args: Vec<String> = env::args().collect();
   let mut opts = Options::new();
 
   opts.optopt("c", "col", "set the column, defaults to 110", "NUMBER");
+  opts.optopt(
+    "",
+    "align",
+    "justified, left, right or center, defaults to justified",
+    "ALIGNMENT",
+  );
+  opts.optopt(
+    "",
+    "algorithm",
+    "greedy or optimal, which spaces whole paragraphs evenly, defaults to greedy",
+    "NAME",
+  );
+  opts.optopt(
+    "",
+    "hyphenate",
+    "break words between syllables with the patterns of a language, e.g. en",
+    "LANG",
+  );
   opts.optflag("h", "help", "print this help menu");
 
   let matches = opts.parse(&args[1..])?;
@@ -22,13 +50,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
   }
 
   let col: usize = match matches.opt_str("c") {
-    Some(x) => x.parse().unwrap_or(110),
+    Some(x) => parse_width(&x)?,
     None => 110,
   };
 
-  let lines_vec: Vec<String> =
-    io::stdin().lock().lines().map_while(Result::ok).collect();
-  let lines = justify(&lines_vec.join("\n"), col).join("\n");
+  let hyphenation = match matches.opt_str("hyphenate") {
+    Some(x) => Some(Hyphenation::new(&x)?),
+    None => None,
+  };
+  let algorithm = match matches.opt_str("algorithm") {
+    Some(x) => parse_algorithm(&x)?,
+    None => Algorithm::Greedy,
+  };
+  let alignment = match matches.opt_str("align") {
+    Some(x) => parse_alignment(&x)?,
+    None => Alignment::Justified,
+  };
+  let settings = Settings { hyphenation, algorithm, alignment };
+
+  // Invalid UTF-8 is replaced instead of cutting the input short
+  let mut input = Vec::new();
+  io::stdin().lock().read_to_end(&mut input)?;
+  let input = String::from_utf8_lossy(&input);
+  let lines =
+    justify_with(input.trim_end_matches('\n'), col, &settings).join("\n");
 
   println!("{lines}");
 
@@ -0,0 +1,63 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::{align_order, Alignment, Line};
+
+impl Line {
+  /// The byte offset in the source text of byte `at` of the text, `None`
+  /// for the blank lines between paragraphs. The spaces between words map
+  /// to the whitespace after the word before them, a hyphen added at the
+  /// end of the line to the rest of the word.
+  pub fn source_offset(&self, at: usize) -> Option<usize> {
+    let i = self.spans.partition_point(|x| x.0 <= at);
+    let (start, source) = self.spans[i.checked_sub(1)?];
+
+    Some(source + at - start)
+  }
+
+  /// The byte offset in the text of byte `source` of the source text, the
+  /// start of the next word when it is in the whitespace between two.
+  /// `None` when the line starts after it.
+  pub fn text_offset(&self, source: usize) -> Option<usize> {
+    let i = self.spans.partition_point(|x| x.1 <= source);
+    let (start, from) = self.spans[i.checked_sub(1)?];
+    let end = self.spans.get(i).map_or(self.text.len(), |x| x.0);
+
+    Some((start + source - from).min(end))
+  }
+}
+
+/// The byte offset in the source text of what is drawn at `column` of the
+/// line aligned to `line_width` columns, counted in logical order. `None`
+/// for the prefix, the spaces added around the text and past its end.
+pub fn source_position(
+  line: &Line,
+  line_width: usize,
+  alignment: Alignment,
+  column: usize,
+) -> Option<usize> {
+  let mut right = 0;
+
+  for (at, c) in align_order(line, line_width, alignment) {
+    right += c.width().unwrap_or(0);
+    if (right > column) {
+      return match at < line.text.len() {
+        true => line.source_offset(at),
+        false => None,
+      };
+    }
+  }
+
+  None
+}
+
+/// The index of the line that byte `source` of the source text is drawn
+/// on, the first of them for a table. Lines laid out for another width
+/// map the same source text, so a position found in them can be found
+/// again after the text is justified anew.
+pub fn find_line(lines: &[Line], source: usize) -> Option<usize> {
+  let start = |x: &Line| x.spans.first().map(|x| x.1);
+  let last =
+    lines.iter().rposition(|x| start(x).is_some_and(|x| x <= source))?;
+
+  lines.iter().position(|x| start(x) == start(&lines[last]))
+}
@@ -0,0 +1,150 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::Hyphenation;
+
+/// Characters a line must not start with, closing brackets, punctuation and
+/// small kana, after the Japanese kinsoku rules.
+const NO_LINE_START: &str =
+  ")]}»›’”、。，．・：；？！‼⁇⁈⁉）］｝〕〉》」』】〙〗〟｠\
+  ヽヾーァィゥェォッャュョヮヵヶぁぃぅぇぉっゃゅょゎゕゖ々〻‐゠–〜～…‥,.:;!?";
+
+/// Characters a line must not end with, opening brackets.
+const NO_LINE_END: &str = "([{«‹‘“（［｛〔〈《「『【〘〖〝｟";
+
+/// The columns the text takes up in a terminal, East Asian wide characters
+/// take two and combining marks none.
+pub(crate) fn width(text: &str) -> usize {
+  text.width()
+}
+
+/// Splits off the longest start of `text` that is at most `max_width`
+/// columns wide, but at least one character with its combining marks.
+pub(crate) fn split_at_width(
+  text: &str,
+  max_width: usize,
+) -> (&str, Option<&str>) {
+  let mut width = 0;
+
+  for (i, c) in text.char_indices() {
+    let char_width = c.width().unwrap_or(0);
+    if (i > 0 && char_width > 0 && width + char_width > max_width) {
+      let (start, rest) = text.split_at(i);
+      return (start, Some(rest));
+    }
+    width += char_width;
+  }
+
+  (text, None)
+}
+
+/// Chinese, Japanese and fullwidth characters, lines may be broken between
+/// them without a space.
+fn is_cjk(c: char) -> bool {
+  matches!(c as u32,
+    0x3000..=0x303F
+      | 0x3040..=0x30FF
+      | 0x3100..=0x312F
+      | 0x31F0..=0x31FF
+      | 0x3400..=0x4DBF
+      | 0x4E00..=0x9FFF
+      | 0xF900..=0xFAFF
+      | 0xFF00..=0xFFEF
+      | 0x20000..=0x2FFFF
+  )
+}
+
+/// Whether a line may be broken between `before` and `after`, two
+/// characters of a word.
+fn can_break(before: char, after: char) -> bool {
+  (is_cjk(before) || is_cjk(after))
+    && after.width().unwrap_or(0) > 0
+    && !NO_LINE_START.contains(after)
+    && !NO_LINE_END.contains(before)
+}
+
+/// A part of a paragraph that is kept on one line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Segment<'a> {
+  pub text: &'a str,
+  pub width: usize,
+  /// Separated from the segment before it by a space
+  pub space: bool,
+  /// Gets a hyphen when it ends a line, it is a syllable of a word
+  pub hyphen: bool,
+}
+
+/// The segments of the paragraph: its words, split between CJK characters
+/// and between syllables when hyphenating, and the parts of words wider
+/// than `line_width`.
+pub(crate) fn segments<'a>(
+  paragraph: &'a str,
+  line_width: usize,
+  hyphenation: Option<&Hyphenation>,
+) -> Vec<Segment<'a>> {
+  let mut segments = Vec::new();
+
+  for word in paragraph.split_whitespace() {
+    let mut space = true;
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+
+    for (i, c) in word.char_indices() {
+      if previous.is_some_and(|x| can_break(x, c)) {
+        let part = &word[start..i];
+        push_syllables(&mut segments, part, space, line_width, hyphenation);
+        space = false;
+        start = i;
+      }
+      previous = Some(c);
+    }
+    let part = &word[start..];
+    push_syllables(&mut segments, part, space, line_width, hyphenation);
+  }
+
+  segments
+}
+
+/// Pushes the syllables of `word`, or the whole word when not hyphenating.
+fn push_syllables<'a>(
+  segments: &mut Vec<Segment<'a>>,
+  word: &'a str,
+  mut space: bool,
+  line_width: usize,
+  hyphenation: Option<&Hyphenation>,
+) {
+  // A line needs room for a letter and the hyphen
+  let breaks = match hyphenation {
+    Some(hyphenation) if line_width > 1 => hyphenation.breaks(word),
+    _ => Vec::new(),
+  };
+
+  let mut start = 0;
+  for end in breaks {
+    push_parts(segments, &word[start..end], space, line_width - 1);
+    if let Some(last) = segments.last_mut() {
+      last.hyphen = true;
+    }
+    space = false;
+    start = end;
+  }
+  push_parts(segments, &word[start..], space, line_width);
+}
+
+/// Pushes `text` as one segment, or as several when it is wider than
+/// `line_width`.
+fn push_parts<'a>(
+  segments: &mut Vec<Segment<'a>>,
+  mut text: &'a str,
+  mut space: bool,
+  line_width: usize,
+) {
+  while let (part, Some(rest)) = split_at_width(text, line_width) {
+    let segment =
+      Segment { text: part, width: width(part), space, hyphen: false };
+    segments.push(segment);
+    space = false;
+    text = rest;
+  }
+
+  segments.push(Segment { text, width: width(text), space, hyphen: false });
+}
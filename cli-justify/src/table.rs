@@ -0,0 +1,130 @@
+use crate::segments;
+
+/// The cells of a paragraph whose lines all look like `| a | b |`, the grid
+/// cli-pdf-to-text writes tables as. A `\|` is a `|` inside a cell.
+pub(crate) fn parse_table(paragraph: &str) -> Option<Vec<Vec<String>>> {
+  let lines: Vec<&str> =
+    paragraph.lines().map(str::trim).filter(|x| !x.is_empty()).collect();
+  if lines.is_empty() {
+    return None;
+  }
+
+  lines
+    .into_iter()
+    .map(|line| {
+      let inner = line.strip_prefix('|')?.strip_suffix('|')?;
+      if inner.ends_with('\\') {
+        return None;
+      }
+
+      Some(split_cells(inner))
+    })
+    .collect()
+}
+
+fn split_cells(inner: &str) -> Vec<String> {
+  let mut cells = vec![String::new()];
+  let mut chars = inner.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if chars.peek() == Some(&'|') => {
+        cells.last_mut().unwrap().push('|');
+        chars.next();
+      }
+      '|' => cells.push(String::new()),
+      c => cells.last_mut().unwrap().push(c),
+    }
+  }
+
+  cells.into_iter().map(|x| x.trim().to_string()).collect()
+}
+
+/// Draws the rows as a grid of at most `line_width` columns. When the cells
+/// do not fit, the widest columns are narrowed and their cells wrapped over
+/// several lines.
+pub(crate) fn render_table(
+  rows: &[Vec<String>],
+  line_width: usize,
+) -> Vec<String> {
+  let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+  let natural: Vec<usize> = (0..columns)
+    .map(|i| {
+      let cells = rows.iter().filter_map(|x| x.get(i));
+      cells.map(|x| segments::width(x)).max().unwrap_or(0).max(1)
+    })
+    .collect();
+
+  // `| ` and ` |` around the row and ` | ` between the cells
+  let available = line_width.saturating_sub(3 * columns + 1).max(columns);
+  let widths = fit_widths(&natural, available);
+
+  let mut lines = Vec::new();
+  for row in rows {
+    let cells: Vec<Vec<String>> = widths
+      .iter()
+      .enumerate()
+      .map(|(i, &width)| wrap(row.get(i).map_or("", |x| x.as_str()), width))
+      .collect();
+    let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+    for line in 0..height {
+      let parts: Vec<String> = cells
+        .iter()
+        .zip(&widths)
+        .map(|(cell, &width)| {
+          let part = cell.get(line).map_or("", |x| x.as_str());
+          let padding = width.saturating_sub(segments::width(part));
+          format!("{part}{}", " ".repeat(padding))
+        })
+        .collect();
+
+      lines.push(format!("| {} |", parts.join(" | ")));
+    }
+  }
+
+  lines
+}
+
+/// Caps the widest columns at the same width, as wide as the space allows.
+fn fit_widths(natural: &[usize], available: usize) -> Vec<usize> {
+  if natural.iter().sum::<usize>() <= available {
+    return natural.to_vec();
+  }
+
+  let fits = |cap: usize| {
+    natural.iter().map(|&x| x.min(cap)).sum::<usize>() <= available
+  };
+  let cap = (1..=available).rev().find(|&x| fits(x)).unwrap_or(1);
+
+  natural.iter().map(|&x| x.min(cap)).collect()
+}
+
+/// Breaks the cell into lines of at most `width` columns, between words
+/// where possible.
+fn wrap(cell: &str, width: usize) -> Vec<String> {
+  let mut lines: Vec<String> = Vec::new();
+  let mut line = String::new();
+
+  for mut word in cell.split_whitespace() {
+    let len = segments::width(&line);
+
+    if (len > 0 && len + 1 + segments::width(word) > width) {
+      lines.push(std::mem::take(&mut line));
+    } else if (len > 0) {
+      line.push(' ');
+    }
+
+    while let (part, Some(rest)) = segments::split_at_width(word, width) {
+      lines.push(part.to_string());
+      word = rest;
+    }
+    line.push_str(word);
+  }
+
+  if (!line.is_empty() || lines.is_empty()) {
+    lines.push(line);
+  }
+
+  lines
+}
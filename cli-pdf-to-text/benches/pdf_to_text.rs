@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn test_data_pdfs() -> Vec<std::path::PathBuf> {
+  let dir =
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-data/pdf");
+
+  let mut pdfs: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+    .unwrap()
+    .map_while(Result::ok)
+    .map(|x| x.path())
+    .filter(|x| x.extension().is_some_and(|ext| ext == "pdf"))
+    .collect();
+  pdfs.sort();
+
+  return pdfs;
+}
+
+fn bench_pdf_to_text(c: &mut Criterion) {
+  let mut group = c.benchmark_group("pdf_to_text");
+  group.sample_size(10);
+
+  for pdf in test_data_pdfs() {
+    let name = pdf.file_name().unwrap().to_string_lossy().to_string();
+    let path = pdf.to_string_lossy().to_string();
+
+    // Single threaded, whole document pass as done before pages were
+    // extracted concurrently, kept as the baseline for the speedup
+    group.bench_function(format!("sequential/{name}"), |b| {
+      b.iter(|| pdf_extract::extract_text(&path).unwrap())
+    });
+
+    group.bench_function(format!("parallel/{name}"), |b| {
+      b.iter(|| cli_pdf_to_text::pdf_to_pages(&path).unwrap())
+    });
+  }
+
+  group.finish();
+}
+
+criterion_group!(benches, bench_pdf_to_text);
+criterion_main!(benches);
@@ -0,0 +1,295 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::BTreeMap;
+
+use crate::info::destination_page;
+use crate::layout::{PageLayout, Rect};
+
+/// How far outside its rectangle a character may be drawn and still count
+/// as covered by the annotation, the rectangles are often a bit too tight.
+const RECT_MARGIN: f64 = 1.0;
+
+/// Where a link leads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+  Uri(String),
+  /// 1-based page number of the same document
+  Page(u32),
+}
+
+impl std::fmt::Display for LinkTarget {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LinkTarget::Uri(uri) => write!(f, "{uri}"),
+      LinkTarget::Page(page) => write!(f, "page {page}"),
+    }
+  }
+}
+
+/// A link annotation of a page, shown as a `[number]` marker after the text
+/// it covers and listed with its target at the end of the page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+  pub number: usize,
+  pub target: LinkTarget,
+}
+
+/// The annotation dictionaries of the page.
+pub(crate) fn page_annotations(
+  doc: &Document,
+  page_id: ObjectId,
+) -> Vec<&Dictionary> {
+  let Some(annots) = doc
+    .get_dictionary(page_id)
+    .and_then(|x| x.get(b"Annots"))
+    .ok()
+    .and_then(|x| resolve(doc, x).as_array().ok())
+  else {
+    return Vec::new();
+  };
+
+  annots.iter().filter_map(|x| resolve(doc, x).as_dict().ok()).collect()
+}
+
+/// The link annotations of the page that lead somewhere, in the order of
+/// the `/Annots` array.
+pub(crate) fn page_links(
+  doc: &Document,
+  page_id: ObjectId,
+  page_numbers: &BTreeMap<ObjectId, u32>,
+) -> Vec<(Rect, LinkTarget)> {
+  page_annotations(doc, page_id)
+    .into_iter()
+    .filter(|x| {
+      x.get(b"Subtype").and_then(|x| x.as_name()).ok() == Some(b"Link")
+    })
+    .filter_map(|annot| {
+      let rect = Rect::from_object(doc, annot.get(b"Rect").ok()?)?;
+      let target = link_target(doc, annot, page_numbers)?;
+
+      Some((rect, target))
+    })
+    .collect()
+}
+
+fn link_target(
+  doc: &Document,
+  annot: &Dictionary,
+  page_numbers: &BTreeMap<ObjectId, u32>,
+) -> Option<LinkTarget> {
+  if let Ok(dest) = annot.get(b"Dest") {
+    return destination_page(doc, dest, page_numbers).map(LinkTarget::Page);
+  }
+
+  let action = resolve(doc, annot.get(b"A").ok()?).as_dict().ok()?;
+  match action.get(b"S").and_then(|x| x.as_name()).ok()? {
+    b"URI" => {
+      let uri = resolve(doc, action.get(b"URI").ok()?).as_str().ok()?;
+      Some(LinkTarget::Uri(String::from_utf8_lossy(uri).trim().to_string()))
+    }
+    b"GoTo" => destination_page(doc, action.get(b"D").ok()?, page_numbers)
+      .map(LinkTarget::Page),
+    _ => None,
+  }
+}
+
+/// Puts a `[n]` marker after the last character each link covers. Links are
+/// numbered in reading order, links that cover no text are numbered last.
+/// Neighbouring annotations with the same target, like the lines of a
+/// wrapped link, become a single link.
+pub(crate) fn mark_links(
+  layout: &mut PageLayout,
+  links: Vec<(Rect, LinkTarget)>,
+) -> Vec<Link> {
+  let mut placed: Vec<(Option<usize>, LinkTarget)> = Vec::new();
+
+  for (rect, target) in links {
+    let end = layout
+      .glyphs
+      .iter()
+      .filter(|x| rect.contains(x.x, x.y, RECT_MARGIN))
+      .map(|x| x.end)
+      .max();
+
+    match placed.last_mut() {
+      Some((previous, x)) if *x == target => *previous = (*previous).max(end),
+      _ => placed.push((end, target)),
+    }
+  }
+
+  placed.sort_by_key(|(end, _)| (end.is_none(), *end));
+
+  let links: Vec<Link> = placed
+    .iter()
+    .enumerate()
+    .map(|(i, (_, target))| Link { number: i + 1, target: target.clone() })
+    .collect();
+
+  // From the end, so the offsets of the markers before stay valid
+  for (link, (end, _)) in links.iter().zip(&placed).rev() {
+    if let Some(end) = *end {
+      layout.insert_str(end, &format!("[{}]", link.number));
+    }
+  }
+
+  return links;
+}
+
+/// Lists the targets of the links at the end of the text.
+pub(crate) fn list_links(text: &mut String, links: &[Link]) {
+  for link in links {
+    text.push_str(&format!("\n\n[{}] {}", link.number, link.target));
+  }
+  text.push('\n');
+}
+
+/// What kind of markup annotation another viewer added.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationKind {
+  Highlight,
+  Underline,
+  /// A sticky note, `/Text` in the PDF
+  Note,
+  /// A comment written on the page
+  FreeText,
+}
+
+/// A highlight or comment of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+  pub kind: AnnotationKind,
+  /// Byte range of the text it covers in the page text. Notes are attached
+  /// to the word nearest to them, `None` when the page has no text.
+  pub range: Option<std::ops::Range<usize>>,
+  /// The comment, the `/Contents` of the annotation
+  pub note: Option<String>,
+}
+
+/// The highlight, underline, note and free text annotations of the page,
+/// with the areas they cover.
+pub(crate) fn page_markup(
+  doc: &Document,
+  page_id: ObjectId,
+) -> Vec<(AnnotationKind, Vec<Rect>, Option<String>)> {
+  let mut markup = Vec::new();
+
+  for annot in page_annotations(doc, page_id) {
+    let kind = match annot.get(b"Subtype").and_then(|x| x.as_name()) {
+      Ok(b"Highlight") => AnnotationKind::Highlight,
+      Ok(b"Underline") => AnnotationKind::Underline,
+      Ok(b"Text") => AnnotationKind::Note,
+      Ok(b"FreeText") => AnnotationKind::FreeText,
+      _ => continue,
+    };
+
+    let note = annot
+      .get(b"Contents")
+      .ok()
+      .and_then(|x| lopdf::decode_text_string(resolve(doc, x)).ok())
+      .map(|x| x.trim().to_string())
+      .filter(|x| !x.is_empty());
+
+    // Text markup covers the quadrilaterals, the rectangle is only their
+    // bounding box
+    let mut areas = match annot.get(b"QuadPoints") {
+      Ok(points) => quads(doc, points),
+      Err(_) => Vec::new(),
+    };
+    if areas.is_empty() {
+      areas.extend(
+        annot.get(b"Rect").ok().and_then(|x| Rect::from_object(doc, x)),
+      );
+    }
+
+    if !areas.is_empty() {
+      markup.push((kind, areas, note));
+    }
+  }
+
+  return markup;
+}
+
+/// The bounding boxes of the `x1 y1 x2 y2 x3 y3 x4 y4` quadrilaterals.
+fn quads(doc: &Document, points: &Object) -> Vec<Rect> {
+  let Ok(points) = resolve(doc, points).as_array() else {
+    return Vec::new();
+  };
+  let numbers: Vec<f64> = points
+    .iter()
+    .filter_map(|x| resolve(doc, x).as_float().ok().map(f64::from))
+    .collect();
+
+  numbers
+    .chunks_exact(8)
+    .map(|quad| {
+      let xs = [quad[0], quad[2], quad[4], quad[6]];
+      let ys = [quad[1], quad[3], quad[5], quad[7]];
+
+      Rect {
+        left: xs.into_iter().fold(f64::INFINITY, f64::min),
+        bottom: ys.into_iter().fold(f64::INFINITY, f64::min),
+        right: xs.into_iter().fold(f64::NEG_INFINITY, f64::max),
+        top: ys.into_iter().fold(f64::NEG_INFINITY, f64::max),
+      }
+    })
+    .collect()
+}
+
+/// Maps the markup onto the text, a highlight covers the characters inside
+/// its areas, a note the word closest to it.
+pub(crate) fn place_markup(
+  layout: &PageLayout,
+  markup: Vec<(AnnotationKind, Vec<Rect>, Option<String>)>,
+) -> Vec<Annotation> {
+  let mut annotations = Vec::with_capacity(markup.len());
+
+  for (kind, areas, note) in markup {
+    let covered: Vec<_> = layout
+      .glyphs
+      .iter()
+      .filter(|x| areas.iter().any(|area| area.contains(x.x, x.y, RECT_MARGIN)))
+      .collect();
+
+    let range = match (covered.first(), covered.last()) {
+      (Some(first), Some(last)) => Some(first.start..last.end),
+      _ if kind == AnnotationKind::Note || kind == AnnotationKind::FreeText => {
+        nearest_word(layout, &areas[0])
+      }
+      _ => None,
+    };
+
+    annotations.push(Annotation { kind, range, note });
+  }
+
+  return annotations;
+}
+
+fn nearest_word(
+  layout: &PageLayout,
+  area: &Rect,
+) -> Option<std::ops::Range<usize>> {
+  let (x, y) = ((area.left + area.right) / 2.0, (area.bottom + area.top) / 2.0);
+  let glyph = layout
+    .glyphs
+    .iter()
+    .filter(|x| !layout.text[x.start..x.end].trim().is_empty())
+    .min_by(|a, b| {
+      let distance = |g: &&crate::layout::Glyph| (g.x - x).hypot(g.y - y);
+      distance(a).total_cmp(&distance(b))
+    })?;
+
+  let text = &layout.text;
+  let start = text[..glyph.start]
+    .rfind(char::is_whitespace)
+    .map(|i| i + text[i..].chars().next().map_or(1, char::len_utf8))
+    .unwrap_or(0);
+  let end = text[glyph.end..]
+    .find(char::is_whitespace)
+    .map(|i| glyph.end + i)
+    .unwrap_or(text.len());
+
+  Some(start..end)
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+  doc.dereference(object).map(|(_, x)| x).unwrap_or(object)
+}
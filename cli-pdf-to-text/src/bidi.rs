@@ -0,0 +1,152 @@
+use unicode_bidi::{bidi_class, BidiClass};
+
+use crate::layout::PageLayout;
+
+/// How a part of a line is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+  Ltr,
+  Rtl,
+  Neutral,
+}
+
+fn direction(text: &str) -> Direction {
+  for c in text.chars() {
+    match bidi_class(c) {
+      BidiClass::R | BidiClass::AL => return Direction::Rtl,
+      // Numbers are drawn left to right in right-to-left text as well
+      BidiClass::L | BidiClass::EN | BidiClass::AN => return Direction::Ltr,
+      _ => {}
+    }
+  }
+
+  Direction::Neutral
+}
+
+/// Many pdfs draw right-to-left text glyph by glyph from left to right, so
+/// the text comes out reversed. Puts the lines whose right-to-left glyphs
+/// were mostly drawn from left to right into logical order, the glyphs are
+/// moved along with their characters.
+pub(crate) fn logical_order(layout: &mut PageLayout) {
+  let mut start = 0;
+  let lines: Vec<(usize, usize)> = layout
+    .text
+    .split_inclusive('\n')
+    .map(|line| {
+      let range = (start, start + line.trim_end_matches('\n').len());
+      start += line.len();
+      range
+    })
+    .collect();
+
+  for (start, end) in lines {
+    if is_visual(layout, start, end) {
+      reorder_line(layout, start, end);
+    }
+  }
+
+  layout.glyphs.sort_by_key(|x| x.start);
+}
+
+/// Whether consecutive right-to-left glyphs of the line move to the right.
+fn is_visual(layout: &PageLayout, start: usize, end: usize) -> bool {
+  let rtl: Vec<f64> = layout
+    .glyphs
+    .iter()
+    .filter(|x| x.start >= start && x.end <= end)
+    .filter(|x| direction(&layout.text[x.start..x.end]) == Direction::Rtl)
+    .map(|x| x.x)
+    .collect();
+
+  let right = rtl.windows(2).filter(|x| x[1] > x[0]).count();
+  let left = rtl.windows(2).filter(|x| x[1] < x[0]).count();
+
+  right > left
+}
+
+/// Reverses the line, then the runs of left-to-right text and numbers in it
+/// back, which undoes the reordering of the bidi algorithm for a
+/// right-to-left paragraph.
+fn reorder_line(layout: &mut PageLayout, start: usize, end: usize) {
+  // The characters of a glyph, like a decomposed ligature, stay together
+  let mut units: Vec<(usize, usize)> = Vec::new();
+  let mut glyphs = layout
+    .glyphs
+    .iter()
+    .filter(|x| x.start >= start && x.end <= end)
+    .map(|x| (x.start, x.end))
+    .peekable();
+  let mut at = start;
+  while at < end {
+    let unit = match glyphs.peek() {
+      Some(&(glyph_start, glyph_end)) if glyph_start == at => {
+        glyphs.next();
+        (glyph_start, glyph_end)
+      }
+      _ => {
+        let len = layout.text[at..].chars().next().map_or(1, char::len_utf8);
+        (at, at + len)
+      }
+    };
+    units.push(unit);
+    at = unit.1.max(at + 1);
+  }
+  units.reverse();
+
+  let directions: Vec<Direction> =
+    units.iter().map(|&(a, b)| direction(&layout.text[a..b])).collect();
+  let mut i = 0;
+  while i < units.len() {
+    if (directions[i] != Direction::Ltr) {
+      i += 1;
+      continue;
+    }
+
+    // The run ends at its last left-to-right unit before right-to-left text
+    let mut run_end = i + 1;
+    for (j, direction) in directions.iter().enumerate().skip(i + 1) {
+      match direction {
+        Direction::Rtl => break,
+        Direction::Ltr => run_end = j + 1,
+        Direction::Neutral => {}
+      }
+    }
+    units[i..run_end].reverse();
+    i = run_end;
+  }
+
+  let mut text = String::with_capacity(end - start);
+  let mut moved = Vec::with_capacity(units.len());
+  for (a, b) in units {
+    let unit = &layout.text[a..b];
+    let unit = match direction(unit) {
+      Direction::Ltr => unit.to_string(),
+      _ => unit.chars().map(mirror).collect(),
+    };
+    moved.push((a, start + text.len()));
+    text.push_str(&unit);
+  }
+
+  for glyph in layout.glyphs.iter_mut() {
+    if let Some(&(_, to)) = moved.iter().find(|x| x.0 == glyph.start) {
+      glyph.end = to + (glyph.end - glyph.start);
+      glyph.start = to;
+    }
+  }
+  layout.text.replace_range(start..end, &text);
+}
+
+/// Brackets are drawn mirrored in right-to-left text.
+fn mirror(c: char) -> char {
+  match c {
+    '(' => ')',
+    ')' => '(',
+    '[' => ']',
+    ']' => '[',
+    '{' => '}',
+    '}' => '{',
+    '<' => '>',
+    '>' => '<',
+    c => c,
+  }
+}
@@ -0,0 +1,128 @@
+use std::ops::Range;
+
+use crate::headings::most_common_size;
+use crate::layout::{PageLayout, Rect};
+use crate::tables::is_label;
+use crate::Heading;
+
+/// What a block of the page text is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockKind {
+  Paragraph,
+  /// A heading of level 1 to 3, see `Heading`
+  Heading(usize),
+  /// A paragraph that starts with a bullet or a number
+  ListItem,
+  /// The grid of the table at this index of the page's tables
+  Table(usize),
+  /// The placeholder of the image at this index of the page's figures
+  Figure(usize),
+}
+
+/// A paragraph of the page text, the text between blank lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+  pub kind: BlockKind,
+  /// Byte range of the block in the page text
+  pub range: Range<usize>,
+  /// Where the block is drawn on the page, `None` for recognized text
+  pub bbox: Option<Rect>,
+  /// The font size most of the block is set in
+  pub font_size: Option<f64>,
+}
+
+/// Splits the page text into blocks at blank lines, with the area and the
+/// font size of their characters.
+pub(crate) fn page_blocks(
+  layout: &PageLayout,
+  headings: &[Heading],
+) -> Vec<Block> {
+  let mut blocks = Vec::new();
+  let mut offset = 0;
+  let mut current: Option<Range<usize>> = None;
+
+  for line in layout.text.split_inclusive('\n') {
+    let (start, end) = (offset, offset + line.len());
+    offset = end;
+
+    if line.trim().is_empty() {
+      blocks.extend(current.take().map(|x| block(layout, headings, x)));
+      continue;
+    }
+
+    let text_start = start + (line.len() - line.trim_start().len());
+    let text_end = start + line.trim_end().len();
+
+    // A list item starts a block of its own, after a bullet or right after
+    // another item, a number can just as well be the end of a sentence
+    let item = is_list_item(line);
+    let after_item =
+      current.as_ref().is_some_and(|x| is_list_item(&layout.text[x.clone()]));
+    if (item && (after_item || starts_with_bullet(line))) {
+      blocks.extend(current.take().map(|x| block(layout, headings, x)));
+    }
+
+    match &mut current {
+      Some(range) => range.end = text_end,
+      None => current = Some(text_start..text_end),
+    }
+  }
+  blocks.extend(current.map(|x| block(layout, headings, x)));
+
+  return blocks;
+}
+
+fn block(
+  layout: &PageLayout,
+  headings: &[Heading],
+  range: Range<usize>,
+) -> Block {
+  let overlaps = |x: &Range<usize>| x.start < range.end && range.start < x.end;
+
+  if let Some(i) = layout.tables.iter().position(|x| overlaps(&x.range)) {
+    return Block {
+      kind: BlockKind::Table(i),
+      range,
+      bbox: layout.tables[i].bbox,
+      font_size: layout.tables[i].font_size,
+    };
+  }
+
+  if let Some(i) = layout.figures.iter().position(|x| overlaps(&x.range)) {
+    return Block {
+      kind: BlockKind::Figure(i),
+      range,
+      bbox: Some(layout.figures[i].bbox),
+      font_size: None,
+    };
+  }
+
+  let first = layout.glyphs.partition_point(|x| x.start < range.start);
+  let last = layout.glyphs.partition_point(|x| x.start < range.end);
+  let glyphs = &layout.glyphs[first..last];
+
+  let bbox = glyphs.iter().map(|x| x.bbox()).reduce(Rect::union);
+
+  let text = &layout.text[range.clone()];
+  let kind = match headings.iter().find(|x| overlaps(&x.range)) {
+    Some(heading) => BlockKind::Heading(heading.level),
+    None if is_list_item(text) => BlockKind::ListItem,
+    None => BlockKind::Paragraph,
+  };
+
+  Block { kind, range, bbox, font_size: most_common_size(layout, glyphs) }
+}
+
+/// Text that starts with a bullet or a number followed by more text.
+fn is_list_item(text: &str) -> bool {
+  let mut words = text.split_whitespace();
+
+  words.next().is_some_and(is_label) && words.next().is_some()
+}
+
+fn starts_with_bullet(text: &str) -> bool {
+  text
+    .split_whitespace()
+    .next()
+    .is_some_and(|x| is_label(x) && !x.chars().any(char::is_alphanumeric))
+}
@@ -0,0 +1,51 @@
+/// Why a document could not be converted.
+#[derive(Debug)]
+pub enum PdfError {
+  Io(std::io::Error),
+  /// The file has no `%PDF-` header, it is some other format
+  NotPdf,
+  /// The document can not be opened with an empty user password
+  Encrypted,
+  IncorrectPassword,
+  /// The document, or the page when `page` is set, could not be parsed
+  Corrupt {
+    page: Option<u32>,
+    reason: String,
+  },
+}
+
+impl std::fmt::Display for PdfError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PdfError::Io(e) => write!(f, "{e}"),
+      PdfError::NotPdf => write!(f, "not a PDF document"),
+      PdfError::Encrypted => {
+        write!(f, "this PDF is encrypted, a password is required to open it")
+      }
+      PdfError::IncorrectPassword => {
+        write!(f, "the password for this encrypted PDF is incorrect")
+      }
+      PdfError::Corrupt { page: Some(page), reason } => {
+        write!(f, "page {page} of the PDF is corrupt: {reason}")
+      }
+      PdfError::Corrupt { page: None, reason } => {
+        write!(f, "the PDF is corrupt: {reason}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for PdfError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      PdfError::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for PdfError {
+  fn from(e: std::io::Error) -> Self {
+    PdfError::Io(e)
+  }
+}
@@ -0,0 +1,317 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, ObjectId, Stream};
+use std::ops::Range;
+
+use crate::layout::{PageLayout, Rect};
+use crate::ocr::{encode_image, inherited, resolve};
+use crate::PageImage;
+
+/// Images covering more of the page are scanned pages or backgrounds.
+const MAX_PAGE_SHARE: f64 = 0.8;
+
+/// Narrower or lower images, in points, are icons, bullets and rules.
+const MIN_SIZE: f64 = 36.0;
+
+/// How far above or below the image its caption may be, in points.
+const CAPTION_DISTANCE: f64 = 48.0;
+
+/// A caption continues over at most this many lines.
+const MAX_CAPTION_LINES: usize = 3;
+
+/// The words a caption starts with, followed by its number.
+const CAPTION_WORDS: [&str; 8] =
+  ["figure", "fig.", "fig", "plate", "illustration", "chart", "photo", "map"];
+
+/// Form XObjects nested deeper are not looked into, they may reference
+/// themselves.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// A transformation matrix `[a b c d e f]`.
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// An image of the page, the text shows a placeholder for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Figure {
+  /// The caption next to the image, like `Figure 3 The pipeline`
+  pub caption: Option<String>,
+  /// The size of the image in pixels
+  pub width: u32,
+  pub height: u32,
+  /// Where the image is drawn on the page
+  pub bbox: Rect,
+  /// Byte range of the placeholder in the page text
+  pub range: Range<usize>,
+  /// The image as a file, `None` for formats without a standalone
+  /// equivalent
+  pub image: Option<PageImage>,
+}
+
+impl Figure {
+  /// `[Figure: caption, 640×480]`, the text that stands in for the image.
+  pub fn placeholder(&self) -> String {
+    match &self.caption {
+      Some(caption) => {
+        format!("[Figure: {caption}, {}×{}]", self.width, self.height)
+      }
+      None => format!("[Figure: {}×{}]", self.width, self.height),
+    }
+  }
+}
+
+/// The images the page draws, with where they are drawn, in the order they
+/// are drawn.
+fn page_images(doc: &Document, page_id: ObjectId) -> Vec<(Rect, &Stream)> {
+  let mut images = Vec::new();
+
+  let resources = inherited(doc, page_id, b"Resources")
+    .and_then(|x| resolve(doc, x).as_dict().ok());
+  let content = doc.get_page_content(page_id);
+
+  if let (Some(resources), Ok(content)) = (resources, content) {
+    collect_images(doc, &content, resources, IDENTITY, 0, &mut images);
+  }
+
+  images
+}
+
+fn collect_images<'a>(
+  doc: &'a Document,
+  content: &[u8],
+  resources: &'a Dictionary,
+  mut ctm: Matrix,
+  depth: usize,
+  images: &mut Vec<(Rect, &'a Stream)>,
+) {
+  let Ok(content) = Content::decode(content) else {
+    return;
+  };
+  let xobjects =
+    resources.get(b"XObject").ok().and_then(|x| resolve(doc, x).as_dict().ok());
+
+  let mut saved = Vec::new();
+  for operation in content.operations {
+    match operation.operator.as_str() {
+      "q" => saved.push(ctm),
+      "Q" => ctm = saved.pop().unwrap_or(ctm),
+      "cm" => {
+        if let Some(matrix) = matrix(doc, &operation.operands) {
+          ctm = multiply(matrix, ctm);
+        }
+      }
+      "Do" => {
+        let stream = operation
+          .operands
+          .first()
+          .and_then(|x| x.as_name().ok())
+          .and_then(|name| xobjects?.get(name).ok())
+          .and_then(|x| resolve(doc, x).as_stream().ok());
+        let Some(stream) = stream else { continue };
+
+        match stream.dict.get(b"Subtype").and_then(|x| x.as_name()) {
+          Ok(b"Image") => images.push((unit_square(ctm), stream)),
+          Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+            let form_matrix = stream
+              .dict
+              .get(b"Matrix")
+              .ok()
+              .and_then(|x| matrix(doc, resolve(doc, x).as_array().ok()?));
+            let form_resources = stream
+              .dict
+              .get(b"Resources")
+              .ok()
+              .and_then(|x| resolve(doc, x).as_dict().ok())
+              .unwrap_or(resources);
+            let Ok(content) = stream.get_plain_content() else { continue };
+
+            collect_images(
+              doc,
+              &content,
+              form_resources,
+              multiply(form_matrix.unwrap_or(IDENTITY), ctm),
+              depth + 1,
+              images,
+            );
+          }
+          _ => {}
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+fn matrix(doc: &Document, operands: &[lopdf::Object]) -> Option<Matrix> {
+  let numbers: Vec<f64> = operands
+    .iter()
+    .map(|x| resolve(doc, x).as_float().map(f64::from).ok())
+    .collect::<Option<_>>()?;
+
+  numbers.try_into().ok()
+}
+
+/// `a` applied after `b`.
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+  [
+    a[0] * b[0] + a[1] * b[2],
+    a[0] * b[1] + a[1] * b[3],
+    a[2] * b[0] + a[3] * b[2],
+    a[2] * b[1] + a[3] * b[3],
+    a[4] * b[0] + a[5] * b[2] + b[4],
+    a[4] * b[1] + a[5] * b[3] + b[5],
+  ]
+}
+
+/// Images are drawn into the unit square of the current transformation.
+fn unit_square(ctm: Matrix) -> Rect {
+  let corners =
+    [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+      (x * ctm[0] + y * ctm[2] + ctm[4], x * ctm[1] + y * ctm[3] + ctm[5])
+    });
+
+  let xs = corners.map(|x| x.0);
+  let ys = corners.map(|x| x.1);
+  Rect {
+    left: xs.into_iter().fold(f64::INFINITY, f64::min),
+    bottom: ys.into_iter().fold(f64::INFINITY, f64::min),
+    right: xs.into_iter().fold(f64::NEG_INFINITY, f64::max),
+    top: ys.into_iter().fold(f64::NEG_INFINITY, f64::max),
+  }
+}
+
+/// A line of the page text with glyphs, and where it is drawn.
+struct Line {
+  range: Range<usize>,
+  left: f64,
+  right: f64,
+  /// The average baseline of its characters
+  y: f64,
+}
+
+fn text_lines(layout: &PageLayout) -> Vec<Line> {
+  let mut lines = Vec::new();
+  let mut glyphs = layout.glyphs.as_slice();
+  let mut start = 0;
+
+  for line in layout.text.split_inclusive('\n') {
+    let end = start + line.len();
+    let count = glyphs.partition_point(|x| x.start < end);
+    let (mine, rest) = glyphs.split_at(count);
+    glyphs = rest;
+
+    if !mine.is_empty() && !line.trim().is_empty() {
+      lines.push(Line {
+        range: start..start + line.trim_end().len(),
+        left: mine.iter().map(|x| x.left()).fold(f64::INFINITY, f64::min),
+        right: mine.iter().map(|x| x.right()).fold(f64::NEG_INFINITY, f64::max),
+        y: mine.iter().map(|x| x.y).sum::<f64>() / mine.len() as f64,
+      });
+    }
+    start = end;
+  }
+
+  lines
+}
+
+/// Whether the line starts like `Figure 3` or `Fig. 2.1`.
+fn is_caption(text: &str) -> bool {
+  let lower = text.trim_start().to_lowercase();
+
+  CAPTION_WORDS.iter().any(|word| {
+    lower
+      .strip_prefix(word)
+      .map(str::trim_start)
+      .and_then(|x| x.chars().next())
+      .is_some_and(|x| x.is_ascii_digit())
+  })
+}
+
+/// The caption closest to the image, above or below it, with the lines of
+/// its paragraph that follow.
+fn find_caption(
+  layout: &PageLayout,
+  lines: &[Line],
+  bbox: &Rect,
+) -> Option<String> {
+  let overlaps = |x: &Line| x.left < bbox.right && bbox.left < x.right;
+  let distance = |x: &Line| match x.y < bbox.bottom {
+    true => bbox.bottom - x.y,
+    false => x.y - bbox.top,
+  };
+
+  let (i, _) = lines
+    .iter()
+    .enumerate()
+    .filter(|(_, x)| overlaps(x) && (x.y < bbox.bottom || x.y > bbox.top))
+    .filter(|(_, x)| distance(x) <= CAPTION_DISTANCE)
+    .filter(|(_, x)| is_caption(&layout.text[x.range.clone()]))
+    .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)))?;
+
+  // The paragraph ends at a blank line
+  let mut caption: Vec<&str> = vec![layout.text[lines[i].range.clone()].trim()];
+  for pair in lines[i..].windows(2).take(MAX_CAPTION_LINES - 1) {
+    let between = &layout.text[pair[0].range.end..pair[1].range.start];
+    if between.matches('\n').count() > 1 {
+      break;
+    }
+    caption.push(layout.text[pair[1].range.clone()].trim());
+  }
+
+  Some(caption.join(" "))
+}
+
+/// Puts a placeholder for every image of the page into the text, before the
+/// first line below the top of the image. Scanned pages, backgrounds and
+/// images too small to be figures are left out.
+pub(crate) fn mark_figures(
+  doc: &Document,
+  page_id: ObjectId,
+  layout: &mut PageLayout,
+) {
+  let page = inherited(doc, page_id, b"MediaBox")
+    .and_then(|x| Rect::from_object(doc, x));
+  let page_area = page.map_or(f64::INFINITY, |x| x.width() * x.height());
+
+  for (bbox, stream) in page_images(doc, page_id) {
+    let size = |key: &[u8]| stream.dict.get(key).and_then(|x| x.as_i64()).ok();
+    let (Some(width), Some(height)) = (size(b"Width"), size(b"Height")) else {
+      continue;
+    };
+
+    if (bbox.width() < MIN_SIZE
+      || bbox.height() < MIN_SIZE
+      || bbox.width() * bbox.height() > page_area * MAX_PAGE_SHARE)
+    {
+      continue;
+    }
+
+    let lines = text_lines(layout);
+    let caption = find_caption(layout, &lines, &bbox);
+
+    let overlaps = |x: &&Line| x.left < bbox.right && bbox.left < x.right;
+    let at = lines
+      .iter()
+      .filter(overlaps)
+      .find(|x| x.y < bbox.top)
+      .map_or(layout.text.len(), |x| x.range.start);
+
+    let mut figure = Figure {
+      caption,
+      width: width.max(0) as u32,
+      height: height.max(0) as u32,
+      bbox,
+      range: 0..0,
+      image: encode_image(doc, stream),
+    };
+    let placeholder = figure.placeholder();
+
+    let before = match layout.text[..at].ends_with('\n') || at == 0 {
+      true => "\n",
+      false => "\n\n",
+    };
+    figure.range = at + before.len()..at + before.len() + placeholder.len();
+    layout.insert_str(at, &format!("{before}{placeholder}\n\n"));
+    layout.figures.push(figure);
+  }
+}
@@ -0,0 +1,135 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::ocr::{inherited, resolve};
+
+/// Forms nested deeper are not looked into, they may refer to each other.
+const MAX_FORM_DEPTH: usize = 8;
+
+/// A font with at least this `/FontWeight` in its descriptor is bold.
+const BOLD_WEIGHT: i64 = 600;
+
+/// The `/ForceBold` flag of a font descriptor.
+const FORCE_BOLD: i64 = 1 << 18;
+
+/// Whether each string the page shows with `Tj` and `TJ` is set in a bold
+/// font, in the order they are shown. pdf-extract does not tell which font
+/// a character is drawn with, but it starts a word for each of these
+/// strings, so the fonts are matched with its characters by counting them.
+pub(crate) fn bold_strings(doc: &Document, page_id: ObjectId) -> Vec<bool> {
+  let mut bold = Vec::new();
+
+  let resources = inherited(doc, page_id, b"Resources")
+    .and_then(|x| resolve(doc, x).as_dict().ok());
+  let content = doc.get_page_content(page_id);
+
+  if let (Some(resources), Ok(content)) = (resources, content) {
+    collect_strings(doc, &content, resources, 0, &mut bold);
+  }
+
+  bold
+}
+
+fn collect_strings(
+  doc: &Document,
+  content: &[u8],
+  resources: &Dictionary,
+  depth: usize,
+  bold: &mut Vec<bool>,
+) {
+  let Ok(content) = Content::decode(content) else {
+    return;
+  };
+  let fonts =
+    resources.get(b"Font").ok().and_then(|x| resolve(doc, x).as_dict().ok());
+  let xobjects =
+    resources.get(b"XObject").ok().and_then(|x| resolve(doc, x).as_dict().ok());
+
+  // Like pdf-extract, a form starts without a font and `Q` restores it
+  let mut font_bold = false;
+  let mut saved = Vec::new();
+  for operation in content.operations {
+    match operation.operator.as_str() {
+      "q" => saved.push(font_bold),
+      "Q" => font_bold = saved.pop().unwrap_or(font_bold),
+      "Tf" => {
+        font_bold = operation
+          .operands
+          .first()
+          .and_then(|x| x.as_name().ok())
+          .and_then(|name| fonts?.get(name).ok())
+          .and_then(|x| resolve(doc, x).as_dict().ok())
+          .is_some_and(|font| is_bold(doc, font));
+      }
+      "Tj" => {
+        if let Some(Object::String(..)) = operation.operands.first() {
+          bold.push(font_bold);
+        }
+      }
+      "TJ" => {
+        if let Some(Object::Array(array)) = operation.operands.first() {
+          let strings = array.iter().filter(|x| x.as_str().is_ok());
+          bold.extend(strings.map(|_| font_bold));
+        }
+      }
+      "Do" => {
+        let stream = operation
+          .operands
+          .first()
+          .and_then(|x| x.as_name().ok())
+          .and_then(|name| xobjects?.get(name).ok())
+          .and_then(|x| resolve(doc, x).as_stream().ok());
+        let Some(stream) = stream else { continue };
+
+        let subtype = stream.dict.get(b"Subtype").and_then(|x| x.as_name());
+        if (subtype.is_ok_and(|x| x == b"Form") && depth < MAX_FORM_DEPTH) {
+          let form_resources = stream
+            .dict
+            .get(b"Resources")
+            .ok()
+            .and_then(|x| resolve(doc, x).as_dict().ok())
+            .unwrap_or(resources);
+          let Ok(content) = stream.get_plain_content() else { continue };
+
+          collect_strings(doc, &content, form_resources, depth + 1, bold);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Whether the font is bold by its name, like `Helvetica-Bold`, or by the
+/// weight or flags of its descriptor. A composite font has the descriptor
+/// in its descendant font.
+fn is_bold(doc: &Document, font: &Dictionary) -> bool {
+  fn get<'a>(
+    doc: &'a Document,
+    dict: &'a Dictionary,
+    key: &[u8],
+  ) -> Option<&'a Object> {
+    dict.get(key).ok().map(|x| resolve(doc, x))
+  }
+
+  let name = get(doc, font, b"BaseFont").and_then(|x| x.as_name().ok());
+  let name = String::from_utf8_lossy(name.unwrap_or_default()).to_lowercase();
+  if (["bold", "black", "heavy"].iter().any(|x| name.contains(x))) {
+    return true;
+  }
+
+  let descendant = get(doc, font, b"DescendantFonts")
+    .and_then(|x| x.as_array().ok()?.first())
+    .and_then(|x| resolve(doc, x).as_dict().ok());
+  let descriptor = get(doc, font, b"FontDescriptor")
+    .or_else(|| get(doc, descendant?, b"FontDescriptor"))
+    .and_then(|x| x.as_dict().ok());
+  let Some(descriptor) = descriptor else {
+    return false;
+  };
+
+  let number = |key: &[u8]| {
+    get(doc, descriptor, key).and_then(|x| x.as_float().ok()).map(|x| x as i64)
+  };
+  number(b"FontWeight").is_some_and(|x| x >= BOLD_WEIGHT)
+    || number(b"Flags").is_some_and(|x| x & FORCE_BOLD != 0)
+}
@@ -0,0 +1,231 @@
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+use crate::{AnnotationKind, Block, BlockKind, LinkTarget, Page, Rect};
+
+/// The pages as a JSON document for tools that index or compare the text:
+/// every page with its blocks, their area on the page and font size, its
+/// headings, links, highlights and warnings.
+pub fn pages_to_json(pages: &[Page]) -> String {
+  let pages: Vec<Value> = pages.iter().map(page_json).collect();
+
+  json!({ "pages": pages }).to_string()
+}
+
+fn page_json(page: &Page) -> Value {
+  let blocks: Vec<Value> =
+    page.blocks.iter().map(|x| block_json(page, x)).collect();
+  let headings: Vec<Value> = page
+    .headings
+    .iter()
+    .map(|x| json!({ "level": x.level, "text": x.text }))
+    .collect();
+  let links: Vec<Value> = page
+    .links
+    .iter()
+    .map(|x| match &x.target {
+      LinkTarget::Uri(uri) => json!({ "number": x.number, "uri": uri }),
+      LinkTarget::Page(to) => json!({ "number": x.number, "page": to }),
+    })
+    .collect();
+  let annotations: Vec<Value> = page
+    .annotations
+    .iter()
+    .map(|x| {
+      let kind = match x.kind {
+        AnnotationKind::Highlight => "highlight",
+        AnnotationKind::Underline => "underline",
+        AnnotationKind::Note => "note",
+        AnnotationKind::FreeText => "free_text",
+      };
+      let text = x.range.clone().and_then(|x| page.text.get(x));
+
+      json!({ "kind": kind, "text": text, "note": x.note })
+    })
+    .collect();
+  let warnings: Vec<String> =
+    page.warnings.iter().map(|x| x.to_string()).collect();
+
+  json!({
+    "number": page.number,
+    "recognized": page.recognized,
+    "blocks": blocks,
+    "headings": headings,
+    "links": links,
+    "annotations": annotations,
+    "warnings": warnings,
+  })
+}
+
+fn block_json(page: &Page, block: &Block) -> Value {
+  let bbox = block.bbox.map(|x: Rect| [x.left, x.bottom, x.right, x.top]);
+  let mut value = json!({
+    "type": "paragraph",
+    "text": page.text[block.range.clone()],
+    "bbox": bbox,
+    "font_size": block.font_size,
+  });
+
+  match block.kind {
+    BlockKind::Paragraph => {}
+    BlockKind::Heading(level) => {
+      value["type"] = json!("heading");
+      value["level"] = json!(level);
+    }
+    BlockKind::ListItem => value["type"] = json!("list_item"),
+    BlockKind::Table(i) => {
+      value["type"] = json!("table");
+      value["rows"] = json!(page.tables.get(i).map(|x| &x.rows));
+    }
+    BlockKind::Figure(i) => {
+      value["type"] = json!("figure");
+      if let Some(figure) = page.figures.get(i) {
+        value["caption"] = json!(figure.caption);
+        value["width"] = json!(figure.width);
+        value["height"] = json!(figure.height);
+      }
+    }
+  }
+
+  value
+}
+
+/// The pages as Markdown: headings, list items, paragraphs with their lines
+/// joined, tables and links to the pages and addresses they lead to.
+pub fn pages_to_markdown(pages: &[Page]) -> String {
+  // Only the pages links lead to get an anchor
+  let targets: BTreeSet<u32> = pages
+    .iter()
+    .flat_map(|x| &x.links)
+    .filter_map(|x| match x.target {
+      LinkTarget::Page(page) => Some(page),
+      LinkTarget::Uri(_) => None,
+    })
+    .collect();
+
+  let mut markdown = String::new();
+  let mut previous = None;
+  for page in pages {
+    if targets.contains(&page.number) {
+      markdown.push_str(&format!("<a id=\"page-{}\"></a>\n\n", page.number));
+      previous = None;
+    }
+
+    for block in &page.blocks {
+      let text = &page.text[block.range.clone()];
+
+      let part = match block.kind {
+        BlockKind::Heading(level) => {
+          format!("{} {}", "#".repeat(level), join_lines(text))
+        }
+        BlockKind::ListItem => list_item(&join_lines(text)),
+        BlockKind::Table(i) => match page.tables.get(i) {
+          Some(table) => markdown_table(&table.rows),
+          None => text.to_string(),
+        },
+        BlockKind::Figure(_) => text.to_string(),
+        BlockKind::Paragraph => escape_start(&join_lines(text)),
+      };
+
+      // The items of a list are on consecutive lines
+      let item = block.kind == BlockKind::ListItem;
+      match previous {
+        Some(true) if item => markdown.push('\n'),
+        Some(_) => markdown.push_str("\n\n"),
+        None => {}
+      }
+      markdown.push_str(&link_markers(page, part));
+      previous = Some(item);
+    }
+  }
+
+  markdown + "\n"
+}
+
+/// The lines of a paragraph as one, words hyphenated at the end of a line
+/// are joined again.
+fn join_lines(text: &str) -> String {
+  let mut joined = String::new();
+
+  for line in text.lines() {
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.is_empty() {
+      continue;
+    }
+
+    if (joined.ends_with('-') && line.starts_with(char::is_lowercase)) {
+      joined.pop();
+    } else if !joined.is_empty() {
+      joined.push(' ');
+    }
+    joined.push_str(&line);
+  }
+
+  joined
+}
+
+/// `• item` becomes `- item`, numbered items keep their number.
+fn list_item(text: &str) -> String {
+  let (label, rest) = text.split_once(' ').unwrap_or((text, ""));
+  let number = label.trim_end_matches(['.', ')']);
+
+  if (!number.is_empty() && number.chars().all(|x| x.is_ascii_digit())) {
+    return format!("{number}. {rest}");
+  }
+  if matches!(label, "•" | "◦" | "▪" | "·" | "–" | "-" | "*") {
+    return format!("- {rest}");
+  }
+
+  format!("- {text}")
+}
+
+/// Keeps a paragraph that starts like a heading, a quote or a list item a
+/// paragraph.
+fn escape_start(text: &str) -> String {
+  if let Some((number, rest)) = text.split_once(". ") {
+    if (!number.is_empty() && number.chars().all(|x| x.is_ascii_digit())) {
+      return format!("{number}\\. {rest}");
+    }
+  }
+
+  match text.starts_with(['#', '>', '-', '+', '*']) {
+    true => format!("\\{text}"),
+    false => text.to_string(),
+  }
+}
+
+/// A table whose first row is the header.
+fn markdown_table(rows: &[Vec<String>]) -> String {
+  let row = |cells: &Vec<String>| {
+    let cells: Vec<String> =
+      cells.iter().map(|x| x.replace('|', "\\|")).collect();
+    format!("| {} |", cells.join(" | "))
+  };
+  let columns = rows.first().map_or(0, Vec::len);
+
+  let mut lines: Vec<String> = Vec::new();
+  for (i, cells) in rows.iter().enumerate() {
+    lines.push(row(cells));
+    if (i == 0) {
+      lines.push(format!("|{}", "---|".repeat(columns)));
+    }
+  }
+
+  lines.join("\n")
+}
+
+/// Turns the `[n]` markers of the page's links into Markdown links.
+fn link_markers(page: &Page, mut text: String) -> String {
+  for link in &page.links {
+    let target = match &link.target {
+      LinkTarget::Uri(uri) => uri.replace(' ', "%20").replace(')', "%29"),
+      LinkTarget::Page(to) => format!("#page-{to}"),
+    };
+    text = text.replace(
+      &format!("[{}]", link.number),
+      &format!("[\\[{}\\]]({target})", link.number),
+    );
+  }
+
+  text
+}
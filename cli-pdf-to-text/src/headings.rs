@@ -0,0 +1,155 @@
+use lopdf::{Document, ObjectId};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::layout::{page_layout, Glyph, PageLayout};
+use crate::{warning, OutlineEntry};
+
+/// A line set at least this many times the size of the body text is a
+/// heading of level 1, 2 and 3 respectively.
+const LEVEL_RATIOS: [f64; 3] = [1.8, 1.4, 1.15];
+
+/// Longer lines in a large font are body text, like a pull quote.
+const MAX_HEADING_LEN: usize = 120;
+
+/// A line of a page set in a larger font than the body text, or in bold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+  /// From 1 for the largest headings to 3
+  pub level: usize,
+  pub text: String,
+  /// Byte range of the heading in the page text
+  pub range: Range<usize>,
+}
+
+/// Classifies the lines of the page by their font size relative to the size
+/// most of the page is set in, a line of body size set entirely in bold is a
+/// heading of the lowest level. Consecutive lines of the same level, a
+/// heading that wraps, become a single heading.
+pub(crate) fn page_headings(layout: &PageLayout) -> Vec<Heading> {
+  let Some(body) = most_common_size(layout, &layout.glyphs) else {
+    return Vec::new();
+  };
+  // On a page set in bold, bold does not stand out
+  let body_bold = bold_share(layout, &layout.glyphs) >= 0.5;
+
+  let mut headings: Vec<Heading> = Vec::new();
+  let mut glyphs = layout.glyphs.as_slice();
+  let mut previous_level = None;
+  let mut start = 0;
+
+  for line in layout.text.split_inclusive('\n') {
+    let end = start + line.len();
+
+    // A superscript or a drop cap does not change the size of the line
+    let count = glyphs.partition_point(|x| x.start < end);
+    let size = most_common_size(layout, &glyphs[..count]);
+    let bold = !body_bold && bold_share(layout, &glyphs[..count]) == 1.0;
+    glyphs = &glyphs[count..];
+
+    let text = line.trim();
+    let level = LEVEL_RATIOS
+      .iter()
+      .position(|ratio| size.is_some_and(|x| x >= body * ratio))
+      .map(|x| x + 1)
+      .or(bold.then_some(LEVEL_RATIOS.len()))
+      .filter(|_| text.len() <= MAX_HEADING_LEN)
+      .filter(|_| text.chars().any(char::is_alphabetic));
+
+    if let Some(level) = level {
+      let text_start = start + (line.len() - line.trim_start().len());
+      let range = text_start..text_start + text.len();
+
+      match headings.last_mut() {
+        Some(heading) if previous_level == Some(level) => {
+          heading.text = format!("{} {text}", heading.text);
+          heading.range.end = range.end;
+        }
+        _ => headings.push(Heading { level, text: text.to_string(), range }),
+      }
+    }
+
+    previous_level = level;
+    start = end;
+  }
+
+  return headings;
+}
+
+/// The font size most of the visible characters are set in.
+pub(crate) fn most_common_size(
+  layout: &PageLayout,
+  glyphs: &[Glyph],
+) -> Option<f64> {
+  // In half points, so sizes that differ by rounding count as the same
+  let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+  for glyph in glyphs {
+    if !layout.text[glyph.start..glyph.end].trim().is_empty() {
+      *counts.entry((glyph.size * 2.0).round() as i64).or_default() += 1;
+    }
+  }
+
+  let (size, _) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+
+  (size > 0).then_some(size as f64 / 2.0)
+}
+
+/// How many of the visible characters are bold, from 0.0 to 1.0.
+fn bold_share(layout: &PageLayout, glyphs: &[Glyph]) -> f64 {
+  let visible: Vec<&Glyph> = glyphs
+    .iter()
+    .filter(|x| !layout.text[x.start..x.end].trim().is_empty())
+    .collect();
+  if visible.is_empty() {
+    return 0.0;
+  }
+
+  visible.iter().filter(|x| x.bold).count() as f64 / visible.len() as f64
+}
+
+/// The headings of the page as entries of a table of contents, their
+/// levels from 0.
+pub(crate) fn outline_entries(
+  page: u32,
+  headings: &[Heading],
+) -> Vec<OutlineEntry> {
+  headings
+    .iter()
+    .map(|heading| OutlineEntry {
+      title: heading.text.clone(),
+      level: heading.level - 1,
+      page: Some(page),
+    })
+    .collect()
+}
+
+/// A table of contents from the headings of every page, for documents
+/// without an outline. Pages that can not be extracted are skipped.
+pub(crate) fn generated_outline(doc: &Document) -> Vec<OutlineEntry> {
+  let pages: Vec<(u32, ObjectId)> = doc.get_pages().into_iter().collect();
+
+  let headings: Vec<Vec<OutlineEntry>> = pages
+    .into_par_iter()
+    .map(|(page, page_id)| {
+      let (layout, _) = warning::capture_warnings(Some(page), || {
+        page_layout(doc, page, page_id)
+      });
+      let Ok(layout) = layout else {
+        return Vec::new();
+      };
+
+      outline_entries(page, &page_headings(&layout))
+    })
+    .collect();
+
+  // Levels no heading of the document uses are left out, so sections are
+  // nested right below the chapters
+  let mut outline = headings.concat();
+  let levels: BTreeSet<usize> = outline.iter().map(|x| x.level).collect();
+  for entry in &mut outline {
+    entry.level = levels.range(..entry.level).count();
+  }
+
+  return outline;
+}
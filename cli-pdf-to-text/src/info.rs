@@ -0,0 +1,214 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfInfo {
+  pub title: Option<String>,
+  pub author: Option<String>,
+  pub subject: Option<String>,
+  pub page_count: usize,
+  pub outline: Vec<OutlineEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+  pub title: String,
+  /// Nesting depth in the outline tree, top level entries are 0
+  pub level: usize,
+  /// 1-based page number, `None` when the destination could not be resolved
+  pub page: Option<u32>,
+}
+
+/// Reads the document information dictionary and the `/Outlines` tree.
+pub fn pdf_info(doc: &Document) -> PdfInfo {
+  let info =
+    doc.trailer.get(b"Info").ok().and_then(|x| resolve(doc, x).as_dict().ok());
+
+  let info_string = |key: &[u8]| -> Option<String> {
+    let value = resolve(doc, info?.get(key).ok()?);
+    let text = lopdf::decode_text_string(value).ok()?;
+    let text = text.trim_matches(char::from(0)).trim();
+
+    if text.is_empty() {
+      return None;
+    }
+
+    Some(text.to_string())
+  };
+
+  let pages = doc.get_pages();
+  let page_numbers: BTreeMap<ObjectId, u32> =
+    pages.iter().map(|(num, id)| (*id, *num)).collect();
+
+  let mut outline = Vec::new();
+  if let Some(first) = doc
+    .catalog()
+    .ok()
+    .and_then(|x| x.get(b"Outlines").ok())
+    .and_then(|x| resolve(doc, x).as_dict().ok())
+    .and_then(|x| x.get(b"First").ok())
+  {
+    let mut visited = HashSet::new();
+    read_outline_items(
+      doc,
+      first,
+      0,
+      &page_numbers,
+      &mut visited,
+      &mut outline,
+    );
+  }
+
+  PdfInfo {
+    title: info_string(b"Title"),
+    author: info_string(b"Author"),
+    subject: info_string(b"Subject"),
+    page_count: pages.len(),
+    outline,
+  }
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+  doc.dereference(object).map(|(_, x)| x).unwrap_or(object)
+}
+
+fn read_outline_items(
+  doc: &Document,
+  first: &Object,
+  level: usize,
+  page_numbers: &BTreeMap<ObjectId, u32>,
+  visited: &mut HashSet<ObjectId>,
+  outline: &mut Vec<OutlineEntry>,
+) {
+  let mut next = Some(first);
+
+  while let Some(item_ref) = next {
+    // Malformed documents can link siblings or children into a cycle
+    let Ok(id) = item_ref.as_reference() else { break };
+    if !visited.insert(id) {
+      break;
+    }
+    let Ok(item) = doc.get_dictionary(id) else { break };
+
+    let title = item
+      .get(b"Title")
+      .ok()
+      .and_then(|x| lopdf::decode_text_string(resolve(doc, x)).ok())
+      .unwrap_or_default();
+
+    outline.push(OutlineEntry {
+      title: title.trim().to_string(),
+      level,
+      page: item_destination(doc, item)
+        .and_then(|x| destination_page(doc, x, page_numbers)),
+    });
+
+    if let Ok(child) = item.get(b"First") {
+      read_outline_items(doc, child, level + 1, page_numbers, visited, outline);
+    }
+
+    next = item.get(b"Next").ok();
+  }
+}
+
+fn item_destination<'a>(
+  doc: &'a Document,
+  item: &'a Dictionary,
+) -> Option<&'a Object> {
+  if let Ok(dest) = item.get(b"Dest") {
+    return Some(resolve(doc, dest));
+  }
+
+  let action = resolve(doc, item.get(b"A").ok()?).as_dict().ok()?;
+  if action.get(b"S").and_then(|x| x.as_name()).ok()? != b"GoTo" {
+    return None;
+  }
+
+  Some(resolve(doc, action.get(b"D").ok()?))
+}
+
+/// Resolves an explicit `[page /XYZ ...]` destination, a named destination
+/// or a destination dictionary to a 1-based page number.
+pub(crate) fn destination_page(
+  doc: &Document,
+  dest: &Object,
+  page_numbers: &BTreeMap<ObjectId, u32>,
+) -> Option<u32> {
+  match resolve(doc, dest) {
+    Object::Array(array) => match array.first()? {
+      Object::Reference(id) => page_numbers.get(id).copied(),
+      // Remote style destinations use a 0-based page index
+      Object::Integer(index) => u32::try_from(*index).ok().map(|x| x + 1),
+      _ => None,
+    },
+    Object::Dictionary(dict) => {
+      destination_page(doc, dict.get(b"D").ok()?, page_numbers)
+    }
+    Object::Name(name) => {
+      destination_page(doc, named_destination(doc, name)?, page_numbers)
+    }
+    Object::String(name, _) => {
+      destination_page(doc, named_destination(doc, name)?, page_numbers)
+    }
+    _ => None,
+  }
+}
+
+fn named_destination<'a>(doc: &'a Document, name: &[u8]) -> Option<&'a Object> {
+  let catalog = doc.catalog().ok()?;
+
+  // PDF 1.1 style dictionary of names
+  if let Some(dest) = catalog
+    .get(b"Dests")
+    .ok()
+    .and_then(|x| resolve(doc, x).as_dict().ok())
+    .and_then(|x| x.get(name).ok())
+  {
+    return Some(resolve(doc, dest));
+  }
+
+  let tree = catalog
+    .get(b"Names")
+    .ok()
+    .and_then(|x| resolve(doc, x).as_dict().ok())?
+    .get(b"Dests")
+    .ok()
+    .and_then(|x| resolve(doc, x).as_dict().ok())?;
+
+  let mut visited = HashSet::new();
+  find_in_name_tree(doc, tree, name, &mut visited)
+}
+
+fn find_in_name_tree<'a>(
+  doc: &'a Document,
+  node: &'a Dictionary,
+  name: &[u8],
+  visited: &mut HashSet<ObjectId>,
+) -> Option<&'a Object> {
+  if let Ok(names) = node.get(b"Names").and_then(|x| resolve(doc, x).as_array())
+  {
+    for pair in names.chunks(2) {
+      if let [key, value] = pair {
+        if resolve(doc, key).as_str().is_ok_and(|x| x == name) {
+          return Some(resolve(doc, value));
+        }
+      }
+    }
+  }
+
+  let kids = node.get(b"Kids").and_then(|x| resolve(doc, x).as_array()).ok()?;
+  for kid in kids {
+    if let Ok(id) = kid.as_reference() {
+      if !visited.insert(id) {
+        continue;
+      }
+    }
+
+    let Ok(kid) = resolve(doc, kid).as_dict() else { continue };
+    if let Some(found) = find_in_name_tree(doc, kid, name, visited) {
+      return Some(found);
+    }
+  }
+
+  None
+}
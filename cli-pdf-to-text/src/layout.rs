@@ -0,0 +1,349 @@
+use lopdf::{Document, Object, ObjectId};
+use pdf_extract::{
+  ColorSpace, ConvertToFmt, MediaBox, OutputDev, OutputError, Path, PathOp,
+  PlainTextOutput, Transform,
+};
+use std::cell::RefCell;
+
+use crate::{Figure, Table};
+use std::rc::Rc;
+
+/// An area of the page in default user space, the origin is at the bottom
+/// left like in the `/Rect` of an annotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+  pub left: f64,
+  pub bottom: f64,
+  pub right: f64,
+  pub top: f64,
+}
+
+impl Rect {
+  /// Reads a `[x1 y1 x2 y2]` array, the corners may be in any order.
+  pub fn from_object(doc: &Document, object: &Object) -> Option<Self> {
+    let array = resolve(doc, object).as_array().ok()?;
+    let numbers: Vec<f64> = array
+      .iter()
+      .map(|x| resolve(doc, x).as_float().map(f64::from))
+      .collect::<Result<_, _>>()
+      .ok()?;
+
+    let [x1, y1, x2, y2] = numbers[..] else {
+      return None;
+    };
+
+    Some(Self {
+      left: x1.min(x2),
+      bottom: y1.min(y2),
+      right: x1.max(x2),
+      top: y1.max(y2),
+    })
+  }
+
+  pub fn width(&self) -> f64 {
+    self.right - self.left
+  }
+
+  pub fn height(&self) -> f64 {
+    self.top - self.bottom
+  }
+
+  /// The smallest rectangle around both.
+  pub fn union(self, other: Rect) -> Rect {
+    Rect {
+      left: self.left.min(other.left),
+      bottom: self.bottom.min(other.bottom),
+      right: self.right.max(other.right),
+      top: self.top.max(other.top),
+    }
+  }
+
+  /// Whether the point is inside, or less than `margin` away from it.
+  pub fn contains(&self, x: f64, y: f64, margin: f64) -> bool {
+    x >= self.left - margin
+      && x <= self.right + margin
+      && y >= self.bottom - margin
+      && y <= self.top + margin
+  }
+}
+
+/// A character of the extracted text and where it was drawn.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Glyph {
+  /// Byte range of the character in the text
+  pub start: usize,
+  pub end: usize,
+  /// The center of the character in default user space
+  pub x: f64,
+  pub y: f64,
+  /// The advance of the character in default user space
+  pub width: f64,
+  /// The font size in default user space
+  pub size: f64,
+  /// Drawn with a bold font
+  pub bold: bool,
+}
+
+impl Glyph {
+  pub fn left(&self) -> f64 {
+    self.x - self.width / 2.0
+  }
+
+  pub fn right(&self) -> f64 {
+    self.x + self.width / 2.0
+  }
+
+  /// About the area the character covers, one font size high.
+  pub fn bbox(&self) -> Rect {
+    Rect {
+      left: self.left(),
+      bottom: self.y - self.size / 2.0,
+      right: self.right(),
+      top: self.y + self.size / 2.0,
+    }
+  }
+}
+
+/// The text of a page like `page_to_text` extracts it, with the position of
+/// every character.
+#[derive(Default)]
+pub(crate) struct PageLayout {
+  pub text: String,
+  pub glyphs: Vec<Glyph>,
+  /// Horizontal and vertical lines drawn on the page, like the rules of a
+  /// table, as rectangles that are at most `RULE_THICKNESS` thick
+  pub rules: Vec<Rect>,
+  /// The tables that replaced their text
+  pub tables: Vec<Table>,
+  /// The images whose placeholders were put into the text
+  pub figures: Vec<Figure>,
+}
+
+impl PageLayout {
+  /// Inserts `s` into the text, the offsets after it are moved along.
+  pub fn insert_str(&mut self, at: usize, s: &str) {
+    self.text.insert_str(at, s);
+
+    for glyph in self.glyphs.iter_mut().filter(|x| x.start >= at) {
+      glyph.start += s.len();
+      glyph.end += s.len();
+    }
+    for table in self.tables.iter_mut().filter(|x| x.range.start >= at) {
+      table.range.start += s.len();
+      table.range.end += s.len();
+    }
+    for figure in self.figures.iter_mut().filter(|x| x.range.start >= at) {
+      figure.range.start += s.len();
+      figure.range.end += s.len();
+    }
+  }
+}
+
+/// Thicker lines and rectangles are shapes, not rules.
+const RULE_THICKNESS: f64 = 2.0;
+
+/// Extracts the page with the same output as `page_to_text`, right-to-left
+/// text drawn from left to right is put into logical order. `page_id` is
+/// the object of page `page_num`.
+pub(crate) fn page_layout(
+  doc: &Document,
+  page_num: u32,
+  page_id: ObjectId,
+) -> Result<PageLayout, crate::PdfError> {
+  let text = SharedText::default();
+  let bold = crate::fonts::bold_strings(doc, page_id);
+  let mut output = LayoutOutput {
+    plain: PlainTextOutput::new(text.clone()),
+    text: text.clone(),
+    glyphs: Vec::new(),
+    rules: Vec::new(),
+    bold,
+    strings: 0,
+  };
+
+  crate::output_page(doc, page_num, &mut output)?;
+
+  let mut layout = PageLayout {
+    text: text.0.take(),
+    glyphs: output.glyphs,
+    rules: output.rules,
+    tables: Vec::new(),
+    figures: Vec::new(),
+  };
+  crate::bidi::logical_order(&mut layout);
+
+  return Ok(layout);
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+  doc.dereference(object).map(|(_, x)| x).unwrap_or(object)
+}
+
+/// A string the plain text output writes to, while the layout output reads
+/// how far it got.
+#[derive(Clone, Default)]
+struct SharedText(Rc<RefCell<String>>);
+
+impl std::fmt::Write for SharedText {
+  fn write_str(&mut self, s: &str) -> std::fmt::Result {
+    self.0.borrow_mut().push_str(s);
+    Ok(())
+  }
+}
+
+impl ConvertToFmt for SharedText {
+  type Writer = Self;
+
+  fn convert(self) -> Self::Writer {
+    self
+  }
+}
+
+/// Leaves the text to `PlainTextOutput`, so it is exactly the text of
+/// `page_to_text`, and records where each character ended up.
+struct LayoutOutput {
+  plain: PlainTextOutput<SharedText>,
+  text: SharedText,
+  glyphs: Vec<Glyph>,
+  rules: Vec<Rect>,
+  /// Whether each string the page shows is set in a bold font
+  bold: Vec<bool>,
+  /// How many strings have been shown, pdf-extract begins a word for each
+  strings: usize,
+}
+
+impl LayoutOutput {
+  /// Keeps the area between the corners `from` and `to` when it is thin
+  /// enough to be a rule, a stroked line has no area at all.
+  fn add_rule(&mut self, ctm: &Transform, from: (f64, f64), to: (f64, f64)) {
+    let point = |(x, y): (f64, f64)| {
+      (ctm.m11 * x + ctm.m21 * y + ctm.m31, ctm.m12 * x + ctm.m22 * y + ctm.m32)
+    };
+    let ((x1, y1), (x2, y2)) = (point(from), point(to));
+
+    let rule = Rect {
+      left: x1.min(x2),
+      bottom: y1.min(y2),
+      right: x1.max(x2),
+      top: y1.max(y2),
+    };
+    if (rule.width().min(rule.height()) <= RULE_THICKNESS) {
+      self.rules.push(rule);
+    }
+  }
+}
+
+impl OutputDev for LayoutOutput {
+  fn begin_page(
+    &mut self,
+    page_num: u32,
+    media_box: &MediaBox,
+    art_box: Option<(f64, f64, f64, f64)>,
+  ) -> Result<(), OutputError> {
+    self.plain.begin_page(page_num, media_box, art_box)
+  }
+
+  fn end_page(&mut self) -> Result<(), OutputError> {
+    self.plain.end_page()
+  }
+
+  fn output_character(
+    &mut self,
+    trm: &Transform,
+    width: f64,
+    spacing: f64,
+    font_size: f64,
+    char: &str,
+  ) -> Result<(), OutputError> {
+    self.plain.output_character(trm, width, spacing, font_size, char)?;
+
+    // The same font size PlainTextOutput uses to find the end of the glyph
+    let size_x = font_size * (trm.m11 + trm.m21);
+    let size_y = font_size * (trm.m12 + trm.m22);
+    let size = (size_x * size_y).abs().sqrt();
+
+    let end = self.text.0.borrow().len();
+    self.glyphs.push(Glyph {
+      start: end - char.len(),
+      end,
+      x: trm.m31 + width * size / 2.0,
+      y: trm.m32 + size / 3.0,
+      width: width * size,
+      size,
+      bold: self
+        .strings
+        .checked_sub(1)
+        .is_some_and(|x| self.bold.get(x) == Some(&true)),
+    });
+
+    Ok(())
+  }
+
+  fn begin_word(&mut self) -> Result<(), OutputError> {
+    self.strings += 1;
+    self.plain.begin_word()
+  }
+
+  fn end_word(&mut self) -> Result<(), OutputError> {
+    self.plain.end_word()
+  }
+
+  fn end_line(&mut self) -> Result<(), OutputError> {
+    self.plain.end_line()
+  }
+
+  fn stroke(
+    &mut self,
+    ctm: &Transform,
+    _colorspace: &ColorSpace,
+    _color: &[f64],
+    path: &Path,
+  ) -> Result<(), OutputError> {
+    let mut start = (0.0, 0.0);
+    let mut current = (0.0, 0.0);
+
+    for op in &path.ops {
+      match *op {
+        PathOp::MoveTo(x, y) => {
+          start = (x, y);
+          current = (x, y);
+        }
+        PathOp::LineTo(x, y) => {
+          self.add_rule(ctm, current, (x, y));
+          current = (x, y);
+        }
+        PathOp::Rect(x, y, width, height) => {
+          let corners =
+            [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+          for i in 0..4 {
+            self.add_rule(ctm, corners[i], corners[(i + 1) % 4]);
+          }
+        }
+        PathOp::Close => {
+          self.add_rule(ctm, current, start);
+          current = start;
+        }
+        PathOp::CurveTo(.., x, y) => current = (x, y),
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Thin filled rectangles are drawn as rules by many producers.
+  fn fill(
+    &mut self,
+    ctm: &Transform,
+    _colorspace: &ColorSpace,
+    _color: &[f64],
+    path: &Path,
+  ) -> Result<(), OutputError> {
+    for op in &path.ops {
+      if let PathOp::Rect(x, y, width, height) = *op {
+        self.add_rule(ctm, (x, y), (x + width, y + height));
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -1,84 +1,388 @@
-use redirect_stderr;
+mod annotations;
+mod bidi;
+mod blocks;
+mod error;
+mod figures;
+mod fonts;
+mod format;
+mod headings;
+mod info;
+mod layout;
+mod ocr;
+mod password;
+mod stream;
+mod tables;
+mod warning;
 
+pub use annotations::{Annotation, AnnotationKind, Link, LinkTarget};
+pub use blocks::{Block, BlockKind};
+pub use error::PdfError;
+pub use figures::Figure;
+pub use format::{pages_to_json, pages_to_markdown};
+pub use headings::Heading;
+pub use info::{pdf_info, OutlineEntry, PdfInfo};
+pub use layout::Rect;
+pub use ocr::{
+  FakeOcr, Ocr, OcrEngine, OcrError, OcrMyPdf, PageImage, ScannedPage,
+  Tesseract,
+};
+pub use password::read_password_file;
+pub use stream::{
+  pdf_to_page_stream, pdf_to_page_stream_with_ocr, Page, PageStream,
+};
+pub use tables::Table;
+pub use warning::{install_warning_logger, silence_caught_panics, Warning};
+
+use layout::PageLayout;
 use lopdf;
+use ocr::DocumentOcr;
 use pdf_extract;
-use std::{
-  env,
-  io::{BufWriter, Cursor},
-};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::env;
+
+/// Raised whenever a release converts the same document to different text,
+/// so applications that cache the text convert it again.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How far into the file the `%PDF-` header may start, readers are required
+/// to skip leading garbage up to this point.
+const HEADER_SEARCH_LEN: usize = 1024;
+
+/// Whether `bytes` start like a PDF document.
+pub fn is_pdf(bytes: &[u8]) -> bool {
+  let head = &bytes[..bytes.len().min(HEADER_SEARCH_LEN)];
+
+  head.windows(5).any(|x| x == b"%PDF-")
+}
 
-pub fn pdf_to_text(
+/// Opens the document, encrypted documents are decrypted with the empty user
+/// password when possible, which covers documents protected only by an owner
+/// password, otherwise `password` is required.
+pub fn open_document(
   pdf_path: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-  #[cfg(target_os = "windows")]
-  redirect_stderr::redirect_stdout()?;
+  password: Option<&str>,
+) -> Result<lopdf::Document, PdfError> {
+  let bytes = std::fs::read(pdf_path)?;
+  if !is_pdf(&bytes) {
+    return Err(PdfError::NotPdf);
+  }
+
+  let mut doc = lopdf::Document::load_mem(&bytes)
+    .map_err(|e| PdfError::Corrupt { page: None, reason: e.to_string() })?;
 
-  #[allow(unused_assignments)]
-  let mut original_fd = -1;
+  if !doc.is_encrypted() {
+    return Ok(doc);
+  }
 
-  #[allow(unused_assignments)]
-  let mut duplicate_fd = -1;
+  // lopdf already decrypted it with the empty password while loading
+  if doc.encryption_state.is_some() {
+    doc.trailer.remove(b"Encrypt");
+    return Ok(doc);
+  }
 
-  #[cfg(not(target_os = "windows"))]
-  {
-    extern crate libc;
+  match password {
+    Some(password) => password::decrypt_document(&bytes, doc, password),
+    None => Err(PdfError::Encrypted),
+  }
+}
 
-    use std::fs::File;
-    use std::io::{self, Write};
-    use std::os::fd::AsRawFd;
-    use std::os::unix::io::FromRawFd;
+/// Whether the document can only be opened with a password.
+pub fn is_password_protected(pdf_path: &str) -> bool {
+  matches!(open_document(pdf_path, None), Err(PdfError::Encrypted))
+}
 
-    let stdout = io::stdout();
-    original_fd = stdout.as_raw_fd();
+/// Loads the document once and decompresses its non-image streams up front,
+/// so fonts, content streams and form XObjects shared between pages are only
+/// inflated a single time instead of once per page and thread.
+pub fn load_document(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<lopdf::Document, PdfError> {
+  let mut doc = open_document(pdf_path, password)?;
 
-    duplicate_fd = unsafe { libc::dup(original_fd) };
+  doc.objects.par_iter_mut().for_each(|(_, object)| {
+    if let lopdf::Object::Stream(stream) = object {
+      let is_image = stream
+        .dict
+        .get(b"Subtype")
+        .and_then(|x| x.as_name())
+        .is_ok_and(|x| x == b"Image");
 
-    let dev_null = File::open("/dev/null").unwrap();
-    unsafe {
-      libc::dup2(dev_null.as_raw_fd(), original_fd);
+      if !is_image {
+        let _ = stream.decompress();
+      }
     }
+  });
+
+  return Ok(doc);
+}
+
+/// Reads the title, author, subject and outline. Documents without an
+/// outline get one generated from the headings of their pages, which means
+/// extracting the text.
+pub fn pdf_to_info(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<PdfInfo, PdfError> {
+  let doc = open_document(pdf_path, password)?;
+  let mut info = pdf_info(&doc);
+
+  if info.outline.is_empty() {
+    info.outline = headings::generated_outline(&doc);
   }
 
-  let path = std::path::Path::new(pdf_path);
+  return Ok(info);
+}
 
-  let mut output_buf = Vec::new();
-  {
-    let mut output_file = BufWriter::new(Cursor::new(&mut output_buf));
+/// Extracts the text of a single page, `page_num` is 1-based. pdf-extract
+/// panics on some malformed content, that is reported as a corrupt page.
+/// Right-to-left text is in logical order.
+pub fn page_to_text(
+  doc: &lopdf::Document,
+  page_num: u32,
+) -> Result<String, PdfError> {
+  let page_id = *doc.get_pages().get(&page_num).ok_or(PdfError::Corrupt {
+    page: Some(page_num),
+    reason: lopdf::Error::PageNumberNotFound(page_num).to_string(),
+  })?;
 
-    let doc = pdf_extract::Document::load(path)?;
+  return Ok(layout::page_layout(doc, page_num, page_id)?.text);
+}
 
-    pdf_extract::print_metadata(&doc);
+/// Runs pdf-extract on a single page, catching its panics.
+fn output_page(
+  doc: &lopdf::Document,
+  page_num: u32,
+  output: &mut dyn pdf_extract::OutputDev,
+) -> Result<(), PdfError> {
+  let corrupt = |reason| PdfError::Corrupt { page: Some(page_num), reason };
 
-    let mut output = Box::new(pdf_extract::PlainTextOutput::new(
-      &mut output_file as &mut dyn std::io::Write,
-    ));
+  let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    pdf_extract::output_doc_page(doc, output, page_num)
+  }));
 
-    pdf_extract::output_doc(&doc, output.as_mut())?;
+  match result {
+    Ok(Ok(())) => Ok(()),
+    Ok(Err(e)) => Err(corrupt(e.to_string())),
+    Err(payload) => Err(corrupt(warning::panic_message(&*payload))),
   }
+}
 
-  #[cfg(target_os = "windows")]
-  redirect_stderr::restore_stdout()?;
+/// The 1-based page number of every page object, to resolve destinations.
+fn page_numbers(
+  pages: &[(u32, lopdf::ObjectId)],
+) -> BTreeMap<lopdf::ObjectId, u32> {
+  pages.iter().map(|&(number, id)| (id, number)).collect()
+}
+
+/// Extracts a page while capturing its diagnostics. Pages with images but
+/// without a text layer are recognized with `ocr`, a page that can not be
+/// extracted is reported as a warning as well. Links are marked in the text
+/// and listed at its end, highlights and comments are mapped onto the text
+/// and lines set in a larger font are reported as headings. Tables are
+/// replaced with a grid of their cells and images with a placeholder before
+/// any of that.
+fn extract_page(
+  doc: &lopdf::Document,
+  page_num: u32,
+  page_id: lopdf::ObjectId,
+  page_numbers: &BTreeMap<lopdf::ObjectId, u32>,
+  ocr: Option<&DocumentOcr>,
+) -> ExtractedPage {
+  let links = annotations::page_links(doc, page_id, page_numbers);
+  let markup = annotations::page_markup(doc, page_id);
 
-  #[cfg(not(target_os = "windows"))]
-  {
-    extern crate libc;
+  let (mut result, mut warnings) =
+    warning::capture_warnings(Some(page_num), || {
+      layout::page_layout(doc, page_num, page_id)
+    });
+  let mut warn = |message: String| {
+    warnings.push(Warning {
+      page: Some(page_num),
+      source: env!("CARGO_CRATE_NAME").to_string(),
+      message,
+    })
+  };
 
-    use std::fs::File;
-    use std::io::{self, Write};
-    use std::os::fd::AsRawFd;
-    use std::os::unix::io::FromRawFd;
+  let page = ScannedPage::new(doc, page_id, page_num);
+  let has_text = result.as_ref().is_ok_and(|x| !x.text.trim().is_empty());
+  let needs_ocr =
+    |ocr: &DocumentOcr| ocr.ocr.all_pages || (!has_text && page.has_images());
+  let mut recognized = false;
 
-    unsafe {
-      libc::dup2(duplicate_fd, original_fd);
+  match ocr {
+    Some(ocr) if needs_ocr(ocr) => match ocr.recognize(&page) {
+      // The recognized text has no positions, the links are only listed
+      Ok(text) => {
+        result = Ok(PageLayout { text, ..Default::default() });
+        recognized = true;
+      }
+      Err(e) => warn(format!("could not recognize the page: {e}")),
+    },
+    None if !has_text && page.has_images() => {
+      warn("the page has no text layer, it needs OCR to be read".to_string())
     }
+    _ => {}
+  }
+
+  if let Err(e) = &result {
+    warn(format!("skipped the page: {e}"));
   }
 
-  // println!("{:?}", output_buf);
-  // panic!();
+  let mut layout = match result {
+    Ok(layout) => layout,
+    Err(e) => {
+      return ExtractedPage {
+        text: Err(e),
+        warnings,
+        tables: Vec::new(),
+        figures: Vec::new(),
+        links: Vec::new(),
+        annotations: Vec::new(),
+        headings: Vec::new(),
+        blocks: Vec::new(),
+        recognized,
+      }
+    }
+  };
+
+  tables::mark_tables(&mut layout);
+  // The image of a page without a text layer is a scan, not a figure
+  if (has_text && !recognized) {
+    figures::mark_figures(doc, page_id, &mut layout);
+  }
+  let links = match links.is_empty() {
+    true => Vec::new(),
+    false => annotations::mark_links(&mut layout, links),
+  };
+  let annotations = annotations::place_markup(&layout, markup);
+  let headings = headings::page_headings(&layout);
+  let blocks = blocks::page_blocks(&layout, &headings);
 
-  let res = std::str::from_utf8(&output_buf)
-    .expect("Could not convert to String")
-    .to_owned();
+  // After the blocks, the list of links is not part of the page
+  if !links.is_empty() {
+    annotations::list_links(&mut layout.text, &links);
+  }
+
+  ExtractedPage {
+    text: Ok(layout.text),
+    warnings,
+    tables: layout.tables,
+    figures: layout.figures,
+    links,
+    annotations,
+    headings,
+    blocks,
+    recognized,
+  }
+}
+
+struct ExtractedPage {
+  text: Result<String, PdfError>,
+  warnings: Vec<Warning>,
+  tables: Vec<Table>,
+  figures: Vec<Figure>,
+  links: Vec<Link>,
+  annotations: Vec<Annotation>,
+  headings: Vec<Heading>,
+  blocks: Vec<Block>,
+  /// The text comes from OCR instead of the text layer
+  recognized: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfText {
+  /// The text of every page, in page order
+  pub pages: Vec<String>,
+  /// Diagnostics from pdf-extract and lopdf, in page order
+  pub warnings: Vec<Warning>,
+  /// The tables of every page, in page order
+  pub tables: Vec<Table>,
+}
+
+/// Extracts all pages concurrently without writing anything to stdout or
+/// stderr, the diagnostics of the converter are returned instead. Pages that
+/// can not be extracted are left empty and reported as a warning, it is only
+/// an error when not a single page could be extracted.
+pub fn pdf_to_pages_with_warnings(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<PdfText, PdfError> {
+  pdf_to_pages_with_ocr(pdf_path, password, None)
+}
+
+/// Like `pdf_to_pages_with_warnings`, scanned pages are recognized with
+/// `ocr` and merged back in page order.
+pub fn pdf_to_pages_with_ocr(
+  pdf_path: &str,
+  password: Option<&str>,
+  ocr: Option<&Ocr>,
+) -> Result<PdfText, PdfError> {
+  let (doc, mut warnings) =
+    warning::capture_warnings(None, || load_document(pdf_path, password));
+  let doc = doc?;
+  let pages: Vec<(u32, lopdf::ObjectId)> =
+    doc.get_pages().into_iter().collect();
+
+  let ocr = ocr.map(|x| DocumentOcr::new(x, pdf_path));
+  let page_numbers = page_numbers(&pages);
+
+  let results: Vec<ExtractedPage> = pages
+    .into_par_iter()
+    .map(|(page_num, page_id)| {
+      extract_page(&doc, page_num, page_id, &page_numbers, ocr.as_ref())
+    })
+    .collect();
+
+  let mut pages = Vec::with_capacity(results.len());
+  let mut first_error = None;
+  let mut tables = Vec::new();
+  for page in results {
+    warnings.extend(page.warnings);
+    tables.extend(page.tables);
+
+    match page.text {
+      Ok(text) => pages.push(text),
+      Err(e) => {
+        pages.push(String::new());
+        first_error.get_or_insert(e);
+      }
+    }
+  }
+
+  if let Some(e) = first_error {
+    if pages.iter().all(|x| x.is_empty()) {
+      return Err(e);
+    }
+  }
+
+  return Ok(PdfText { pages, warnings, tables });
+}
+
+/// Extracts all pages concurrently, returned in page order.
+pub fn pdf_to_pages(pdf_path: &str) -> Result<Vec<String>, PdfError> {
+  pdf_to_pages_with_password(pdf_path, None)
+}
+
+pub fn pdf_to_pages_with_password(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<Vec<String>, PdfError> {
+  return Ok(pdf_to_pages_with_warnings(pdf_path, password)?.pages);
+}
+
+pub fn pdf_to_text(pdf_path: &str) -> Result<String, PdfError> {
+  pdf_to_text_with_password(pdf_path, None)
+}
+
+pub fn pdf_to_text_with_password(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<String, PdfError> {
+  let pages = pdf_to_pages_with_password(pdf_path, password)?;
 
-  return Ok(res);
+  // Every page starts on fresh lines of its own, so the pages are simply
+  // concatenated like the single pass output used to be
+  return Ok(pages.concat());
 }
@@ -1,9 +1,179 @@
 use cli_pdf_to_text;
 use std;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let file = std::env::args().nth(1).unwrap();
-  println!("{}", cli_pdf_to_text::pdf_to_text(&file)?);
+use getopts::Options;
+
+fn main() {
+  // The warnings are printed with --warnings, the panics they come with
+  // are reported as errors
+  let _ = cli_pdf_to_text::install_warning_logger();
+  cli_pdf_to_text::silence_caught_panics();
+
+  if let Err(e) = run() {
+    eprintln!("error: {e}");
+    std::process::exit(1);
+  }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+  let args: Vec<String> = std::env::args().collect();
+  let mut opts = Options::new();
+
+  opts.optflag(
+    "i",
+    "info",
+    "print the title, author, subject and table of contents",
+  );
+  opts.optopt("p", "password", "password for encrypted documents", "PASSWORD");
+  opts.optopt(
+    "",
+    "password-file",
+    "read the password for encrypted documents from the first line of FILE",
+    "FILE",
+  );
+  opts.optflag(
+    "o",
+    "ocr",
+    "recognize pages without a text layer with ocrmypdf or tesseract",
+  );
+  opts.optopt(
+    "",
+    "ocr-lang",
+    "tesseract language of the scanned pages, like eng or deu+eng",
+    "LANG",
+  );
+  opts.optopt(
+    "n",
+    "normalize",
+    "true expands ligatures and removes invisible characters, the default, \
+     ascii also replaces smart quotes and dashes, false keeps the text",
+    "MODE",
+  );
+  opts.optopt(
+    "f",
+    "format",
+    "text, the default, json with the blocks, headings and links of every \
+     page, or markdown",
+    "FORMAT",
+  );
+  opts.optopt(
+    "",
+    "tables",
+    "grid draws the tables found in the text, the default, csv prints only \
+     the tables as comma separated values",
+    "MODE",
+  );
+  opts.optflag("w", "warnings", "print conversion warnings to stderr");
+  opts.optflag("h", "help", "print this help menu");
+
+  let matches = opts.parse(&args[1..])?;
+
+  if matches.opt_present("h") || matches.free.is_empty() {
+    let brief = format!("Usage: {} FILE [options]", args[0]);
+    print!("{}", opts.usage(&brief));
+    return Ok(());
+  }
+
+  let file = &matches.free[0];
+
+  let password = match matches.opt_str("password-file") {
+    Some(x) => Some(cli_pdf_to_text::read_password_file(&x)?),
+    None => matches.opt_str("p"),
+  };
+
+  if matches.opt_present("i") {
+    let info = cli_pdf_to_text::pdf_to_info(file, password.as_deref())?;
+
+    println!("Title: {}", info.title.unwrap_or_default());
+    println!("Author: {}", info.author.unwrap_or_default());
+    println!("Subject: {}", info.subject.unwrap_or_default());
+    println!("Pages: {}", info.page_count);
+    println!();
+
+    for entry in info.outline {
+      let page = entry.page.map(|x| x.to_string()).unwrap_or_default();
+      println!("{}{} {}", "  ".repeat(entry.level), entry.title, page);
+    }
+
+    return Ok(());
+  }
+
+  let normalization = match matches.opt_str("n") {
+    Some(x) => normalize_text::Normalization::parse(&x)
+      .ok_or_else(|| format!("invalid --normalize '{x}'"))?,
+    None => normalize_text::Normalization::default(),
+  };
+
+  let csv = match matches.opt_str("tables").as_deref() {
+    None | Some("grid") => false,
+    Some("csv") => true,
+    Some(x) => return Err(format!("invalid --tables '{x}'").into()),
+  };
+
+  let format = matches.opt_str("f").unwrap_or("text".to_string());
+  if !matches!(format.as_str(), "text" | "json" | "markdown") {
+    return Err(format!("invalid --format '{format}'").into());
+  }
+
+  let ocr = match matches.opt_present("o") {
+    true => Some(
+      cli_pdf_to_text::Ocr::detect(matches.opt_str("ocr-lang").as_deref())
+        .ok_or("--ocr needs ocrmypdf or tesseract to be installed")?,
+    ),
+    false => None,
+  };
+
+  if (format != "text") {
+    let stream = cli_pdf_to_text::pdf_to_page_stream_with_ocr(
+      file,
+      password.as_deref(),
+      ocr,
+    )?;
+    let mut pages: Vec<cli_pdf_to_text::Page> = stream.pages.iter().collect();
+
+    if matches.opt_present("w") {
+      let page_warnings = pages.iter().flat_map(|x| &x.warnings);
+      for warning in stream.warnings.iter().chain(page_warnings) {
+        eprintln!("{warning}");
+      }
+    }
+
+    // The text is normalized before it is escaped, a fullwidth quotation
+    // mark would otherwise end a JSON string
+    for page in &mut pages {
+      page.normalize(&normalization);
+    }
+    let output = match format.as_str() {
+      "json" => cli_pdf_to_text::pages_to_json(&pages),
+      _ => cli_pdf_to_text::pages_to_markdown(&pages),
+    };
+    print!("{output}");
+    return Ok(());
+  }
+
+  let pdf = cli_pdf_to_text::pdf_to_pages_with_ocr(
+    file,
+    password.as_deref(),
+    ocr.as_ref(),
+  )?;
+
+  if matches.opt_present("w") {
+    for warning in &pdf.warnings {
+      eprintln!("{warning}");
+    }
+  }
+
+  if csv {
+    // A blank line between the tables, like the paragraphs of the text
+    let tables: Vec<String> = pdf.tables.iter().map(|x| x.to_csv()).collect();
+    print!("{}", normalize_text::normalize(&tables.join("\n"), &normalization));
+    return Ok(());
+  }
+
+  println!(
+    "{}",
+    normalize_text::normalize(&pdf.pages.concat(), &normalization)
+  );
 
   return Ok(());
 }
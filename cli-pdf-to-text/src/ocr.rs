@@ -0,0 +1,666 @@
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Why a page could not be recognized.
+#[derive(Debug)]
+pub enum OcrError {
+  Io(std::io::Error),
+  /// The OCR command failed, with what it printed to stderr
+  Failed(String),
+  /// The page has no image the engine can read
+  UnsupportedImage,
+}
+
+impl std::fmt::Display for OcrError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      OcrError::Io(e) => write!(f, "{e}"),
+      OcrError::Failed(reason) => write!(f, "OCR failed: {reason}"),
+      OcrError::UnsupportedImage => {
+        write!(f, "the page has no image in a format OCR can read")
+      }
+    }
+  }
+}
+
+impl std::error::Error for OcrError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      OcrError::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for OcrError {
+  fn from(e: std::io::Error) -> Self {
+    OcrError::Io(e)
+  }
+}
+
+/// Recognizes the text of pages that have no text layer.
+pub trait OcrEngine: Send + Sync {
+  fn recognize(&self, page: &ScannedPage) -> Result<String, OcrError>;
+
+  /// Identifies the engine and the settings that change its output, results
+  /// are only taken from the cache when this is the same.
+  fn cache_key(&self) -> String;
+}
+
+/// How pages are recognized during extraction.
+#[derive(Clone)]
+pub struct Ocr {
+  pub engine: Arc<dyn OcrEngine>,
+  /// Recognize every page, instead of only the pages with images but
+  /// without a text layer
+  pub all_pages: bool,
+  /// Where recognized pages are kept, keyed by the hash of the file, so
+  /// opening the document again does not redo the OCR
+  pub cache_dir: Option<PathBuf>,
+}
+
+impl Ocr {
+  pub fn new(engine: impl OcrEngine + 'static) -> Self {
+    Self { engine: Arc::new(engine), all_pages: false, cache_dir: None }
+  }
+
+  /// Uses ocrmypdf when it is installed, otherwise tesseract. `language` is
+  /// a tesseract language like `eng` or `deu+eng`.
+  pub fn detect(language: Option<&str>) -> Option<Self> {
+    let language = language.map(str::to_string);
+
+    if find_program("ocrmypdf").is_some() {
+      return Some(Self::new(OcrMyPdf { language, ..OcrMyPdf::new() }));
+    }
+    if find_program("tesseract").is_some() {
+      return Some(Self::new(Tesseract { language, ..Tesseract::new() }));
+    }
+
+    None
+  }
+}
+
+/// The OCR of a single document, with the cache directory of its pages.
+pub(crate) struct DocumentOcr {
+  pub ocr: Ocr,
+  cache: Option<PathBuf>,
+}
+
+impl DocumentOcr {
+  /// Pages are cached under the hash of the file content, so a renamed or
+  /// moved document still finds its pages, and the engine settings, since
+  /// another language gives another text.
+  pub fn new(ocr: &Ocr, pdf_path: &str) -> Self {
+    let cache = ocr.cache_dir.as_ref().and_then(|dir| {
+      let hash = file_hash(Path::new(pdf_path)).ok()?;
+      let key: String = ocr
+        .engine
+        .cache_key()
+        .chars()
+        .map(|x| if x.is_ascii_alphanumeric() || x == '-' { x } else { '_' })
+        .collect();
+
+      // Opening the document counts as a use when the cache is evicted
+      let document = dir.join(hash);
+      if let Ok(dir) = std::fs::File::open(&document) {
+        let _ = dir.set_modified(std::time::SystemTime::now());
+      }
+
+      Some(document.join(key))
+    });
+
+    Self { ocr: ocr.clone(), cache }
+  }
+
+  /// Takes the text from the cache, or recognizes the page and stores it.
+  /// Failing to write the cache is not an error, the text is still returned.
+  pub fn recognize(&self, page: &ScannedPage) -> Result<String, OcrError> {
+    let path =
+      self.cache.as_ref().map(|x| x.join(format!("{}.txt", page.number)));
+    if let Some(text) =
+      path.as_ref().and_then(|x| std::fs::read_to_string(x).ok())
+    {
+      return Ok(text);
+    }
+
+    let text = self.ocr.engine.recognize(page)?;
+    if let Some(path) = path {
+      let _ = write_atomically(&path, &text);
+    }
+
+    return Ok(text);
+  }
+}
+
+fn file_hash(path: &Path) -> std::io::Result<String> {
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+
+  return Ok(format!("{:x}", hasher.finalize()));
+}
+
+/// Writes next to `path` and renames, so a concurrent reader or an
+/// interrupted write never leaves a partial page in the cache.
+fn write_atomically(path: &Path, text: &str) -> std::io::Result<()> {
+  let dir = path.parent().unwrap_or(Path::new("."));
+  std::fs::create_dir_all(dir)?;
+
+  let mut file = tempfile::NamedTempFile::new_in(dir)?;
+  std::io::Write::write_all(&mut file, text.as_bytes())?;
+  file.persist(path).map_err(|e| e.error)?;
+
+  return Ok(());
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+  let paths = std::env::var_os("PATH")?;
+
+  std::env::split_paths(&paths).map(|x| x.join(name)).find(|x| x.is_file())
+}
+
+/// A page handed to an `OcrEngine`.
+pub struct ScannedPage<'a> {
+  doc: &'a Document,
+  page_id: ObjectId,
+  /// 1-based page number
+  pub number: u32,
+}
+
+impl<'a> ScannedPage<'a> {
+  pub fn new(doc: &'a Document, page_id: ObjectId, number: u32) -> Self {
+    Self { doc, page_id, number }
+  }
+
+  /// Whether the page shows any images, scanned pages are one large image.
+  pub fn has_images(&self) -> bool {
+    !page_images(self.doc, self.page_id).is_empty()
+  }
+
+  /// The page as a document of its own, decrypted and without the rest of
+  /// the document, so command line tools only have to process this page.
+  pub fn to_pdf(&self) -> Result<Vec<u8>, OcrError> {
+    let mut doc = single_page_document(self.doc, self.page_id)
+      .ok_or_else(|| OcrError::Failed("the page can not be copied".into()))?;
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)?;
+
+    Ok(bytes)
+  }
+
+  /// The largest image of the page, encoded in a file format tesseract reads.
+  pub fn image(&self) -> Option<PageImage> {
+    page_images(self.doc, self.page_id)
+      .into_iter()
+      .filter_map(|x| Some((image_area(x)?, x)))
+      .max_by_key(|(area, _)| *area)
+      .and_then(|(_, x)| encode_image(self.doc, x))
+  }
+}
+
+/// An image file in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageImage {
+  pub data: Vec<u8>,
+  /// The file extension of the format e.g. `jpg`
+  pub extension: &'static str,
+}
+
+/// Runs `ocrmypdf` on the page.
+pub struct OcrMyPdf {
+  pub program: PathBuf,
+  /// Tesseract language, the default of ocrmypdf when `None`
+  pub language: Option<String>,
+}
+
+impl OcrMyPdf {
+  pub fn new() -> Self {
+    Self::with_program("ocrmypdf")
+  }
+
+  pub fn with_program(program: impl Into<PathBuf>) -> Self {
+    Self { program: program.into(), language: None }
+  }
+}
+
+impl Default for OcrMyPdf {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl OcrEngine for OcrMyPdf {
+  fn recognize(&self, page: &ScannedPage) -> Result<String, OcrError> {
+    let dir = private_temp_dir()?;
+    let input = dir.path().join("page.pdf");
+    let output = dir.path().join("page-ocr.pdf");
+    std::fs::write(&input, page.to_pdf()?)?;
+
+    let mut command = std::process::Command::new(&self.program);
+    command.args(["--force-ocr", "--output-type", "pdf", "--quiet"]);
+    if let Some(language) = &self.language {
+      command.args(["-l", language]);
+    }
+    run(command.arg(&input).arg(&output))?;
+
+    let doc =
+      Document::load(&output).map_err(|e| OcrError::Failed(e.to_string()))?;
+
+    crate::page_to_text(&doc, 1).map_err(|e| OcrError::Failed(e.to_string()))
+  }
+
+  fn cache_key(&self) -> String {
+    format!("ocrmypdf-{}", self.language.as_deref().unwrap_or("default"))
+  }
+}
+
+/// Runs `tesseract` on the largest image of the page.
+pub struct Tesseract {
+  pub program: PathBuf,
+  /// Tesseract language, the default of tesseract when `None`
+  pub language: Option<String>,
+}
+
+impl Tesseract {
+  pub fn new() -> Self {
+    Self::with_program("tesseract")
+  }
+
+  pub fn with_program(program: impl Into<PathBuf>) -> Self {
+    Self { program: program.into(), language: None }
+  }
+}
+
+impl Default for Tesseract {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl OcrEngine for Tesseract {
+  fn recognize(&self, page: &ScannedPage) -> Result<String, OcrError> {
+    let image = page.image().ok_or(OcrError::UnsupportedImage)?;
+    let dir = private_temp_dir()?;
+    let input = dir.path().join(format!("page.{}", image.extension));
+    std::fs::write(&input, &image.data)?;
+
+    let mut command = std::process::Command::new(&self.program);
+    command.arg(&input).arg("stdout");
+    if let Some(language) = &self.language {
+      command.args(["-l", language]);
+    }
+
+    run(&mut command)
+  }
+
+  fn cache_key(&self) -> String {
+    format!("tesseract-{}", self.language.as_deref().unwrap_or("default"))
+  }
+}
+
+/// Answers with a fixed text instead of recognizing anything, and remembers
+/// which pages it was asked for.
+#[derive(Default)]
+pub struct FakeOcr {
+  text: String,
+  recognized: Mutex<Vec<u32>>,
+}
+
+impl FakeOcr {
+  /// `{page}` in `text` is replaced with the page number.
+  pub fn new(text: &str) -> Self {
+    Self { text: text.to_string(), recognized: Mutex::new(Vec::new()) }
+  }
+
+  /// The page numbers recognized so far, in ascending order.
+  pub fn recognized_pages(&self) -> Vec<u32> {
+    let mut pages = self.recognized.lock().unwrap().clone();
+    pages.sort_unstable();
+    pages
+  }
+}
+
+impl OcrEngine for FakeOcr {
+  fn recognize(&self, page: &ScannedPage) -> Result<String, OcrError> {
+    self.recognized.lock().unwrap().push(page.number);
+
+    Ok(self.text.replace("{page}", &page.number.to_string()))
+  }
+
+  fn cache_key(&self) -> String {
+    "fake".to_string()
+  }
+}
+
+/// Runs the command and returns its stdout, failing when it exits with an
+/// error.
+fn run(command: &mut std::process::Command) -> Result<String, OcrError> {
+  let output = command.output()?;
+
+  if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    return Err(OcrError::Failed(stderr.trim().to_string()));
+  }
+
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// A directory only the current user can access, removed with everything in
+/// it when dropped, also when the OCR fails or panics.
+fn private_temp_dir() -> std::io::Result<tempfile::TempDir> {
+  tempfile::Builder::new().prefix("cli-pdf-to-text-ocr-").tempdir()
+}
+
+pub(crate) fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+  doc.dereference(object).map(|(_, x)| x).unwrap_or(object)
+}
+
+/// Looks up an attribute the page may inherit from its ancestors.
+pub(crate) fn inherited<'a>(
+  doc: &'a Document,
+  page_id: ObjectId,
+  key: &[u8],
+) -> Option<&'a Object> {
+  let mut visited = HashSet::new();
+  let mut node = doc.get_dictionary(page_id).ok()?;
+
+  loop {
+    if let Ok(value) = node.get(key) {
+      return Some(value);
+    }
+
+    let parent = node.get(b"Parent").and_then(|x| x.as_reference()).ok()?;
+    if !visited.insert(parent) {
+      return None;
+    }
+    node = doc.get_dictionary(parent).ok()?;
+  }
+}
+
+/// The image XObjects of the page, including those of its form XObjects.
+fn page_images(doc: &Document, page_id: ObjectId) -> Vec<&Stream> {
+  let mut images = Vec::new();
+  let mut visited = HashSet::new();
+
+  if let Some(resources) = inherited(doc, page_id, b"Resources")
+    .and_then(|x| resolve(doc, x).as_dict().ok())
+  {
+    collect_images(doc, resources, &mut visited, &mut images);
+  }
+
+  images
+}
+
+fn collect_images<'a>(
+  doc: &'a Document,
+  resources: &'a Dictionary,
+  visited: &mut HashSet<ObjectId>,
+  images: &mut Vec<&'a Stream>,
+) {
+  let Some(xobjects) =
+    resources.get(b"XObject").ok().and_then(|x| resolve(doc, x).as_dict().ok())
+  else {
+    return;
+  };
+
+  for (_, xobject) in xobjects.iter() {
+    if let Ok(id) = xobject.as_reference() {
+      if !visited.insert(id) {
+        continue;
+      }
+    }
+
+    let Ok(stream) = resolve(doc, xobject).as_stream() else { continue };
+    let subtype = stream.dict.get(b"Subtype").and_then(|x| x.as_name());
+
+    match subtype {
+      Ok(b"Image") => images.push(stream),
+      Ok(b"Form") => {
+        if let Some(resources) = stream
+          .dict
+          .get(b"Resources")
+          .ok()
+          .and_then(|x| resolve(doc, x).as_dict().ok())
+        {
+          collect_images(doc, resources, visited, images);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Copies the page and everything it references into a new document, the
+/// attributes it inherits are copied onto the page itself.
+fn single_page_document(doc: &Document, page_id: ObjectId) -> Option<Document> {
+  let mut page = doc.get_dictionary(page_id).ok()?.clone();
+  for key in [b"Resources".as_slice(), b"MediaBox", b"CropBox", b"Rotate"] {
+    if !page.has(key) {
+      if let Some(value) = inherited(doc, page_id, key) {
+        page.set(key, value.clone());
+      }
+    }
+  }
+  page.remove(b"Parent");
+
+  let mut out = Document::with_version(doc.version.clone());
+  let mut pending = Vec::new();
+  collect_references(&Object::Dictionary(page.clone()), &mut pending);
+
+  while let Some(id) = pending.pop() {
+    if id == page_id || out.objects.contains_key(&id) {
+      continue;
+    }
+    let Ok(object) = doc.get_object(id) else { continue };
+
+    collect_references(object, &mut pending);
+    out.objects.insert(id, object.clone());
+  }
+
+  out.max_id = doc.max_id;
+  let pages_id = out.new_object_id();
+  page.set("Parent", pages_id);
+  out.objects.insert(page_id, Object::Dictionary(page));
+  out.objects.insert(
+    pages_id,
+    Object::Dictionary(dictionary! {
+      "Type" => "Pages",
+      "Kids" => vec![page_id.into()],
+      "Count" => 1,
+    }),
+  );
+  let catalog_id = out.add_object(dictionary! {
+    "Type" => "Catalog",
+    "Pages" => pages_id,
+  });
+  out.trailer.set("Root", catalog_id);
+
+  Some(out)
+}
+
+/// Collects the references of `object`, except `/Parent` links which would
+/// pull in the rest of the page tree.
+fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+  let dict = match object {
+    Object::Reference(id) => return references.push(*id),
+    Object::Array(array) => {
+      for item in array {
+        collect_references(item, references);
+      }
+      return;
+    }
+    Object::Dictionary(dict) => dict,
+    Object::Stream(stream) => &stream.dict,
+    _ => return,
+  };
+
+  for (key, value) in dict.iter() {
+    if key != b"Parent" {
+      collect_references(value, references);
+    }
+  }
+}
+
+/// The width and height of the image in pixels, `None` when they are
+/// missing or negative.
+fn image_size(image: &Stream) -> Option<(usize, usize)> {
+  let size = |key: &[u8]| {
+    let value = image.dict.get(key).and_then(|x| x.as_i64()).ok()?;
+    usize::try_from(value).ok()
+  };
+
+  Some((size(b"Width")?, size(b"Height")?))
+}
+
+/// The pixels of the image, `None` when there are too many to count.
+fn image_area(image: &Stream) -> Option<usize> {
+  let (width, height) = image_size(image)?;
+
+  width.checked_mul(height)
+}
+
+fn is_inverted(image: &Stream) -> bool {
+  image
+    .dict
+    .get(b"Decode")
+    .and_then(|x| x.as_array())
+    .is_ok_and(|x| x.first().and_then(|x| x.as_float().ok()) == Some(1.0))
+}
+
+/// Re-encodes the image stream as a standalone file, images in formats that
+/// have no standalone equivalent or need a palette are not supported.
+pub(crate) fn encode_image(
+  doc: &Document,
+  image: &Stream,
+) -> Option<PageImage> {
+  let filters = image.filters().unwrap_or_default();
+
+  match filters.as_slice() {
+    [b"DCTDecode"] => {
+      Some(PageImage { data: image.content.clone(), extension: "jpg" })
+    }
+    [b"JPXDecode"] => {
+      Some(PageImage { data: image.content.clone(), extension: "jp2" })
+    }
+    [b"CCITTFaxDecode"] => encode_ccitt(image),
+    filters
+      if filters.iter().all(|x| {
+        matches!(*x, b"FlateDecode" | b"LZWDecode" | b"ASCII85Decode")
+      }) =>
+    {
+      encode_pnm(doc, image)
+    }
+    _ => None,
+  }
+}
+
+/// The number of color components of a device, calibrated or ICC based
+/// color space.
+fn color_components(doc: &Document, image: &Stream) -> Option<usize> {
+  let color_space = resolve(doc, image.dict.get(b"ColorSpace").ok()?);
+
+  let (name, params) = match color_space {
+    Object::Name(name) => (name.as_slice(), None),
+    Object::Array(array) => (array.first()?.as_name().ok()?, array.get(1)),
+    _ => return None,
+  };
+
+  match name {
+    b"DeviceGray" | b"CalGray" => Some(1),
+    b"DeviceRGB" | b"CalRGB" => Some(3),
+    b"ICCBased" => {
+      let profile = resolve(doc, params?).as_stream().ok()?;
+      match profile.dict.get(b"N").and_then(|x| x.as_i64()) {
+        Ok(1) => Some(1),
+        Ok(3) => Some(3),
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+/// Raw 8 bit gray or RGB, or 1 bit gray images as binary PNM.
+fn encode_pnm(doc: &Document, image: &Stream) -> Option<PageImage> {
+  let (width, height) = image_size(image)?;
+  let bits = image.dict.get(b"BitsPerComponent").and_then(|x| x.as_i64());
+  let components = color_components(doc, image)?;
+  let pixels = image.get_plain_content().ok()?;
+
+  let (magic, row_len) = match (bits, components) {
+    (Ok(8), 1) => ("P5", width),
+    (Ok(8), 3) => ("P6", width.checked_mul(3)?),
+    (Ok(1), 1) => ("P4", width.div_ceil(8)),
+    _ => return None,
+  };
+  let pixels = pixels.get(..row_len.checked_mul(height)?)?;
+
+  let mut data = match magic {
+    "P4" => format!("{magic}\n{width} {height}\n"),
+    _ => format!("{magic}\n{width} {height}\n255\n"),
+  }
+  .into_bytes();
+
+  // In PDF a set bit is white, in PBM it is black
+  if magic == "P4" && !is_inverted(image) {
+    data.extend(pixels.iter().map(|x| !x));
+  } else {
+    data.extend_from_slice(pixels);
+  }
+
+  Some(PageImage { data, extension: "pnm" })
+}
+
+/// Wraps CCITT group 3 or 4 fax data in a single strip TIFF.
+fn encode_ccitt(image: &Stream) -> Option<PageImage> {
+  let params = image.dict.get(b"DecodeParms").ok().and_then(|x| match x {
+    Object::Array(array) => array.first()?.as_dict().ok(),
+    x => x.as_dict().ok(),
+  });
+  let param = |key: &[u8]| params?.get(key).and_then(|x| x.as_i64()).ok();
+
+  let k = param(b"K").unwrap_or(0);
+  let width = u32::try_from(param(b"Columns").unwrap_or(1728)).ok()?;
+  let height = match param(b"Rows") {
+    Some(rows) if rows > 0 => u32::try_from(rows).ok()?,
+    _ => u32::try_from(image_size(image)?.1).ok()?,
+  };
+
+  let compression = if (k < 0) { 4 } else { 3 };
+  let photometric = if is_inverted(image) { 1 } else { 0 };
+
+  let mut entries: Vec<(u16, u16, u32)> = vec![
+    (256, 4, width),
+    (257, 4, height),
+    (258, 3, 1),
+    (259, 3, compression),
+    (262, 3, photometric),
+    (273, 4, 0),
+    (277, 3, 1),
+    (278, 4, height),
+    (279, 4, image.content.len() as u32),
+  ];
+  if compression == 3 {
+    entries.push((292, 4, if (k > 0) { 1 } else { 0 }));
+  }
+
+  let data_offset = 8 + 2 + entries.len() as u32 * 12 + 4;
+  entries[5].2 = data_offset;
+
+  let mut data = b"II*\0".to_vec();
+  data.extend(8u32.to_le_bytes());
+  data.extend((entries.len() as u16).to_le_bytes());
+  for (tag, kind, value) in entries {
+    data.extend(tag.to_le_bytes());
+    data.extend(kind.to_le_bytes());
+    data.extend(1u32.to_le_bytes());
+    data.extend(value.to_le_bytes());
+  }
+  data.extend(0u32.to_le_bytes());
+  data.extend_from_slice(&image.content);
+
+  Some(PageImage { data, extension: "tif" })
+}
@@ -0,0 +1,62 @@
+use std::collections::{BTreeMap, HashSet};
+
+use lopdf::encryption::DecryptionError;
+use lopdf::xref::XrefEntry;
+
+use crate::PdfError;
+
+/// Reads a password from the first line of `path`, so it does not have to be
+/// passed on the command line where it would end up in the shell history.
+pub fn read_password_file(path: &str) -> Result<String, PdfError> {
+  let content = std::fs::read_to_string(path)?;
+  let password = content.lines().next().unwrap_or_default();
+
+  return Ok(password.to_string());
+}
+
+/// Decrypts a document lopdf could not decrypt with the empty user password
+/// while loading. Its loader then skips every object but the encryption
+/// dictionary, so they are read again at their offsets in the
+/// cross-reference table, which it does keep, before decrypting them.
+pub(crate) fn decrypt_document(
+  bytes: &[u8],
+  document: lopdf::Document,
+  password: &str,
+) -> Result<lopdf::Document, PdfError> {
+  // Offsets count from the header, like the loader counts them
+  let start = bytes.windows(5).position(|x| x == b"%PDF-").unwrap_or(0);
+  let reader = lopdf::Reader {
+    buffer: &bytes[start..],
+    document,
+    encryption_state: None,
+    raw_objects: BTreeMap::new(),
+  };
+
+  let objects: Vec<(lopdf::ObjectId, lopdf::Object)> = reader
+    .document
+    .reference_table
+    .entries
+    .iter()
+    .filter_map(|(&number, entry)| match *entry {
+      XrefEntry::Normal { generation, .. } => {
+        let id = (number, generation);
+        let object = reader.get_object(id, &mut HashSet::new()).ok()?;
+        Some((id, object))
+      }
+      _ => None,
+    })
+    .collect();
+
+  let mut doc = reader.document;
+  for (id, object) in objects {
+    doc.objects.entry(id).or_insert(object);
+  }
+
+  match doc.decrypt(password) {
+    Ok(()) => Ok(doc),
+    Err(lopdf::Error::Decryption(DecryptionError::IncorrectPassword)) => {
+      Err(PdfError::IncorrectPassword)
+    }
+    Err(e) => Err(PdfError::Corrupt { page: None, reason: e.to_string() }),
+  }
+}
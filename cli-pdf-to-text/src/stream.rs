@@ -0,0 +1,189 @@
+use rayon::prelude::*;
+use std::ops::Range;
+use std::sync::mpsc;
+
+use crate::headings::outline_entries;
+use crate::ocr::DocumentOcr;
+use crate::{
+  extract_page, load_document, page_numbers, pdf_info, warning, Annotation,
+  Block, Figure, Heading, Link, Ocr, OutlineEntry, PdfError, Table, Warning,
+};
+
+/// A single extracted page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Page {
+  /// 1-based page number
+  pub number: u32,
+  /// Empty when the page could not be extracted, see `warnings`
+  pub text: String,
+  pub warnings: Vec<Warning>,
+  /// The page could not be extracted, the last warning says why
+  pub failed: bool,
+  /// The tables of the page, their grids are in the text
+  pub tables: Vec<Table>,
+  /// The images of the page, their placeholders are in the text
+  pub figures: Vec<Figure>,
+  /// The links of the page, their `[number]` markers are in the text
+  pub links: Vec<Link>,
+  /// Highlights and comments added in other viewers
+  pub annotations: Vec<Annotation>,
+  /// Lines set in a larger font than the body text, in page order
+  pub headings: Vec<Heading>,
+  /// The entries of the document outline that lead to the page, or its
+  /// headings when the document has no outline
+  pub outline: Vec<OutlineEntry>,
+  /// The paragraphs, headings, list items and tables of the text, without
+  /// the list of links at its end
+  pub blocks: Vec<Block>,
+  /// The text was recognized with OCR, which is much slower than reading
+  /// the text layer
+  pub recognized: bool,
+}
+
+impl Page {
+  /// Cleans up the text and the headings, cells, captions and notes of the
+  /// page like `normalize_text::normalize`, the ranges into the text are
+  /// moved along. The text between two ends of a range is normalized on its
+  /// own, so no range ends up in the middle of a character.
+  pub fn normalize(&mut self, normalization: &normalize_text::Normalization) {
+    let normalize = |x: &str| normalize_text::normalize(x, normalization);
+
+    let ranges = self
+      .blocks
+      .iter()
+      .map(|x| &x.range)
+      .chain(self.tables.iter().map(|x| &x.range))
+      .chain(self.figures.iter().map(|x| &x.range))
+      .chain(self.headings.iter().map(|x| &x.range))
+      .chain(self.annotations.iter().filter_map(|x| x.range.as_ref()));
+    let mut ends: Vec<usize> = ranges
+      .flat_map(|x| [x.start, x.end])
+      .chain([self.text.len()])
+      .filter(|&x| self.text.is_char_boundary(x))
+      .collect();
+    ends.sort();
+    ends.dedup();
+
+    // Where each end is in the normalized text
+    let mut moved = vec![(0, 0)];
+    let mut text = String::with_capacity(self.text.len());
+    for end in ends {
+      let (start, _) = moved[moved.len() - 1];
+      text.push_str(&normalize(&self.text[start..end]));
+      moved.push((end, text.len()));
+    }
+    let moved = |x: usize| {
+      let i = moved.partition_point(|&(end, _)| end <= x);
+      moved[i.saturating_sub(1)].1
+    };
+    let move_range = |x: &mut Range<usize>| *x = moved(x.start)..moved(x.end);
+
+    self.text = text;
+    self.blocks.iter_mut().for_each(|x| move_range(&mut x.range));
+    for table in &mut self.tables {
+      move_range(&mut table.range);
+      for cell in table.rows.iter_mut().flatten() {
+        *cell = normalize(cell);
+      }
+    }
+    for figure in &mut self.figures {
+      move_range(&mut figure.range);
+      figure.caption = figure.caption.as_deref().map(normalize);
+    }
+    for heading in &mut self.headings {
+      move_range(&mut heading.range);
+      heading.text = normalize(&heading.text);
+    }
+    for entry in &mut self.outline {
+      entry.title = normalize(&entry.title);
+    }
+    for annotation in &mut self.annotations {
+      if let Some(range) = &mut annotation.range {
+        move_range(range);
+      }
+      annotation.note = annotation.note.as_deref().map(normalize);
+    }
+  }
+}
+
+/// Pages that are extracted in the background and arrive in page order.
+pub struct PageStream {
+  pub page_count: usize,
+  /// Diagnostics from loading the document
+  pub warnings: Vec<Warning>,
+  pub pages: mpsc::Receiver<Page>,
+}
+
+/// Loads the document and extracts its pages on a background thread, so the
+/// first pages can be shown while the rest is still being extracted. Errors
+/// opening the document are returned right away, pages that can not be
+/// extracted arrive empty. Dropping the receiver stops the extraction.
+pub fn pdf_to_page_stream(
+  pdf_path: &str,
+  password: Option<&str>,
+) -> Result<PageStream, PdfError> {
+  pdf_to_page_stream_with_ocr(pdf_path, password, None)
+}
+
+/// Like `pdf_to_page_stream`, scanned pages are recognized with `ocr` before
+/// they are sent.
+pub fn pdf_to_page_stream_with_ocr(
+  pdf_path: &str,
+  password: Option<&str>,
+  ocr: Option<Ocr>,
+) -> Result<PageStream, PdfError> {
+  let (doc, warnings) =
+    warning::capture_warnings(None, || load_document(pdf_path, password));
+  let doc = doc?;
+  let pages: Vec<(u32, lopdf::ObjectId)> =
+    doc.get_pages().into_iter().collect();
+  let page_count = pages.len();
+  let ocr = ocr.map(|x| DocumentOcr::new(&x, pdf_path));
+  let page_numbers = page_numbers(&pages);
+  let outline = pdf_info(&doc).outline;
+
+  let (sender, receiver) = mpsc::channel();
+
+  std::thread::spawn(move || {
+    // Batches of one page per thread keep the output in order while the
+    // first pages are still available almost immediately
+    for batch in pages.chunks(rayon::current_num_threads()) {
+      let pages: Vec<Page> = batch
+        .par_iter()
+        .map(|&(number, page_id)| {
+          let page =
+            extract_page(&doc, number, page_id, &page_numbers, ocr.as_ref());
+          Page {
+            number,
+            failed: page.text.is_err(),
+            text: page.text.unwrap_or_default(),
+            warnings: page.warnings,
+            tables: page.tables,
+            figures: page.figures,
+            links: page.links,
+            annotations: page.annotations,
+            outline: match outline.is_empty() {
+              true => outline_entries(number, &page.headings),
+              false => outline
+                .iter()
+                .filter(|x| x.page == Some(number))
+                .cloned()
+                .collect(),
+            },
+            headings: page.headings,
+            blocks: page.blocks,
+            recognized: page.recognized,
+          }
+        })
+        .collect();
+
+      for page in pages {
+        if sender.send(page).is_err() {
+          return;
+        }
+      }
+    }
+  });
+
+  return Ok(PageStream { page_count, warnings, pages: receiver });
+}
@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::headings::most_common_size;
+use crate::layout::{Glyph, PageLayout, Rect};
+
+/// Runs of text further apart than this many font sizes are cells of
+/// different columns, words are much closer even in justified text.
+const COLUMN_GAP: f64 = 1.0;
+
+/// Characters closer than this many font sizes belong to the same word.
+const WORD_GAP: f64 = 0.15;
+
+/// Rows further apart than this many font sizes start a new table.
+const MAX_ROW_GAP: f64 = 2.5;
+
+/// Tables without rules need this many rows, two columns of body text would
+/// be mistaken for a table otherwise.
+const MIN_ROWS: usize = 3;
+
+/// The cells of a column of a table without rules are this short on
+/// average, longer runs are columns of text or the items of a list.
+const MAX_MEAN_CELL_LEN: usize = 30;
+
+/// A horizontal rule this close to the rows, in font sizes, belongs to the
+/// table.
+const RULE_DISTANCE: f64 = 1.5;
+
+/// A table of a page, found from text aligned in columns and the rules
+/// drawn between them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+  /// The cells row by row, every row has a cell for every column
+  pub rows: Vec<Vec<String>>,
+  /// Byte range of the table, rendered as a grid, in the page text
+  pub range: Range<usize>,
+  /// Where the cells are drawn on the page
+  pub bbox: Option<Rect>,
+  /// The font size most of the cells are set in
+  pub font_size: Option<f64>,
+}
+
+impl Table {
+  /// The rows as comma separated values, cells are quoted when needed.
+  pub fn to_csv(&self) -> String {
+    let cell = |x: &String| match x.contains([',', '"', '\n']) {
+      true => format!("\"{}\"", x.replace('"', "\"\"")),
+      false => x.clone(),
+    };
+
+    self
+      .rows
+      .iter()
+      .map(|row| row.iter().map(cell).collect::<Vec<_>>().join(","))
+      .map(|x| x + "\n")
+      .collect()
+  }
+
+  /// The rows as `| a | b |` lines with the columns padded to the same
+  /// width, the format the justifier keeps as a table.
+  pub fn to_grid(&self) -> String {
+    let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+      .map(|i| {
+        let cells = self.rows.iter().filter_map(|x| x.get(i));
+        cells.map(|x| escape(x).chars().count()).max().unwrap_or(0)
+      })
+      .collect();
+
+    let lines: Vec<String> = self
+      .rows
+      .iter()
+      .map(|row| {
+        let cells: Vec<String> = widths
+          .iter()
+          .enumerate()
+          .map(|(i, width)| {
+            let cell = escape(row.get(i).map_or("", |x| x.as_str()));
+            format!("{cell}{}", " ".repeat(width - cell.chars().count()))
+          })
+          .collect();
+
+        format!("| {} |", cells.join(" | "))
+      })
+      .collect();
+
+    lines.join("\n")
+  }
+}
+
+fn escape(cell: &str) -> String {
+  cell.replace('|', "\\|")
+}
+
+/// Characters of a line that are close enough to be a single cell.
+struct Run {
+  glyphs: Vec<usize>,
+  left: f64,
+  right: f64,
+}
+
+/// The characters drawn at about the same height, split into runs.
+struct Row {
+  y: f64,
+  size: f64,
+  runs: Vec<Run>,
+  /// A vertical rule separates some of the runs
+  ruled: bool,
+}
+
+/// Replaces the tables of the page with `Table::to_grid` blocks, each its
+/// own paragraph, and adds them to `layout.tables`. The characters of a
+/// table are dropped from the layout, only tables whose text is contiguous
+/// in the page text are replaced.
+pub(crate) fn mark_tables(layout: &mut PageLayout) {
+  let mut found = Vec::new();
+  let mut block: Vec<Row> = Vec::new();
+
+  for row in text_rows(layout) {
+    let continues =
+      block.last().is_some_and(|x| x.y - row.y <= MAX_ROW_GAP * x.size);
+
+    if (row.runs.len() < 2 || !continues) {
+      found.extend(table(layout, &std::mem::take(&mut block)));
+    }
+    if (row.runs.len() >= 2) {
+      block.push(row);
+    }
+  }
+  found.extend(table(layout, &block));
+
+  // From the end, so the offsets of the tables before stay valid
+  let mut tables: Vec<Table> = Vec::new();
+  found.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+
+  for (range, mut table) in found {
+    let grid = format!("\n\n{}\n\n", table.to_grid());
+    let delta = grid.len() as isize - range.len() as isize;
+
+    layout.text.replace_range(range.clone(), &grid);
+    table.range = range.start + 2..range.start + grid.len() - 2;
+
+    layout.glyphs.retain(|x| !range.contains(&x.start));
+    for glyph in layout.glyphs.iter_mut().filter(|x| x.start >= range.end) {
+      glyph.start = glyph.start.saturating_add_signed(delta);
+      glyph.end = glyph.end.saturating_add_signed(delta);
+    }
+    for later in &mut tables {
+      later.range.start = later.range.start.saturating_add_signed(delta);
+      later.range.end = later.range.end.saturating_add_signed(delta);
+    }
+
+    tables.push(table);
+  }
+
+  tables.reverse();
+  layout.tables = tables;
+}
+
+/// The visible characters grouped into rows from the top of the page down,
+/// each split into runs at wide gaps and vertical rules.
+fn text_rows(layout: &PageLayout) -> Vec<Row> {
+  let glyphs = &layout.glyphs;
+  let mut visible: Vec<usize> = (0..glyphs.len())
+    .filter(|&i| !layout.text[glyphs[i].start..glyphs[i].end].trim().is_empty())
+    .collect();
+  visible.sort_by(|&a, &b| glyphs[b].y.total_cmp(&glyphs[a].y));
+
+  let mut lines: Vec<Vec<usize>> = Vec::new();
+  for i in visible {
+    match lines.last_mut() {
+      Some(line)
+        if (glyphs[line[0]].y - glyphs[i].y).abs() <= glyphs[i].size / 2.0 =>
+      {
+        line.push(i)
+      }
+      _ => lines.push(vec![i]),
+    }
+  }
+
+  let vertical: Vec<&Rect> =
+    layout.rules.iter().filter(|x| x.height() > x.width()).collect();
+
+  lines
+    .into_iter()
+    .map(|mut line| {
+      line.sort_by(|&a, &b| glyphs[a].x.total_cmp(&glyphs[b].x));
+      let y = glyphs[line[0]].y;
+      let size = line.iter().map(|&i| glyphs[i].size).fold(0.0, f64::max);
+
+      let mut runs: Vec<Run> = Vec::new();
+      let mut ruled = false;
+      for i in line {
+        let glyph = &glyphs[i];
+        let split = runs.last().map(|run| {
+          let gap = glyph.left() - run.right;
+          let rule = vertical.iter().any(|x| {
+            x.left >= run.right - 0.5
+              && x.right <= glyph.left() + 0.5
+              && x.bottom <= y
+              && x.top >= y
+          });
+          (gap > COLUMN_GAP * size, rule)
+        });
+
+        match (runs.last_mut(), split) {
+          (Some(run), Some((false, false))) => {
+            run.glyphs.push(i);
+            run.right = run.right.max(glyph.right());
+          }
+          _ => {
+            ruled |= split.is_some_and(|(_, rule)| rule);
+            runs.push(Run {
+              glyphs: vec![i],
+              left: glyph.left(),
+              right: glyph.right(),
+            });
+          }
+        }
+      }
+
+      Row { y, size, runs, ruled }
+    })
+    .collect()
+}
+
+/// The byte range of the rows in the text and their cells, when they are
+/// aligned in columns like a table.
+fn table(layout: &PageLayout, rows: &[Row]) -> Option<(Range<usize>, Table)> {
+  let (first, last) = (rows.first()?, rows.last()?);
+  if (rows.len() < 2) {
+    return None;
+  }
+
+  let mut columns: Vec<(f64, f64)> = Vec::new();
+  let mut runs: Vec<&Run> = rows.iter().flat_map(|x| &x.runs).collect();
+  runs.sort_by(|a, b| a.left.total_cmp(&b.left));
+
+  // Runs that overlap horizontally are in the same column
+  for run in runs {
+    match columns.last_mut() {
+      Some(column) if run.left <= column.1 => {
+        column.1 = column.1.max(run.right)
+      }
+      _ => columns.push((run.left, run.right)),
+    }
+  }
+  if (columns.len() < 2) {
+    return None;
+  }
+
+  let (left, right) = (columns[0].0, columns[columns.len() - 1].1);
+  let ruled = rows.iter().any(|x| x.ruled)
+    || layout.rules.iter().any(|x| {
+      x.width() >= (right - left) * 0.8
+        && x.bottom >= last.y - RULE_DISTANCE * last.size
+        && x.top <= first.y + RULE_DISTANCE * first.size
+    });
+
+  let cells: Vec<Vec<String>> = rows
+    .iter()
+    .map(|row| {
+      let mut cells = vec![String::new(); columns.len()];
+      for run in &row.runs {
+        let column = columns.iter().position(|x| run.left <= x.1).unwrap_or(0);
+        if !cells[column].is_empty() {
+          cells[column].push(' ');
+        }
+        cells[column].push_str(&run_text(layout, run));
+      }
+      cells
+    })
+    .collect();
+
+  // Most rows have a cell in at least two columns, unless the first one
+  // only holds the numbers or bullets of a list
+  let filled_in = |column: usize| {
+    cells.iter().filter(|x| !x[column].is_empty()).count() * 2 >= cells.len()
+  };
+  let dense: Vec<usize> =
+    (0..columns.len()).filter(|&x| filled_in(x)).collect();
+  let is_list = dense.len() == 2
+    && cells.iter().all(|x| x[dense[0]].is_empty() || is_label(&x[dense[0]]));
+  if (dense.len() < 2 || (!ruled && is_list)) {
+    return None;
+  }
+
+  let mean_len = |column: usize| {
+    let filled: Vec<usize> = cells
+      .iter()
+      .map(|x| x[column].chars().count())
+      .filter(|&x| x > 0)
+      .collect();
+    filled.iter().sum::<usize>() / filled.len().max(1)
+  };
+  let prose = (0..columns.len()).any(|x| mean_len(x) > MAX_MEAN_CELL_LEN);
+  if (!ruled && (rows.len() < MIN_ROWS || prose)) {
+    return None;
+  }
+
+  let range = text_range(layout, rows)?;
+
+  let glyphs: Vec<Glyph> = rows
+    .iter()
+    .flat_map(|x| &x.runs)
+    .flat_map(|x| &x.glyphs)
+    .map(|&i| layout.glyphs[i])
+    .collect();
+
+  Some((
+    range,
+    Table {
+      rows: cells,
+      range: 0..0,
+      bbox: glyphs.iter().map(|x| x.bbox()).reduce(Rect::union),
+      font_size: most_common_size(layout, &glyphs),
+    },
+  ))
+}
+
+/// Whether the cell is the bullet or number of a list item, like `•`,
+/// `3.10`, `B.1`, `a)` or `(iv)`.
+pub(crate) fn is_label(cell: &str) -> bool {
+  if matches!(cell, "•" | "◦" | "▪" | "·" | "–" | "-" | "*") {
+    return true;
+  }
+
+  let label = cell.trim_start_matches('(').trim_end_matches(['.', ')']);
+  let numeral = |x: &str| {
+    x.chars().all(|c| c.is_ascii_digit())
+      || x.chars().count() == 1 && x.chars().all(char::is_alphabetic)
+      || x.chars().all(|c| "ivxlc".contains(c))
+  };
+
+  !label.is_empty()
+    && label.len() <= 8
+    && label.split('.').all(|x| !x.is_empty() && numeral(x))
+}
+
+/// The characters of the run, with a space between words.
+fn run_text(layout: &PageLayout, run: &Run) -> String {
+  let mut text = String::new();
+  let mut previous: Option<&Glyph> = None;
+
+  for glyph in run.glyphs.iter().map(|&i| &layout.glyphs[i]) {
+    if previous
+      .is_some_and(|x| glyph.left() - x.right() > WORD_GAP * glyph.size)
+    {
+      text.push(' ');
+    }
+    text.push_str(&layout.text[glyph.start..glyph.end]);
+    previous = Some(glyph);
+  }
+
+  text
+}
+
+/// The whole lines of the text the rows were extracted to, `None` when
+/// other text is mixed in.
+fn text_range(layout: &PageLayout, rows: &[Row]) -> Option<Range<usize>> {
+  let glyphs: HashSet<usize> = rows
+    .iter()
+    .flat_map(|x| &x.runs)
+    .flat_map(|x| x.glyphs.iter().copied())
+    .collect();
+
+  let start = glyphs.iter().map(|&i| layout.glyphs[i].start).min()?;
+  let end = glyphs.iter().map(|&i| layout.glyphs[i].end).max()?;
+  let start = layout.text[..start].rfind('\n').map_or(0, |x| x + 1);
+  let end =
+    layout.text[end..].find('\n').map_or(layout.text.len(), |x| end + x);
+
+  let mixed = layout.glyphs.iter().enumerate().any(|(i, x)| {
+    (start..end).contains(&x.start)
+      && !glyphs.contains(&i)
+      && !layout.text[x.start..x.end].trim().is_empty()
+  });
+
+  (!mixed).then_some(start..end)
+}
@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+
+/// A diagnostic pdf-extract or lopdf logged while converting a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+  /// 1-based page number, `None` for document level diagnostics
+  pub page: Option<u32>,
+  /// The module that logged it e.g. `pdf_extract`
+  pub source: String,
+  pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.page {
+      Some(page) => write!(f, "page {page}: {}: {}", self.source, self.message),
+      None => write!(f, "{}: {}", self.source, self.message),
+    }
+  }
+}
+
+thread_local! {
+  static CAPTURE: RefCell<Option<(Option<u32>, Vec<Warning>)>> =
+    const { RefCell::new(None) };
+}
+
+/// Collects the log records of the current thread while a capture is active,
+/// records from other threads are dropped, so nothing reaches stdout or
+/// stderr and concurrent captures on other threads stay separate.
+struct CaptureLogger;
+
+static LOGGER: CaptureLogger = CaptureLogger;
+
+impl log::Log for CaptureLogger {
+  fn enabled(&self, metadata: &log::Metadata) -> bool {
+    metadata.level() <= log::Level::Warn
+  }
+
+  fn log(&self, record: &log::Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+
+    CAPTURE.with(|capture| {
+      if let Some((page, warnings)) = capture.borrow_mut().as_mut() {
+        let source = record.target().split("::").next().unwrap_or_default();
+
+        warnings.push(Warning {
+          page: *page,
+          source: source.to_string(),
+          message: record.args().to_string(),
+        });
+      }
+    });
+  }
+
+  fn flush(&self) {}
+}
+
+fn is_capturing() -> bool {
+  CAPTURE.with(|x| x.try_borrow().is_ok_and(|x| x.is_some()))
+}
+
+/// Routes the log records of pdf-extract and lopdf into the warnings of the
+/// extraction that logged them, applications that want the warnings install
+/// it once at startup. Without it the records go to the application's own
+/// logger, or nowhere, and the warnings stay empty.
+pub fn install_warning_logger() -> Result<(), log::SetLoggerError> {
+  log::set_logger(&LOGGER)?;
+  log::set_max_level(log::LevelFilter::Warn);
+
+  Ok(())
+}
+
+/// Keeps the panic hook from printing panics raised inside an extraction,
+/// they are returned as errors already, e.g. so they are not printed over a
+/// terminal UI. Other panics still reach the hook installed before.
+pub fn silence_caught_panics() {
+  let previous = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |info| {
+    if !is_capturing() {
+      previous(info);
+    }
+  }));
+}
+
+/// Restores the enclosing capture, also when the extraction panics.
+struct CaptureGuard {
+  outer: Option<(Option<u32>, Vec<Warning>)>,
+}
+
+impl Drop for CaptureGuard {
+  fn drop(&mut self) {
+    CAPTURE.with(|x| *x.borrow_mut() = self.outer.take());
+  }
+}
+
+/// Runs `f` on the current thread and returns the warnings it logged.
+pub(crate) fn capture_warnings<T>(
+  page: Option<u32>,
+  f: impl FnOnce() -> T,
+) -> (T, Vec<Warning>) {
+  let guard = CaptureGuard {
+    outer: CAPTURE.with(|x| x.replace(Some((page, Vec::new())))),
+  };
+  let result = f();
+  let captured = CAPTURE.with(|x| x.take());
+  drop(guard);
+
+  (result, captured.map(|(_, warnings)| warnings).unwrap_or_default())
+}
+
+/// The message of a caught panic, for panics raised with a string.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+  if let Some(message) = payload.downcast_ref::<&str>() {
+    return message.to_string();
+  }
+  if let Some(message) = payload.downcast_ref::<String>() {
+    return message.clone();
+  }
+
+  "the converter panicked".to_string()
+}
@@ -0,0 +1,93 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::AnnotationKind;
+  use lopdf::{dictionary, Object};
+
+  fn numbers(values: &[i64]) -> Vec<Object> {
+    values.iter().map(|x| (*x).into()).collect()
+  }
+
+  /// Writes a single page document with the given annotations.
+  fn write_annotated_pdf(
+    name: &str,
+    annots: Vec<lopdf::Dictionary>,
+  ) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    let page_id = pdf.page(
+      [
+        text_line("F1", 24, 100, 600, "Visit the website"),
+        text_line("F1", 24, 100, 500, "Read on"),
+      ]
+      .concat(),
+    );
+    let annots: Vec<Object> =
+      annots.into_iter().map(|x| pdf.doc.add_object(x).into()).collect();
+    pdf.doc.get_dictionary_mut(page_id).unwrap().set("Annots", annots);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_highlights_cover_their_text() {
+    let path = write_annotated_pdf(
+      "highlights",
+      vec![
+        dictionary! {
+          "Type" => "Annot",
+          "Subtype" => "Highlight",
+          "Rect" => numbers(&[190, 595, 280, 625]),
+          "QuadPoints" => numbers(&[190, 625, 280, 625, 190, 595, 280, 595]),
+          "Contents" => Object::string_literal("check this"),
+        },
+        dictionary! {
+          "Type" => "Annot",
+          "Subtype" => "Underline",
+          "Rect" => numbers(&[95, 495, 200, 525]),
+          "QuadPoints" => numbers(&[95, 525, 200, 525, 95, 495, 200, 495]),
+        },
+      ],
+    );
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(&path, None).unwrap();
+    let page = stream.pages.recv().unwrap();
+    let covered: Vec<_> = page
+      .annotations
+      .iter()
+      .map(|x| {
+        (x.kind, &page.text[x.range.clone().unwrap()], x.note.as_deref())
+      })
+      .collect();
+
+    assert_eq!(
+      covered,
+      vec![
+        (AnnotationKind::Highlight, "website", Some("check this")),
+        (AnnotationKind::Underline, "Read on", None),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_notes_are_attached_to_the_nearest_word() {
+    let path = write_annotated_pdf(
+      "notes",
+      vec![dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Text",
+        "Rect" => numbers(&[300, 600, 320, 620]),
+        "Contents" => Object::string_literal("a sticky note"),
+      }],
+    );
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(&path, None).unwrap();
+    let page = stream.pages.recv().unwrap();
+    let note = &page.annotations[0];
+
+    assert_eq!(note.kind, AnnotationKind::Note);
+    assert_eq!(&page.text[note.range.clone().unwrap()], "website");
+    assert_eq!(note.note.as_deref(), Some("a sticky note"));
+  }
+}
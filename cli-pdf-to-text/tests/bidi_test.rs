@@ -0,0 +1,82 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{PdfBuilder, TempFile};
+  use lopdf::content::Operation;
+  use lopdf::{dictionary, Object};
+
+  /// Hebrew letters of the font, by their code.
+  const LETTERS: [(u8, char, &str); 5] = [
+    (200, 'ש', "shin"),
+    (201, 'ל', "lamed"),
+    (202, 'ו', "vav"),
+    (203, 'ם', "finalmem"),
+    (204, 'ע', "ayin"),
+  ];
+
+  /// Draws `text` from left to right in the order of its characters, like
+  /// pdfs that store right-to-left text in visual order.
+  fn text_at(y: i64, text: &str) -> Vec<Operation> {
+    let bytes: Vec<u8> = text
+      .chars()
+      .map(|c| match LETTERS.iter().find(|x| x.1 == c) {
+        Some(letter) => letter.0,
+        None => c as u8,
+      })
+      .collect();
+
+    vec![
+      Operation::new("BT", vec![]),
+      Operation::new("Tf", vec!["F1".into(), 10.into()]),
+      Operation::new("Td", vec![72.into(), y.into()]),
+      Operation::new(
+        "Tj",
+        vec![Object::String(bytes, lopdf::StringFormat::Literal)],
+      ),
+      Operation::new("ET", vec![]),
+    ]
+  }
+
+  fn write_pdf(name: &str, operations: Vec<Operation>) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    let mut differences: Vec<Object> = vec![200.into()];
+    differences.extend(LETTERS.iter().map(|x| Object::Name(x.2.into())));
+    pdf.font(
+      "F1",
+      dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "FirstChar" => 32,
+        "LastChar" => 204,
+        "Widths" => vec![Object::Integer(500); 173],
+        "Encoding" => dictionary! {
+          "Type" => "Encoding",
+          "BaseEncoding" => "WinAnsiEncoding",
+          "Differences" => differences,
+        },
+      },
+    );
+    pdf.page(operations);
+
+    return pdf.save(name);
+  }
+
+  #[test]
+  fn test_rtl_text_drawn_left_to_right_is_in_logical_order() {
+    let operations = [
+      text_at(700, "םלוע םולש"),
+      text_at(680, "123 (םולש)"),
+      text_at(660, "hello world"),
+    ]
+    .concat();
+    let path = write_pdf("bidi-visual.pdf", operations);
+
+    let text = cli_pdf_to_text::pdf_to_text(&path).unwrap();
+    let lines: Vec<&str> =
+      text.lines().map(str::trim).filter(|x| !x.is_empty()).collect();
+
+    assert_eq!(lines, vec!["שלום עולם", "(שלום) 123", "hello world"]);
+  }
+}
@@ -119,6 +119,124 @@ mod tests {
     similarity * 100.0
   }
 
+  /// The text without the `[n]` link markers and the list of links after it.
+  fn without_links(text: &str) -> String {
+    let text = match text.find("\n\n[1] ") {
+      Some(i) => &text[..i],
+      None => text,
+    };
+
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+      let end = rest[start..].find(']').map(|x| start + x);
+      match end {
+        Some(end)
+          if rest[start + 1..end].chars().all(|x| x.is_ascii_digit()) =>
+        {
+          result.push_str(&rest[..start]);
+          rest = &rest[end + 1..];
+        }
+        _ => {
+          result.push_str(&rest[..=start]);
+          rest = &rest[start + 1..];
+        }
+      }
+    }
+    result.push_str(rest);
+
+    return result;
+  }
+
+  #[test]
+  fn test_pdf_to_pages_keeps_page_order() {
+    let input_path = "../test-data/pdf/pdfreference1.7old-1-50.pdf";
+
+    let pages = cli_pdf_to_text::pdf_to_pages(input_path).unwrap();
+
+    let doc = cli_pdf_to_text::load_document(input_path, None).unwrap();
+    let sequential: Vec<String> = (1..=pages.len() as u32)
+      .map(|page_num| cli_pdf_to_text::page_to_text(&doc, page_num).unwrap())
+      .collect();
+
+    // Pages with tables have a grid in place of their text
+    let with_tables = |x: &String| x.lines().any(|x| x.starts_with("| "));
+
+    assert_eq!(pages.len(), 50);
+    assert_eq!(pages.iter().filter(|x| with_tables(x)).count(), 1);
+    for (page, text) in pages.iter().zip(&sequential) {
+      if !with_tables(page) {
+        assert_eq!(&without_links(page), text);
+      }
+    }
+  }
+
+  #[test]
+  fn test_page_stream_matches_pdf_to_pages() {
+    let input_path = "../test-data/pdf/pdfreference1.7old-1-50.pdf";
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(input_path, None).unwrap();
+    let numbers: Vec<u32> = (1..=50).collect();
+    let streamed: Vec<cli_pdf_to_text::Page> = stream.pages.iter().collect();
+
+    assert_eq!(stream.page_count, 50);
+    assert_eq!(streamed.iter().map(|x| x.number).collect::<Vec<_>>(), numbers);
+    assert_eq!(
+      streamed.into_iter().map(|x| x.text).collect::<Vec<_>>(),
+      cli_pdf_to_text::pdf_to_pages(input_path).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_pdf_info_resolves_outline_pages() {
+    let input_path = "../test-data/pdf/pdfreference1.7old-1-50.pdf";
+
+    let info = cli_pdf_to_text::pdf_to_info(input_path, None).unwrap();
+
+    assert_eq!(info.title.as_deref(), Some("PDF Reference, version 1.7"));
+    assert_eq!(info.author.as_deref(), Some("Adobe Systems Incorporated"));
+    assert_eq!(info.page_count, 50);
+
+    let about =
+      info.outline.iter().find(|x| x.title == "1.1 About This Book").unwrap();
+    assert_eq!(about.level, 1);
+    assert_eq!(about.page, Some(25));
+  }
+
+  #[test]
+  fn test_streamed_pages_have_their_outline_entries() {
+    let input_path = "../test-data/pdf/pdfreference1.7old-1-50.pdf";
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(input_path, None).unwrap();
+    let page = stream.pages.iter().find(|x| x.number == 25).unwrap();
+
+    assert!(page.outline.iter().any(|x| x.title == "1.1 About This Book"));
+    assert!(page.outline.iter().all(|x| x.page == Some(25)));
+  }
+
+  #[test]
+  fn test_warnings_are_captured_per_extraction() {
+    let input_path = "../test-data/pdf/pdfreference1.7old-1-50.pdf";
+    let _ = cli_pdf_to_text::install_warning_logger();
+
+    let handles: Vec<_> = (0..2)
+      .map(|_| {
+        std::thread::spawn(move || {
+          cli_pdf_to_text::pdf_to_pages_with_warnings(input_path, None)
+            .map_err(|e| e.to_string())
+        })
+      })
+      .collect();
+    let results: Vec<_> =
+      handles.into_iter().map(|x| x.join().unwrap().unwrap()).collect();
+
+    let warnings = &results[0].warnings;
+    assert!(!warnings.is_empty());
+    assert!(warnings.iter().all(|x| x.page.is_some()));
+    assert!(warnings.iter().any(|x| x.message.contains("unknown glyph name")));
+    assert_eq!(results[0], results[1]);
+  }
+
   #[test]
   fn test_end_to_end() {
     // Path to the reference input PDF file.
@@ -0,0 +1,154 @@
+// Each test file uses only some of the helpers
+#![allow(dead_code)]
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::ops::Deref;
+
+/// A file in the temporary directory, removed when it is dropped. It
+/// dereferences to its path.
+pub struct TempFile(tempfile::TempPath);
+
+impl TempFile {
+  /// An empty file named like `name`, e.g. `links.pdf`, with a random part
+  /// so tests running at the same time do not share it.
+  pub fn new(name: &str) -> Self {
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let file = tempfile::Builder::new()
+      .prefix(&format!("cli-pdf-to-text-{stem}-"))
+      .suffix(&format!(".{extension}"))
+      .tempfile()
+      .unwrap();
+
+    return Self(file.into_temp_path());
+  }
+}
+
+impl Deref for TempFile {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    self.0.to_str().unwrap()
+  }
+}
+
+/// Shows `text` in `font` of `size` points, starting at `(x, y)`.
+pub fn text_line(
+  font: &str,
+  size: i64,
+  x: i64,
+  y: i64,
+  text: &str,
+) -> Vec<Operation> {
+  vec![
+    Operation::new("BT", vec![]),
+    Operation::new("Tf", vec![font.into(), size.into()]),
+    Operation::new("Td", vec![x.into(), y.into()]),
+    Operation::new("Tj", vec![Object::string_literal(text)]),
+    Operation::new("ET", vec![]),
+  ]
+}
+
+/// Builds a document whose pages share the resources of their `Pages` node,
+/// Helvetica is the font `F1` unless it is replaced.
+pub struct PdfBuilder {
+  pub doc: Document,
+  pub page_ids: Vec<ObjectId>,
+  pages_id: ObjectId,
+  fonts: Dictionary,
+  images: Dictionary,
+}
+
+impl Default for PdfBuilder {
+  fn default() -> Self {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+      "Type" => "Font",
+      "Subtype" => "Type1",
+      "BaseFont" => "Helvetica",
+    });
+
+    Self {
+      doc,
+      page_ids: Vec::new(),
+      pages_id,
+      fonts: dictionary! { "F1" => font_id },
+      images: Dictionary::new(),
+    }
+  }
+}
+
+impl PdfBuilder {
+  pub fn font(&mut self, name: &str, font: Dictionary) {
+    let font_id = self.doc.add_object(font);
+    self.fonts.set(name, font_id);
+  }
+
+  pub fn image(&mut self, name: &str, image: Stream) {
+    let image_id = self.doc.add_object(image);
+    self.images.set(name, image_id);
+  }
+
+  /// Adds a page drawn by `operations`, returns its object.
+  pub fn page(&mut self, operations: Vec<Operation>) -> ObjectId {
+    let content = Content { operations };
+    let content_id = self
+      .doc
+      .add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+    let page_id = self.doc.add_object(dictionary! {
+      "Type" => "Page",
+      "Parent" => self.pages_id,
+      "Contents" => content_id,
+    });
+    self.page_ids.push(page_id);
+
+    return page_id;
+  }
+
+  /// Adds the `Pages` node and the catalog.
+  pub fn finish(self) -> Document {
+    let mut doc = self.doc;
+    let kids: Vec<Object> = self.page_ids.iter().map(|x| (*x).into()).collect();
+    let mut resources = dictionary! { "Font" => self.fonts };
+    if !self.images.is_empty() {
+      resources.set("XObject", self.images);
+    }
+
+    doc.objects.insert(
+      self.pages_id,
+      Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+        "Resources" => resources,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+      }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+      "Type" => "Catalog",
+      "Pages" => self.pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    return doc;
+  }
+
+  /// Finishes the document and saves it to a temporary file named like
+  /// `name`.
+  pub fn save(self, name: &str) -> TempFile {
+    save(self.finish(), name)
+  }
+}
+
+pub fn save(mut doc: Document, name: &str) -> TempFile {
+  let file = TempFile::new(name);
+  doc.save(&*file).unwrap();
+
+  return file;
+}
+
+pub fn first_page(path: &str) -> cli_pdf_to_text::Page {
+  let stream = cli_pdf_to_text::pdf_to_page_stream(path, None).unwrap();
+  stream.pages.recv().unwrap()
+}
@@ -0,0 +1,63 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::PdfError;
+
+  /// Writes a document with one page per `(font, text)` entry, fonts other
+  /// than `F1` are missing from the resources, which makes the page unreadable.
+  fn write_pdf(name: &str, pages: &[(&str, &str)]) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    for (font, text) in pages {
+      pdf.page(text_line(font, 24, 100, 600, text));
+    }
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_not_a_pdf() {
+    let path = TempFile::new("not-a-pdf.txt");
+    std::fs::write(&*path, "just some text").unwrap();
+
+    assert!(matches!(
+      cli_pdf_to_text::pdf_to_text(&path),
+      Err(PdfError::NotPdf)
+    ));
+  }
+
+  #[test]
+  fn test_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("missing.pdf");
+
+    assert!(matches!(
+      cli_pdf_to_text::pdf_to_text(path.to_str().unwrap()),
+      Err(PdfError::Io(_))
+    ));
+  }
+
+  #[test]
+  fn test_broken_page_is_skipped() {
+    let path =
+      write_pdf("broken-page", &[("F1", "First page"), ("F9", "Second page")]);
+
+    let pdf = cli_pdf_to_text::pdf_to_pages_with_warnings(&path, None).unwrap();
+
+    assert_eq!(pdf.pages.len(), 2);
+    assert!(pdf.pages[0].contains("First page"));
+    assert_eq!(pdf.pages[1], "");
+    assert!(pdf.warnings.iter().any(|x| x.page == Some(2)));
+  }
+
+  #[test]
+  fn test_no_readable_page_is_corrupt() {
+    let path = write_pdf("broken-document", &[("F9", "Only page")]);
+
+    assert!(matches!(
+      cli_pdf_to_text::pdf_to_text(&path),
+      Err(PdfError::Corrupt { page: Some(1), .. })
+    ));
+  }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{first_page, text_line, PdfBuilder, TempFile};
+  use lopdf::content::Operation;
+  use lopdf::{dictionary, Stream};
+
+  /// Draws a 4×2 gray image at `(x, y)` scaled to `width`×`height` points.
+  fn image_at(x: i64, y: i64, width: i64, height: i64) -> Vec<Operation> {
+    vec![
+      Operation::new("q", vec![]),
+      Operation::new(
+        "cm",
+        vec![
+          width.into(),
+          0.into(),
+          0.into(),
+          height.into(),
+          x.into(),
+          y.into(),
+        ],
+      ),
+      Operation::new("Do", vec!["Im1".into()]),
+      Operation::new("Q", vec![]),
+    ]
+  }
+
+  fn write_figure_pdf(name: &str, operations: Vec<Operation>) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    pdf.image(
+      "Im1",
+      Stream::new(
+        dictionary! {
+          "Type" => "XObject",
+          "Subtype" => "Image",
+          "Width" => 4,
+          "Height" => 2,
+          "ColorSpace" => "DeviceGray",
+          "BitsPerComponent" => 8,
+        },
+        vec![0, 64, 128, 255, 255, 128, 64, 0],
+      ),
+    );
+    pdf.page(operations);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_image_becomes_a_placeholder_with_its_caption() {
+    let path = write_figure_pdf(
+      "figure",
+      [
+        text_line("F1", 10, 72, 760, "The pipeline is shown below."),
+        image_at(72, 560, 300, 150),
+        text_line("F1", 10, 72, 540, "Figure 3 The stages of the pipeline"),
+        text_line("F1", 10, 72, 500, "Each stage runs on a thread of its own."),
+      ]
+      .concat(),
+    );
+
+    let page = first_page(&path);
+
+    assert_eq!(page.figures.len(), 1);
+    let figure = &page.figures[0];
+    assert_eq!(
+      figure.caption.as_deref(),
+      Some("Figure 3 The stages of the pipeline")
+    );
+    assert_eq!((figure.width, figure.height), (4, 2));
+    assert_eq!(
+      &page.text[figure.range.clone()],
+      "[Figure: Figure 3 The stages of the pipeline, 4×2]"
+    );
+    assert_eq!(figure.image.as_ref().map(|x| x.extension), Some("pnm"));
+
+    // Between the text above and the caption below the image
+    let above = page.text.find("shown below").unwrap();
+    let caption = page.text.rfind("Figure 3 The stages").unwrap();
+    assert!(above < figure.range.start && figure.range.end < caption);
+  }
+
+  #[test]
+  fn test_small_images_are_not_figures() {
+    let path = write_figure_pdf(
+      "icon",
+      [
+        text_line("F1", 10, 72, 760, "A line with an icon."),
+        image_at(200, 755, 12, 12),
+      ]
+      .concat(),
+    );
+
+    let page = first_page(&path);
+
+    assert!(page.figures.is_empty());
+    assert!(!page.text.contains("[Figure"));
+  }
+}
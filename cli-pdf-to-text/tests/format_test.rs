@@ -0,0 +1,135 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{text_line, PdfBuilder, TempFile};
+  use lopdf::content::Operation;
+  use lopdf::{dictionary, Object};
+
+  /// Writes a single page document with a heading, a paragraph, a list and
+  /// a table.
+  fn write_document_pdf(name: &str) -> TempFile {
+    let mut operations = [
+      text_line("F1", 24, 72, 760, "Getting Started"),
+      text_line("F1", 10, 72, 720, "The first lines of the body text are set"),
+      text_line("F1", 10, 72, 706, "in a font of ten points, like most of"),
+      text_line("F1", 10, 72, 692, "the text of an ordinary document."),
+      text_line("F1", 10, 72, 660, "1. Copy the files to a directory"),
+      text_line("F1", 10, 72, 646, "2. Run the program from there"),
+    ]
+    .concat();
+
+    let rows: &[&[&str]] = &[
+      &["Part", "Voltage", "Current"],
+      &["R1", "5 V", "10 mA"],
+      &["R2", "3.3 V", "2 mA"],
+    ];
+    for (i, row) in rows.iter().enumerate() {
+      for (j, cell) in row.iter().enumerate() {
+        let (x, y) = (72 + 150 * j as i64, 600 - 14 * i as i64);
+        operations.extend(text_line("F1", 10, x, y, cell));
+      }
+    }
+
+    let mut pdf = PdfBuilder::default();
+    pdf.page(operations);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  fn pages(path: &str) -> Vec<cli_pdf_to_text::Page> {
+    let stream = cli_pdf_to_text::pdf_to_page_stream(path, None).unwrap();
+    stream.pages.iter().collect()
+  }
+
+  #[test]
+  fn test_json_has_blocks_with_their_area_and_size() {
+    let path = write_document_pdf("json");
+
+    let json = cli_pdf_to_text::pages_to_json(&pages(&path));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    let page = &value["pages"][0];
+    assert_eq!(page["number"], 1);
+    let blocks = page["blocks"].as_array().unwrap();
+    let types: Vec<&str> =
+      blocks.iter().map(|x| x["type"].as_str().unwrap()).collect();
+    assert_eq!(
+      types,
+      ["heading", "paragraph", "list_item", "list_item", "table"]
+    );
+
+    let heading = &blocks[0];
+    assert_eq!(heading["text"], "Getting Started");
+    assert_eq!(heading["level"], 1);
+    assert_eq!(heading["font_size"], 24.0);
+    let bbox = heading["bbox"].as_array().unwrap();
+    assert_eq!(bbox.len(), 4);
+    assert!(bbox[0].as_f64().unwrap() >= 71.0);
+    assert!(bbox[1].as_f64().unwrap() < bbox[3].as_f64().unwrap());
+
+    assert_eq!(blocks[1]["font_size"], 10.0);
+    assert_eq!(blocks[4]["rows"][1][1], "5 V");
+    assert_eq!(page["headings"][0]["text"], "Getting Started");
+  }
+
+  #[test]
+  fn test_markdown_has_headings_lists_and_tables() {
+    let path = write_document_pdf("markdown");
+
+    let markdown = cli_pdf_to_text::pages_to_markdown(&pages(&path));
+
+    assert_eq!(
+      markdown,
+      "# Getting Started\n\n\
+       The first lines of the body text are set in a font of ten points, \
+       like most of the text of an ordinary document.\n\n\
+       1. Copy the files to a directory\n\
+       2. Run the program from there\n\n\
+       | Part | Voltage | Current |\n\
+       |---|---|---|\n\
+       | R1 | 5 V | 10 mA |\n\
+       | R2 | 3.3 V | 2 mA |\n"
+    );
+  }
+
+  #[test]
+  fn test_normalized_json_is_valid() {
+    let mut pdf = PdfBuilder::default();
+    pdf.font(
+      "F1",
+      dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+      },
+    );
+    // “Quoted” in WinAnsiEncoding
+    let quoted = Object::String(
+      b"\x93Quoted\x94 text and a \\ backslash".to_vec(),
+      lopdf::StringFormat::Literal,
+    );
+    pdf.page(vec![
+      Operation::new("BT", vec![]),
+      Operation::new("Tf", vec!["F1".into(), 10.into()]),
+      Operation::new("Td", vec![72.into(), 720.into()]),
+      Operation::new("Tj", vec![quoted]),
+      Operation::new("ET", vec![]),
+    ]);
+    let path = pdf.save("normalized.pdf");
+
+    let output =
+      std::process::Command::new(env!("CARGO_BIN_EXE_cli-pdf-to-text"))
+        .args(["--format", "json", "--normalize", "ascii", &path])
+        .output()
+        .unwrap();
+    let json = String::from_utf8(output.stdout).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(
+      value["pages"][0]["blocks"][0]["text"],
+      "\"Quoted\" text and a \\ backslash"
+    );
+  }
+}
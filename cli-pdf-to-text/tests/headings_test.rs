@@ -0,0 +1,111 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{save, text_line, PdfBuilder, TempFile};
+  use lopdf::{dictionary, Document};
+
+  /// Writes a single page document with a title, a section heading, a
+  /// bold subsection heading and a few lines of body text.
+  fn write_headings_pdf(name: &str) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    pdf.font(
+      "F2",
+      dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica-Bold",
+      },
+    );
+    pdf.page(
+      [
+        text_line("F1", 24, 72, 750, "Getting Started"),
+        text_line(
+          "F1",
+          10,
+          72,
+          700,
+          "The first lines of the body text are set",
+        ),
+        text_line("F1", 10, 72, 686, "in a font of ten points, like most of"),
+        text_line("F1", 10, 72, 672, "the text of an ordinary document."),
+        text_line("F1", 15, 72, 630, "Installation"),
+        text_line("F1", 10, 72, 600, "Copy the files to a directory of your"),
+        text_line("F1", 10, 72, 586, "choice and run the program from there."),
+        text_line("F2", 10, 72, 556, "Requirements"),
+        text_line("F1", 10, 72, 530, "Any computer made in the last ten years"),
+        text_line(
+          "F1",
+          10,
+          72,
+          516,
+          "will do, with a little room on the disk.",
+        ),
+      ]
+      .concat(),
+    );
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_larger_and_bold_lines_are_headings() {
+    let path = write_headings_pdf("headings");
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(&path, None).unwrap();
+    let page = stream.pages.recv().unwrap();
+    let headings: Vec<_> = page
+      .headings
+      .iter()
+      .map(|x| (x.level, x.text.as_str(), &page.text[x.range.clone()]))
+      .collect();
+
+    assert_eq!(
+      headings,
+      vec![
+        (1, "Getting Started", "Getting Started"),
+        (2, "Installation", "Installation"),
+        (3, "Requirements", "Requirements"),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_outline_is_generated_from_headings() {
+    let path = write_headings_pdf("generated-outline");
+
+    let info = cli_pdf_to_text::pdf_to_info(&path, None).unwrap();
+    let outline: Vec<_> = info
+      .outline
+      .iter()
+      .map(|x| (x.level, x.title.as_str(), x.page))
+      .collect();
+
+    assert_eq!(
+      outline,
+      vec![
+        (0, "Getting Started", Some(1)),
+        (1, "Installation", Some(1)),
+        (2, "Requirements", Some(1)),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_documents_without_outline_get_one() {
+    let mut doc =
+      Document::load("../test-data/pdf/pdfreference1.7old-1-50.pdf").unwrap();
+    doc.catalog_mut().unwrap().remove(b"Outlines");
+    let path = save(doc, "without-outline.pdf");
+
+    let info = cli_pdf_to_text::pdf_to_info(&path, None).unwrap();
+    let entry = |title: &str| {
+      let entry = info.outline.iter().find(|x| x.title == title).unwrap();
+      (entry.level, entry.page)
+    };
+
+    assert_eq!(entry("Preface"), (0, Some(23)));
+    assert_eq!(entry("1.1 About This Book"), (1, Some(25)));
+    assert_eq!(entry("2.3 Creating PDF"), (1, Some(43)));
+  }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::{Link, LinkTarget};
+  use lopdf::{dictionary, Object};
+
+  /// Writes a document whose first page links to a website and to the
+  /// second page.
+  fn write_linked_pdf(name: &str) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    let first = pdf.page(
+      [
+        text_line("F1", 24, 100, 600, "Visit the website"),
+        text_line("F1", 24, 100, 500, "Read on"),
+      ]
+      .concat(),
+    );
+    let second = pdf.page(text_line("F1", 24, 100, 600, "Second page"));
+
+    let uri = pdf.doc.add_object(dictionary! {
+      "Type" => "Annot",
+      "Subtype" => "Link",
+      "Rect" => vec![190.into(), 595.into(), 280.into(), 625.into()],
+      "A" => dictionary! {
+        "S" => "URI",
+        "URI" => Object::string_literal("https://example.com"),
+      },
+    });
+    let goto = pdf.doc.add_object(dictionary! {
+      "Type" => "Annot",
+      "Subtype" => "Link",
+      "Rect" => vec![95.into(), 495.into(), 200.into(), 525.into()],
+      "Dest" => vec![second.into(), "Fit".into()],
+    });
+    pdf
+      .doc
+      .get_dictionary_mut(first)
+      .unwrap()
+      .set("Annots", vec![goto.into(), uri.into()]);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_links_are_marked_and_listed() {
+    let path = write_linked_pdf("links");
+
+    let pdf = cli_pdf_to_text::pdf_to_pages_with_warnings(&path, None).unwrap();
+
+    let page = &pdf.pages[0];
+    assert!(page.contains("website[1]"), "{page}");
+    assert!(page.contains("Read on[2]"), "{page}");
+    assert!(page.ends_with("\n\n[1] https://example.com\n\n[2] page 2\n"));
+    assert!(!pdf.pages[1].contains('['));
+  }
+
+  #[test]
+  fn test_page_stream_has_the_links() {
+    let path = write_linked_pdf("links-stream");
+
+    let stream = cli_pdf_to_text::pdf_to_page_stream(&path, None).unwrap();
+    let pages: Vec<_> = stream.pages.iter().collect();
+
+    assert_eq!(
+      pages[0].links,
+      vec![
+        Link {
+          number: 1,
+          target: LinkTarget::Uri("https://example.com".to_string())
+        },
+        Link { number: 2, target: LinkTarget::Page(2) },
+      ]
+    );
+    assert!(pages[1].links.is_empty());
+  }
+
+  #[test]
+  fn test_pages_without_links_are_unchanged() {
+    let path = write_linked_pdf("links-unchanged");
+    let doc = cli_pdf_to_text::load_document(&path, None).unwrap();
+
+    let pdf = cli_pdf_to_text::pdf_to_pages_with_warnings(&path, None).unwrap();
+
+    assert_eq!(pdf.pages[1], cli_pdf_to_text::page_to_text(&doc, 2).unwrap());
+  }
+}
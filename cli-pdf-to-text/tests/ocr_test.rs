@@ -0,0 +1,215 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::{FakeOcr, Ocr, OcrMyPdf, Tesseract};
+  use lopdf::content::Operation;
+  use lopdf::{dictionary, Document, Stream};
+  use std::sync::Arc;
+
+  /// Writes a document whose first page has a text layer and whose second
+  /// page is a scan, a single gray image without any text.
+  fn write_scanned_pdf(name: &str) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    let mut image = Stream::new(
+      dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => 4,
+        "Height" => 2,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+      },
+      vec![0, 255, 0, 255, 255, 0, 255, 0],
+    );
+    image.compress().unwrap();
+    pdf.image("Im1", image);
+
+    pdf.page(text_line("F1", 24, 100, 600, "Typed page"));
+    pdf.page(vec![
+      Operation::new("q", vec![]),
+      Operation::new(
+        "cm",
+        vec![595.into(), 0.into(), 0.into(), 842.into(), 0.into(), 0.into()],
+      ),
+      Operation::new("Do", vec!["Im1".into()]),
+      Operation::new("Q", vec![]),
+    ]);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  /// Writes an executable shell script standing in for an OCR command.
+  #[cfg(unix)]
+  fn write_script(name: &str, body: &str) -> TempFile {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = TempFile::new(name);
+    std::fs::write(&*path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    std::fs::set_permissions(&*path, std::fs::Permissions::from_mode(0o755))
+      .unwrap();
+
+    return path;
+  }
+
+  #[test]
+  fn test_image_sizes_that_overflow_are_skipped() {
+    let sizes: [(i64, i64, &str); 4] = [
+      (i64::MAX, 2, "DeviceGray"),
+      (1 << 40, 1 << 40, "DeviceGray"),
+      (-4, -2, "DeviceGray"),
+      (i64::MAX / 2, 1, "DeviceRGB"),
+    ];
+
+    for (width, height, color_space) in sizes {
+      let mut doc = Document::with_version("1.5");
+      let image_id = doc.add_object(Stream::new(
+        dictionary! {
+          "Type" => "XObject",
+          "Subtype" => "Image",
+          "Width" => width,
+          "Height" => height,
+          "ColorSpace" => color_space,
+          "BitsPerComponent" => 8,
+        },
+        vec![0; 8],
+      ));
+      let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Resources" => dictionary! {
+          "XObject" => dictionary! { "Im1" => image_id },
+        },
+      });
+
+      let page = cli_pdf_to_text::ScannedPage::new(&doc, page_id, 1);
+      assert!(page.has_images());
+      assert!(page.image().is_none(), "{width}x{height}");
+    }
+  }
+
+  #[test]
+  fn test_only_scanned_pages_are_recognized() {
+    let path = write_scanned_pdf("scanned");
+    let fake = Arc::new(FakeOcr::new("recognized page {page}"));
+    let ocr = Ocr { engine: fake.clone(), all_pages: false, cache_dir: None };
+
+    let pdf =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&path, None, Some(&ocr)).unwrap();
+
+    assert_eq!(fake.recognized_pages(), vec![2]);
+    assert!(pdf.pages[0].contains("Typed page"));
+    assert_eq!(pdf.pages[1], "recognized page 2");
+  }
+
+  #[test]
+  fn test_all_pages_are_recognized_in_page_order() {
+    let path = write_scanned_pdf("all-pages");
+    let fake = Arc::new(FakeOcr::new("recognized page {page}"));
+    let ocr = Ocr { engine: fake.clone(), all_pages: true, cache_dir: None };
+
+    let stream =
+      cli_pdf_to_text::pdf_to_page_stream_with_ocr(&path, None, Some(ocr))
+        .unwrap();
+    let pages: Vec<String> = stream.pages.iter().map(|x| x.text).collect();
+
+    assert_eq!(fake.recognized_pages(), vec![1, 2]);
+    assert_eq!(pages, vec!["recognized page 1", "recognized page 2"]);
+  }
+
+  #[test]
+  fn test_scanned_page_without_ocr_is_reported() {
+    let path = write_scanned_pdf("no-ocr");
+
+    let pdf = cli_pdf_to_text::pdf_to_pages_with_warnings(&path, None).unwrap();
+
+    assert_eq!(pdf.pages[1].trim(), "");
+    assert!(pdf
+      .warnings
+      .iter()
+      .any(|x| x.page == Some(2) && x.message.contains("no text layer")));
+    assert!(!pdf.warnings.iter().any(|x| x.page == Some(1)));
+  }
+
+  #[test]
+  fn test_recognized_pages_are_cached() {
+    let path = write_scanned_pdf("cached");
+    let cache_dir = tempfile::tempdir().unwrap();
+    let first = Arc::new(FakeOcr::new("recognized page {page}"));
+    let ocr = Ocr {
+      engine: first.clone(),
+      all_pages: false,
+      cache_dir: Some(cache_dir.path().to_path_buf()),
+    };
+    let pdf =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&path, None, Some(&ocr)).unwrap();
+    assert_eq!(first.recognized_pages(), vec![2]);
+
+    let second = Arc::new(FakeOcr::new("not from the cache"));
+    let ocr = Ocr { engine: second.clone(), ..ocr };
+    let cached =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&path, None, Some(&ocr)).unwrap();
+
+    assert!(second.recognized_pages().is_empty());
+    assert_eq!(cached.pages, pdf.pages);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_ocr_language_is_passed_on() {
+    // Answers with its arguments after the image
+    let program = write_script("tesseract-lang", r#"shift; echo "$@""#);
+    let ocr = Ocr::new(Tesseract {
+      language: Some("deu+eng".to_string()),
+      ..Tesseract::with_program(&*program)
+    });
+
+    let path = write_scanned_pdf("tesseract-lang");
+    let pdf =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&path, None, Some(&ocr)).unwrap();
+
+    assert_eq!(pdf.pages[1].trim(), "stdout -l deu+eng");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_tesseract_gets_the_page_image() {
+    // Answers with the magic number of the image it was given
+    let program = write_script("tesseract", r#"head -c 2 "$1""#);
+    let ocr = Ocr::new(Tesseract::with_program(&*program));
+
+    let scanned = write_scanned_pdf("tesseract");
+    let pdf =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&scanned, None, Some(&ocr))
+        .unwrap();
+    assert_eq!(pdf.pages[1], "P5");
+
+    // A real scan, an RGB image with a soft mask
+    let pdf = cli_pdf_to_text::pdf_to_pages_with_ocr(
+      "../test-data/pdf/ocr-0.pdf",
+      None,
+      Some(&ocr),
+    )
+    .unwrap();
+    assert_eq!(pdf.pages[0], "P6");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_ocrmypdf_gets_a_single_page_document() {
+    // Copies the input instead of adding a text layer
+    let program = write_script("ocrmypdf", r#"cp "$5" "$6""#);
+    let ocr = Ocr {
+      engine: Arc::new(OcrMyPdf::with_program(&*program)),
+      all_pages: true,
+      cache_dir: None,
+    };
+
+    let path = write_scanned_pdf("ocrmypdf");
+    let pdf =
+      cli_pdf_to_text::pdf_to_pages_with_ocr(&path, None, Some(&ocr)).unwrap();
+
+    assert!(pdf.pages[0].contains("Typed page"));
+    assert_eq!(pdf.pages[1].trim(), "");
+  }
+}
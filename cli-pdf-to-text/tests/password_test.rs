@@ -0,0 +1,65 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{save, text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::PdfError;
+  use lopdf::Object;
+
+  /// Writes a one page document saying "Hello encrypted world", encrypted
+  /// with RC4 128 bit and the given user password.
+  fn write_encrypted_pdf(name: &str, user_password: &str) -> TempFile {
+    let mut pdf = PdfBuilder::default();
+    pdf.page(text_line("F1", 24, 100, 600, "Hello encrypted world"));
+    let mut doc = pdf.finish();
+    doc.trailer.set(
+      "ID",
+      vec![
+        Object::string_literal("hygg-password-id"),
+        Object::string_literal("hygg-password-id"),
+      ],
+    );
+
+    let version = lopdf::EncryptionVersion::V2 {
+      document: &doc,
+      owner_password: "owner",
+      user_password,
+      key_length: 128,
+      permissions: lopdf::Permissions::all(),
+    };
+    let state = lopdf::EncryptionState::try_from(version).unwrap();
+    doc.encrypt(&state).unwrap();
+
+    return save(doc, &format!("{name}.pdf"));
+  }
+
+  #[test]
+  fn test_empty_user_password_opens_without_password() {
+    let path = write_encrypted_pdf("empty-user", "");
+
+    let text = cli_pdf_to_text::pdf_to_text(&path).unwrap();
+
+    assert!(text.contains("Hello encrypted world"));
+    assert!(!cli_pdf_to_text::is_password_protected(&path));
+  }
+
+  #[test]
+  fn test_user_password() {
+    let path = write_encrypted_pdf("user", "secret");
+
+    assert!(cli_pdf_to_text::is_password_protected(&path));
+    assert!(matches!(
+      cli_pdf_to_text::pdf_to_text(&path),
+      Err(PdfError::Encrypted)
+    ));
+    assert!(matches!(
+      cli_pdf_to_text::pdf_to_text_with_password(&path, Some("wrong")),
+      Err(PdfError::IncorrectPassword)
+    ));
+
+    let text =
+      cli_pdf_to_text::pdf_to_text_with_password(&path, Some("secret"))
+        .unwrap();
+    assert!(text.contains("Hello encrypted world"));
+  }
+}
@@ -0,0 +1,116 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+  use crate::common::{first_page, text_line, PdfBuilder, TempFile};
+  use cli_pdf_to_text::Table;
+  use lopdf::content::Operation;
+
+  fn line(from: (i64, i64), to: (i64, i64)) -> Vec<Operation> {
+    vec![
+      Operation::new("m", vec![from.0.into(), from.1.into()]),
+      Operation::new("l", vec![to.0.into(), to.1.into()]),
+      Operation::new("S", vec![]),
+    ]
+  }
+
+  /// Writes the rows as columns `column_width` points apart, below a
+  /// paragraph.
+  fn write_table_pdf(
+    name: &str,
+    rows: &[&[&str]],
+    column_width: i64,
+    extra: Vec<Operation>,
+  ) -> TempFile {
+    let mut operations =
+      text_line("F1", 10, 72, 760, "The measurements are listed below.");
+    for (i, row) in rows.iter().enumerate() {
+      for (j, cell) in row.iter().enumerate() {
+        let (x, y) = (72 + column_width * j as i64, 720 - 14 * i as i64);
+        operations.extend(text_line("F1", 10, x, y, cell));
+      }
+    }
+    operations.extend(extra);
+
+    let mut pdf = PdfBuilder::default();
+    pdf.page(operations);
+
+    return pdf.save(&format!("{name}.pdf"));
+  }
+
+  fn owned(rows: &[&[&str]]) -> Vec<Vec<String>> {
+    rows.iter().map(|x| x.iter().map(|x| x.to_string()).collect()).collect()
+  }
+
+  #[test]
+  fn test_aligned_columns_are_a_table() {
+    let rows: &[&[&str]] = &[
+      &["Part", "Voltage", "Current"],
+      &["R1", "5 V", "10 mA"],
+      &["R2", "3.3 V", "2 mA"],
+    ];
+    let path = write_table_pdf("aligned", rows, 150, Vec::new());
+
+    let page = first_page(&path);
+
+    assert_eq!(page.tables.len(), 1);
+    assert_eq!(page.tables[0].rows, owned(rows));
+    assert_eq!(
+      &page.text[page.tables[0].range.clone()],
+      "| Part | Voltage | Current |\n\
+       | R1   | 5 V     | 10 mA   |\n\
+       | R2   | 3.3 V   | 2 mA    |"
+    );
+    assert!(page
+      .text
+      .trim_start()
+      .starts_with("The measurements are listed below."));
+  }
+
+  #[test]
+  fn test_rules_make_short_tables() {
+    let rows: &[&[&str]] = &[&["Key", "Value"], &["Mode", "Fast"]];
+
+    let path = write_table_pdf("two-rows", rows, 150, Vec::new());
+    assert!(first_page(&path).tables.is_empty());
+
+    let rule = line((200, 735), (200, 700));
+    let path = write_table_pdf("ruled", rows, 150, rule);
+    assert_eq!(first_page(&path).tables[0].rows, owned(rows));
+  }
+
+  #[test]
+  fn test_columns_of_text_are_not_a_table() {
+    let rows: &[&[&str]] = &[
+      &[
+        "The first column of the article runs on",
+        "while the second column of the text",
+      ],
+      &[
+        "line after line down the left half of",
+        "goes on right next to it in the other",
+      ],
+      &[
+        "the page, every one of them about as",
+        "half, until the end of the page or of",
+      ],
+      &[
+        "long as the others in the column is.",
+        "the article is finally reached there.",
+      ],
+    ];
+    let path = write_table_pdf("prose", rows, 230, Vec::new());
+
+    assert!(first_page(&path).tables.is_empty());
+  }
+
+  #[test]
+  fn test_tables_as_csv() {
+    let table = Table {
+      rows: owned(&[&["Name", "Note"], &["a, b", "say \"hi\""]]),
+      ..Default::default()
+    };
+
+    assert_eq!(table.to_csv(), "Name,Note\n\"a, b\",\"say \"\"hi\"\"\"\n");
+  }
+}
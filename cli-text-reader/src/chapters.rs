@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// An entry of the table of contents, listed with `:toc`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+  pub title: String,
+  /// Nesting depth, top level chapters are 0
+  pub level: usize,
+}
+
+/// The titles of the table of contents, indented by their level. Levels no
+/// chapter uses are left out, so sections are nested right below their
+/// chapters.
+pub(crate) fn chapter_list(chapters: &[(Chapter, usize)]) -> Vec<String> {
+  let levels: BTreeSet<usize> = chapters.iter().map(|x| x.0.level).collect();
+
+  chapters
+    .iter()
+    .map(|(chapter, _)| {
+      let depth = levels.range(..chapter.level).count();
+      format!("{}{}", "  ".repeat(depth), chapter.title)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unused_levels_are_left_out() {
+    let chapter =
+      |title: &str, level| (Chapter { title: title.to_string(), level }, 0);
+    let chapters =
+      [chapter("Part", 0), chapter("Section", 2), chapter("Detail", 3)];
+
+    assert_eq!(chapter_list(&chapters), ["Part", "  Section", "    Detail"]);
+    assert_eq!(chapter_list(&chapters[1..]), ["Section", "  Detail"]);
+  }
+}
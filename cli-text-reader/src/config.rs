@@ -6,6 +6,12 @@ use std::path::PathBuf;
 pub struct AppConfig {
   pub enable_tutorial: Option<bool>,
   pub enable_line_highlighter: Option<bool>,
+  /// The program `:o` opens images with
+  pub image_viewer: Option<String>,
+  /// How images are drawn, `auto`, `kitty`, `sixel` or `none`
+  pub image_protocol: Option<String>,
+  /// How lines are aligned, `justified`, `left`, `right` or `center`
+  pub alignment: Option<String>,
 }
 
 fn get_config_env_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -32,7 +38,7 @@ pub fn load_config() -> AppConfig {
   let mut config = AppConfig::default();
 
   if let Ok(config_path) = get_config_env_path() {
-    if let Ok(_) = ensure_config_file() {
+    if ensure_config_file().is_ok() {
       dotenvy::from_path(config_path).ok();
       if let Ok(val) = std::env::var("ENABLE_TUTORIAL") {
         config.enable_tutorial = Some(val.to_lowercase() == "true");
@@ -40,6 +46,15 @@ pub fn load_config() -> AppConfig {
       if let Ok(val) = std::env::var("ENABLE_LINE_HIGHLIGHTER") {
         config.enable_line_highlighter = Some(val.to_lowercase() == "true");
       }
+      if let Ok(val) = std::env::var("IMAGE_VIEWER") {
+        config.image_viewer = Some(val);
+      }
+      if let Ok(val) = std::env::var("IMAGE_PROTOCOL") {
+        config.image_protocol = Some(val);
+      }
+      if let Ok(val) = std::env::var("ALIGNMENT") {
+        config.alignment = Some(val);
+      }
     }
   }
 
@@ -2,13 +2,24 @@ use crossterm::{
   cursor::{Hide, MoveTo, Show},
   event::{self, Event as CEvent, KeyCode},
   execute,
-  style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
+  style::{
+    Attribute, Color, ResetColor, SetAttribute, SetBackgroundColor,
+    SetForegroundColor,
+  },
   terminal::{self, Clear, ClearType},
 };
+use std::collections::HashMap;
 use std::io::{self, IsTerminal, Write};
+use std::sync::mpsc;
+use std::time::Duration;
 
+use crate::chapters::{chapter_list, Chapter};
 use crate::config::load_config;
-use crate::progress::{generate_hash, load_progress, save_progress};
+use crate::figures::{default_viewer, open_image, Figure};
+use crate::graphics::{self, Images};
+use crate::highlights::{find_words, Highlight};
+use crate::loading::LineChunk;
+use crate::progress::{generate_hash, load_progress, save_progress, Progress};
 use crate::tutorial::get_tutorial_text;
 
 #[derive(PartialEq)]
@@ -24,7 +35,6 @@ pub struct EditorState {
   pub command_buffer: String,
   pub search_query: String,
   pub search_direction: bool, // true for forward, false for backward
-  pub last_search_index: Option<usize>,
   pub current_match: Option<(usize, usize, usize)>, // (line_index, start, end)
 }
 
@@ -35,14 +45,18 @@ impl EditorState {
       command_buffer: String::new(),
       search_query: String::new(),
       search_direction: true,
-      last_search_index: None,
       current_match: None,
     }
   }
 }
 
 pub struct Editor {
+  /// The lines before they are aligned
   lines: Vec<String>,
+  /// The bullets and indentation drawn before each line
+  line_prefixes: Vec<String>,
+  line_kinds: Vec<cli_justify::LineKind>,
+  alignment: cli_justify::Alignment,
   col: usize,
   offset: usize,
   width: usize,
@@ -51,63 +65,421 @@ pub struct Editor {
   editor_state: EditorState,
   document_hash: u64,
   total_lines: usize,
-  progress_display_until: Option<std::time::Instant>,
   show_progress: bool,
+  enable_tutorial: bool,
+  /// Lines still arriving from the converter, `None` once complete
+  source: Option<mpsc::Receiver<LineChunk>>,
+  loading_progress: f64,
+  recognizing: bool,
+  anchors: HashMap<String, usize>,
+  /// The table of contents with the line each chapter starts at
+  chapters: Vec<(Chapter, usize)>,
+  /// Where `:b` returns to after following references
+  jumps: Vec<usize>,
+  highlights: Vec<Highlight>,
+  /// The line and byte range of each part of a highlight, with its index
+  highlight_spans: Vec<(usize, std::ops::Range<usize>, usize)>,
+  figures: Vec<Figure>,
+  /// The line each figure starts at with its index, by line
+  figure_lines: Vec<(usize, usize)>,
+  image_viewer: String,
+  /// Draws the images of the figures below their placeholder, `None` when
+  /// the terminal can not show images
+  images: Option<Images>,
+  /// Whether each line is in a right-to-left paragraph
+  rtl_lines: Vec<bool>,
+  /// Where the words of each line are in the source text, see
+  /// `cli_justify::Line::spans`
+  line_spans: Vec<Vec<(usize, usize)>>,
+  line_paragraphs: Vec<usize>,
+  /// How many lines the last render showed, fewer than the height when
+  /// images take up rows
+  shown_lines: usize,
+  /// Shown at the bottom until the next key is pressed
+  status: Option<String>,
 }
 
+/// Resets and sets the bidi support mode of ECMA-48, the terminal draws
+/// the characters in the order they are printed.
+const BIDI_EXPLICIT_MODE: &str = "\x1b[8l";
+const BIDI_IMPLICIT_MODE: &str = "\x1b[8h";
+
+/// How long to wait for a key before checking for newly converted lines.
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 impl Editor {
-  pub fn new(lines: Vec<String>, col: usize) -> Self {
-    let document_hash = generate_hash(&lines);
-    let total_lines = lines.len();
+  pub fn new(lines: Vec<cli_justify::Line>, col: usize) -> Self {
     let (width, height) = terminal::size()
       .map(|(w, h)| (w as usize, h as usize))
       .unwrap_or((80, 24));
 
-    Self {
-      lines,
+    let mut editor = Self {
+      lines: Vec::new(),
+      line_prefixes: Vec::new(),
+      line_kinds: Vec::new(),
+      alignment: cli_justify::Alignment::default(),
       col,
       offset: 0,
       width,
       height,
       show_highlighter: true,
       editor_state: EditorState::new(),
-      document_hash,
-      total_lines,
-      progress_display_until: None,
+      document_hash: 0,
+      total_lines: 0,
       show_progress: false,
+      enable_tutorial: true,
+      source: None,
+      loading_progress: 0.0,
+      recognizing: false,
+      anchors: HashMap::new(),
+      chapters: Vec::new(),
+      jumps: Vec::new(),
+      highlights: Vec::new(),
+      highlight_spans: Vec::new(),
+      figures: Vec::new(),
+      figure_lines: Vec::new(),
+      image_viewer: default_viewer().to_string(),
+      images: None,
+      shown_lines: height,
+      status: None,
+      rtl_lines: Vec::new(),
+      line_spans: Vec::new(),
+      line_paragraphs: Vec::new(),
+    };
+    editor.append_lines(lines);
+    editor.document_hash = editor.hash_text();
+
+    editor
+  }
+
+  /// An editor for a document that is still being converted.
+  pub fn with_source(source: mpsc::Receiver<LineChunk>, col: usize) -> Self {
+    let mut editor = Self::new(Vec::new(), col);
+    editor.source = Some(source);
+    editor
+  }
+
+  fn append_chunk(&mut self, chunk: LineChunk) {
+    self.append_lines(chunk.lines);
+    self.loading_progress = chunk.progress;
+    self.recognizing = chunk.recognizing;
+    self.anchors.extend(chunk.anchors);
+    self.chapters.extend(chunk.chapters);
+    self.place_highlights(chunk.highlights);
+    self.place_figures(chunk.figures);
+  }
+
+  fn append_lines(&mut self, lines: Vec<cli_justify::Line>) {
+    for line in lines {
+      self.lines.push(line.text);
+      self.line_prefixes.push(line.prefix);
+      self.line_kinds.push(line.kind);
+      self.rtl_lines.push(line.rtl);
+      self.line_spans.push(line.spans);
+      self.line_paragraphs.push(line.paragraph);
     }
+    self.total_lines = self.lines.len();
   }
 
-  pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    let mut stdout = io::stdout();
-    let config = load_config();
+  /// The hash the reading progress is saved under, of the text without the
+  /// whitespace and hyphens it is broken into lines with, so it stays the
+  /// same for any width. Tables are drawn anew for each width and count
+  /// by where they are in the source text.
+  fn hash_text(&self) -> u64 {
+    let mut text = String::new();
 
-    self.show_highlighter = config.enable_line_highlighter.unwrap_or(true);
+    for (i, line) in self.lines.iter().enumerate() {
+      if (self.is_table_row(i)) {
+        let previous = i.checked_sub(1).map(|x| &self.line_spans[x]);
+        if (previous != Some(&self.line_spans[i])) {
+          text.push_str(&format!("\0{}\0", self.line_spans[i][0].1));
+        }
+        continue;
+      }
 
-    let show_tutorial = match config.enable_tutorial {
-      Some(false) => false,
-      _ => self.lines.is_empty() || load_progress(self.document_hash).is_err(),
+      let line = match self.is_hyphenated(i) {
+        true => &line[..line.len() - 1],
+        false => line.as_str(),
+      };
+      text.extend(line.chars().filter(|x| !x.is_whitespace()));
+    }
+
+    generate_hash(&text)
+  }
+
+  /// Whether line `index` ends with a hyphen the word was broken with,
+  /// rather than one of the source text: the rest of the word on the next
+  /// line follows right after the hyphen's place in the source text.
+  fn is_hyphenated(&self, index: usize) -> bool {
+    let line = &self.lines[index];
+    let last = self.line_spans[index].last();
+    let next = self.line_spans.get(index + 1).and_then(|x| x.first());
+    let (Some(&(at, source)), Some(&(_, next))) = (last, next) else {
+      return false;
     };
 
-    if show_tutorial {
-      self.show_tutorial(&mut stdout)?;
+    line.ends_with('-') && next == source + line.len() - 1 - at
+  }
+
+  /// Whether line `index` is part of a table, all of whose rows map to
+  /// where the table starts.
+  fn is_table_row(&self, index: usize) -> bool {
+    let spans = &self.line_spans[index];
+    let same = |x: Option<usize>| {
+      x.and_then(|x| self.line_spans.get(x)).is_some_and(|x| x == spans)
+    };
+
+    self.line_kinds[index] == cli_justify::LineKind::Fixed
+      && !spans.is_empty()
+      && (same(index.checked_sub(1)) || same(Some(index + 1)))
+  }
+
+  /// The hash progress was saved under before it was saved with the
+  /// source offset, of the justified lines whichever way they are aligned.
+  fn hash_lines(&self) -> u64 {
+    let justified: Vec<String> = (0..self.lines.len())
+      .map(|i| {
+        let line = self.line(i);
+        cli_justify::align(&line, self.col, cli_justify::Alignment::Justified)
+      })
+      .collect();
+
+    generate_hash(&justified)
+  }
+
+  fn line(&self, index: usize) -> cli_justify::Line {
+    cli_justify::Line {
+      prefix: self.line_prefixes[index].clone(),
+      text: self.lines[index].clone(),
+      kind: self.line_kinds[index],
+      rtl: self.rtl_lines[index],
+      spans: self.line_spans[index].clone(),
+      paragraph: self.line_paragraphs[index],
     }
+  }
 
-    // If the file is empty, exit after tutorial
-    if self.lines.is_empty() {
-      self.cleanup(&mut stdout)?;
-      return Ok(());
+  /// The characters of line `index` as they are drawn from left to right,
+  /// aligned, each with its byte offset in the line.
+  fn line_order(&self, index: usize) -> Vec<(usize, char)> {
+    let line = self.line(index);
+    let aligned = cli_justify::align_order(&line, self.col, self.alignment);
+    if (!line.rtl && !cli_justify::has_rtl(&line.text)) {
+      return aligned;
     }
 
-    self.offset = match load_progress(self.document_hash) {
-      Ok(progress) => {
-        (progress.percentage / 100.0 * self.total_lines as f64).round() as usize
+    let text: String = aligned.iter().map(|x| x.1).collect();
+    let starts: Vec<usize> = text.char_indices().map(|x| x.0).collect();
+    cli_justify::visual_order(&text, line.rtl)
+      .into_iter()
+      .map(|(i, c)| (aligned[starts.partition_point(|&x| x < i)].0, c))
+      .collect()
+  }
+
+  /// Looks for the highlights in the lines of their piece, each after the
+  /// one before it in the same piece. Those not found there are dropped.
+  fn place_highlights(
+    &mut self,
+    highlights: Vec<(std::ops::Range<usize>, Highlight)>,
+  ) {
+    let mut piece = 0..0;
+    let mut from = (0, 0);
+
+    for (lines, highlight) in highlights {
+      if (lines != piece) {
+        from = (lines.start, 0);
+        piece = lines.clone();
+      }
+      let lines = &self.lines[..lines.end.min(self.lines.len())];
+      let Some(spans) = find_words(lines, from, &highlight.text) else {
+        continue;
+      };
+
+      if let Some((line, range)) = spans.last() {
+        from = (*line, range.end);
+      }
+      let index = self.highlights.len();
+      self
+        .highlight_spans
+        .extend(spans.into_iter().map(|(line, range)| (line, range, index)));
+      self.highlights.push(highlight);
+    }
+  }
+
+  /// Looks for the placeholders of the figures in the lines of their piece,
+  /// each after the one before it in the same piece, so figures with the
+  /// same placeholder are not all placed on the first. Those not found there
+  /// are dropped.
+  fn place_figures(&mut self, figures: Vec<(std::ops::Range<usize>, Figure)>) {
+    let mut piece = 0..0;
+    let mut from = (0, 0);
+
+    for (lines, figure) in figures {
+      if (lines != piece) {
+        from = (lines.start, 0);
+        piece = lines.clone();
+      }
+      let lines = &self.lines[..lines.end.min(self.lines.len())];
+      let Some(spans) = find_words(lines, from, &figure.label) else {
+        continue;
+      };
+
+      if let Some((line, range)) = spans.last() {
+        from = (*line, range.end);
+      }
+      self.figure_lines.push((spans[0].0, self.figures.len()));
+      self.figures.push(figure);
+    }
+    self.figure_lines.sort();
+  }
+
+  /// Appends the lines that arrived since the last call without blocking,
+  /// returns whether anything changed.
+  fn receive_lines(&mut self) -> bool {
+    let Some(source) = self.source.take() else {
+      return false;
+    };
+
+    let mut changed = false;
+    loop {
+      match source.try_recv() {
+        Ok(chunk) => {
+          self.append_chunk(chunk);
+          changed = true;
+        }
+        Err(mpsc::TryRecvError::Empty) => {
+          self.source = Some(source);
+          return changed;
+        }
+        Err(mpsc::TryRecvError::Disconnected) => return true,
       }
-      Err(_) => 0,
+    }
+  }
+
+  /// Blocks until more lines arrive or the document is complete.
+  fn wait_for_lines(&mut self) {
+    let Some(source) = self.source.take() else {
+      return;
+    };
+
+    if let Ok(chunk) = source.recv() {
+      self.append_chunk(chunk);
+      self.source = Some(source);
+      self.receive_lines();
+    }
+  }
+
+  fn should_show_tutorial(&self) -> bool {
+    self.enable_tutorial
+      && (self.lines.is_empty() || self.saved_progress().is_none())
+  }
+
+  /// The progress saved for the document, or under the hash of its lines
+  /// it was saved with before.
+  fn saved_progress(&self) -> Option<Progress> {
+    load_progress(self.document_hash)
+      .or_else(|_| load_progress(self.hash_lines()))
+      .ok()
+  }
+
+  fn restore_progress(&mut self) {
+    self.offset = match self.saved_progress() {
+      Some(progress) => self.progress_offset(&progress),
+      None => 0,
     };
+  }
+
+  /// The line to show at the top for saved progress, the one its source
+  /// offset is on however the text was justified when it was saved.
+  fn progress_offset(&self, progress: &Progress) -> usize {
+    let lines: Vec<cli_justify::Line> = self
+      .line_spans
+      .iter()
+      .map(|x| cli_justify::Line { spans: x.clone(), ..Default::default() })
+      .collect();
+    let line = progress.source.and_then(|x| cli_justify::find_line(&lines, x));
+
+    line.unwrap_or_else(|| {
+      (progress.percentage / 100.0 * self.total_lines as f64).round() as usize
+    })
+  }
+
+  /// The byte offset in the source text of the top line, or of the last
+  /// line with text before it.
+  fn top_source(&self) -> Option<usize> {
+    let above = self.line_spans.get(..=self.offset)?;
+    above.iter().rev().find_map(|x| x.first().map(|x| x.1))
+  }
+
+  /// The hash of a streamed document, and with it the saved progress, is
+  /// only known once it is complete. Nothing is restored when the user
+  /// already scrolled, so the view does not jump away while reading.
+  fn document_loaded(
+    &mut self,
+    stdout: &mut io::Stdout,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    self.document_hash = self.hash_text();
+
+    if self.offset != 0 {
+      return Ok(());
+    }
+
+    if self.should_show_tutorial() {
+      self.show_tutorial(stdout)?;
+    }
+    self.restore_progress();
+
+    Ok(())
+  }
+
+  pub fn run(
+    &mut self,
+    alignment: Option<cli_justify::Alignment>,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout();
+    let config = load_config();
+
+    let configured = config
+      .alignment
+      .and_then(|x| cli_justify::parse_alignment(x.trim()).ok());
+    self.alignment = alignment.or(configured).unwrap_or_default();
+
+    self.show_highlighter = config.enable_line_highlighter.unwrap_or(true);
+    self.enable_tutorial = config.enable_tutorial != Some(false);
+    if let Some(viewer) = config.image_viewer {
+      self.image_viewer = viewer;
+    }
+    if std::io::stdout().is_terminal() {
+      self.images =
+        graphics::protocol(config.image_protocol.as_deref()).map(Images::new);
+    }
+
+    // Without a terminal the whole document is printed at once
+    self.wait_for_lines();
+    while !std::io::stdout().is_terminal() && self.source.is_some() {
+      self.wait_for_lines();
+    }
+
+    if self.source.is_none() {
+      self.document_hash = self.hash_text();
+
+      if self.should_show_tutorial() {
+        self.show_tutorial(&mut stdout)?;
+      }
+
+      // If the file is empty, exit after tutorial
+      if self.lines.is_empty() {
+        self.cleanup(&mut stdout)?;
+        return Ok(());
+      }
+
+      self.restore_progress();
+    }
 
     if std::io::stdout().is_terminal() {
       execute!(stdout, terminal::EnterAlternateScreen, Hide)?;
+      // Terminals that reorder right-to-left text themselves, like VTE,
+      // leave it to the reader in explicit mode
+      print!("{}", BIDI_EXPLICIT_MODE);
       terminal::enable_raw_mode()?;
     }
 
@@ -141,6 +513,9 @@ impl Editor {
       loop {
         // Display tutorial with scrolling
         execute!(stdout, Clear(ClearType::All))?;
+        if let Some(images) = &self.images {
+          images.clear(stdout)?;
+        }
         let center_offset = if self.width > self.col {
           (self.width / 2) - self.col / 2
         } else {
@@ -160,8 +535,8 @@ impl Editor {
         stdout.flush()?;
 
         // Handle scrolling input
-        match event::read()? {
-          CEvent::Key(key_event) => match key_event.code {
+        if let CEvent::Key(key_event) = event::read()? {
+          match key_event.code {
             KeyCode::Char('j') | KeyCode::Down => {
               if tutorial_offset + self.height < tutorial_lines.len() {
                 tutorial_offset += 1;
@@ -180,8 +555,7 @@ impl Editor {
               tutorial_offset = tutorial_offset.saturating_sub(self.height);
             }
             _ => break,
-          },
-          _ => {}
+          }
         }
       }
 
@@ -203,6 +577,10 @@ impl Editor {
     stdout: &mut io::Stdout,
   ) -> Result<(), Box<dyn std::error::Error>> {
     if std::io::stdout().is_terminal() {
+      if let Some(images) = &self.images {
+        images.release(stdout)?;
+      }
+      print!("{}", BIDI_IMPLICIT_MODE);
       execute!(stdout, Show, terminal::LeaveAlternateScreen)?;
       terminal::disable_raw_mode()?;
     }
@@ -213,9 +591,12 @@ impl Editor {
     &mut self,
     stdout: &mut io::Stdout,
   ) -> Result<(), Box<dyn std::error::Error>> {
-    loop {
+    'render: loop {
       if std::io::stdout().is_terminal() {
         execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        if let Some(images) = &self.images {
+          images.clear(stdout)?;
+        }
       }
 
       let center = true;
@@ -225,10 +606,15 @@ impl Editor {
       let center_offset_string =
         if center { " ".repeat(center_offset) } else { "".to_string() };
 
-      for (i, line_orig) in
-        self.lines.iter().skip(self.offset).take(self.height).enumerate()
-      {
-        let line = line_orig.clone();
+      let cell = graphics::cell_size();
+      let mut i = 0;
+      self.shown_lines = 0;
+
+      for (index, line) in self.lines.iter().enumerate().skip(self.offset) {
+        if (i >= self.height) {
+          break;
+        }
+        self.shown_lines += 1;
         execute!(stdout, MoveTo(0, i as u16))?;
 
         if self.show_highlighter && i == self.height / 2 {
@@ -240,28 +626,39 @@ impl Editor {
           execute!(stdout, MoveTo(0, i as u16))?;
         }
 
-        // Handle search highlight
-        if let Some((line_idx, start, end)) = self.editor_state.current_match {
-          if line_idx == self.offset + i {
-            print!("{}", center_offset_string);
-            print!("{}", &line[..start]);
-            execute!(
-              stdout,
-              SetBackgroundColor(Color::Yellow),
-              SetForegroundColor(Color::Black)
-            )?;
-            print!("{}", &line[start..end]);
-            execute!(stdout, ResetColor)?;
-            println!("{}", &line[end..]);
-            continue;
-          }
-        }
-
-        println!("{}{}", center_offset_string, line);
+        // Lines are aligned and right-to-left text is put in visual order
+        // as they are drawn, the styles still refer to the stored line
+        let styles = self.line_styles(index, line.len());
+        let order = self.line_order(index);
+        print!("{}", center_offset_string);
+        print_styled(stdout, &order, &styles)?;
+        println!();
 
         if self.show_highlighter && i == self.height / 2 {
           execute!(stdout, SetBackgroundColor(Color::Reset))?;
         }
+        i += 1;
+
+        // The image of a figure takes up the rows below its placeholder, at
+        // most half the screen so it is seen whole while reading
+        let image = self
+          .figure_lines
+          .iter()
+          .find(|x| x.0 == index)
+          .and_then(|x| self.figures[x.1].image.as_deref());
+        if let (Some(path), Some(images)) = (image, self.images.as_mut()) {
+          let width = self.col.min(self.width);
+          let max_rows = (self.height / 2) as u16;
+          if let Some(size) = images.size(path, width as u16, max_rows, cell) {
+            // Drawn once it clears the lines at the bottom of the screen
+            if (i + size.1 as usize + 2 <= self.height) {
+              let x = center_offset + (width - size.0 as usize) / 2;
+              execute!(stdout, MoveTo(x as u16, i as u16))?;
+              images.draw(stdout, path, size, cell)?;
+            }
+            i += size.1 as usize;
+          }
+        }
       }
 
       if self.editor_state.mode == EditorMode::Command {
@@ -275,6 +672,33 @@ impl Editor {
         print!("?{}", self.editor_state.command_buffer);
       }
 
+      if let Some(note) = self.visible_note() {
+        let note: String = note
+          .split_whitespace()
+          .collect::<Vec<_>>()
+          .join(" ")
+          .chars()
+          .take(self.width.saturating_sub(12))
+          .collect();
+        execute!(stdout, MoveTo(0, self.height as u16 - 2))?;
+        print!("note: {}", note);
+      }
+
+      // An image that can not be decoded shows only its placeholder
+      let errors = self.images.as_mut().map(Images::take_errors);
+      if let Some(error) = errors.into_iter().flatten().last() {
+        self.status.get_or_insert(error);
+      }
+
+      if let Some(status) = &self.status {
+        if self.editor_state.mode == EditorMode::Normal {
+          let status: String =
+            status.chars().take(self.width.saturating_sub(20)).collect();
+          execute!(stdout, MoveTo(0, (self.height - 1) as u16))?;
+          print!("{}", status);
+        }
+      }
+
       // Show progress if enabled
       if self.show_progress {
         let progress =
@@ -286,10 +710,37 @@ impl Editor {
         print!("{}", message);
       }
 
+      if self.source.is_some() {
+        let progress = (self.loading_progress * 100.0).round();
+        let message = match self.recognizing {
+          true => format!("recognizing text {}%", progress),
+          false => format!("loading {}%", progress),
+        };
+        let x = (self.width as u16).saturating_sub(message.len() as u16 + 2);
+        let y = self.height as u16 - 1;
+        execute!(stdout, MoveTo(x, y))?;
+        print!("{}", message);
+      }
+
       stdout.flush()?;
 
       if std::io::stdout().is_terminal() {
-        match event::read()? {
+        // Lines arriving while no key is pressed are rendered right away
+        while self.source.is_some() && !event::poll(LOADING_POLL_INTERVAL)? {
+          if self.receive_lines() {
+            if self.source.is_none() {
+              self.document_loaded(stdout)?;
+            }
+            continue 'render;
+          }
+        }
+
+        let event = event::read()?;
+        if let CEvent::Key(_) = event {
+          self.status = None;
+        }
+
+        match event {
           CEvent::Key(key_event) => match self.editor_state.mode {
             EditorMode::Normal => match key_event.code {
               KeyCode::Char(':') => {
@@ -306,34 +757,34 @@ impl Editor {
                 self.editor_state.command_buffer.clear();
                 self.editor_state.search_direction = false;
               }
-              KeyCode::Char('n') => {
-                if !self.editor_state.search_query.is_empty() {
-                  // Use the original search direction
-                  self.find_next_match(self.editor_state.search_direction);
-                  self.center_on_match();
-                }
+              KeyCode::Char('n')
+                if !self.editor_state.search_query.is_empty() =>
+              {
+                // Use the original search direction
+                self.find_next_match(self.editor_state.search_direction);
+                self.center_on_match();
               }
-              KeyCode::Char('N') => {
-                if !self.editor_state.search_query.is_empty() {
-                  // Use opposite of original search direction
-                  self.find_next_match(!self.editor_state.search_direction);
-                  self.center_on_match();
-                }
+              KeyCode::Char('N')
+                if !self.editor_state.search_query.is_empty() =>
+              {
+                // Use opposite of original search direction
+                self.find_next_match(!self.editor_state.search_direction);
+                self.center_on_match();
               }
-              KeyCode::Char('j') | KeyCode::Down => {
-                if self.offset + self.height < self.total_lines {
-                  self.offset += 1;
-                }
+              KeyCode::Char(']') => self.jump_to_figure(true),
+              KeyCode::Char('[') => self.jump_to_figure(false),
+              KeyCode::Char('j') | KeyCode::Down
+                if self.offset + self.shown_lines < self.total_lines =>
+              {
+                self.offset += 1;
               }
-              KeyCode::Char('k') | KeyCode::Up => {
-                if self.offset > 0 {
-                  self.offset -= 1;
-                }
+              KeyCode::Char('k') | KeyCode::Up if self.offset > 0 => {
+                self.offset -= 1;
               }
-              KeyCode::PageDown => {
-                if self.offset + self.height < self.total_lines {
-                  self.offset += self.height - 3;
-                }
+              KeyCode::PageDown
+                if self.offset + self.shown_lines < self.total_lines =>
+              {
+                self.offset += self.shown_lines.saturating_sub(3).max(1);
               }
               KeyCode::PageUp => {
                 if self.offset as i32 - self.height as i32 > 0 {
@@ -401,7 +852,15 @@ impl Editor {
         break;
       }
 
-      save_progress(self.document_hash, self.offset, self.total_lines)?;
+      // The hash of a document that is still loading is not known yet
+      if self.source.is_none() {
+        save_progress(
+          self.document_hash,
+          self.offset,
+          self.total_lines,
+          self.top_source(),
+        )?;
+      }
     }
 
     Ok(())
@@ -424,10 +883,217 @@ impl Editor {
         self.editor_state.command_buffer.clear();
         Ok(false)
       }
+      "b" => {
+        if let Some(offset) = self.jumps.pop() {
+          self.offset = offset;
+        }
+        self.editor_state.mode = EditorMode::Normal;
+        self.editor_state.command_buffer.clear();
+        Ok(false)
+      }
+      "toc" => {
+        self.show_chapters(stdout)?;
+        self.editor_state.mode = EditorMode::Normal;
+        self.editor_state.command_buffer.clear();
+        Ok(false)
+      }
+      "o" => {
+        self.open_figure();
+        self.editor_state.mode = EditorMode::Normal;
+        self.editor_state.command_buffer.clear();
+        Ok(false)
+      }
+      cmd if cmd == "align" || cmd.starts_with("align ") => {
+        let alignment = cmd["align".len()..].trim();
+        match cli_justify::parse_alignment(alignment) {
+          Ok(alignment) => self.alignment = alignment,
+          Err(e) => self.status = Some(e.to_string()),
+        }
+        self.editor_state.mode = EditorMode::Normal;
+        self.editor_state.command_buffer.clear();
+        Ok(false)
+      }
+      cmd if cmd.starts_with("l ") => {
+        let label = cmd[2..].trim().to_string();
+        self.follow_reference(&label);
+        self.editor_state.mode = EditorMode::Normal;
+        self.editor_state.command_buffer.clear();
+        Ok(false)
+      }
       cmd => Ok(handle_command(cmd, &mut self.show_highlighter)),
     }
   }
 
+  /// Jumps to the anchor of the first reference `[label]` listed from the
+  /// top of the screen on, references to anything else are ignored.
+  fn follow_reference(&mut self, label: &str) {
+    let prefix = format!("[{label}] ");
+    let target = self.lines[self.offset.min(self.lines.len())..]
+      .iter()
+      .find_map(|x| x.trim_start().strip_prefix(&prefix))
+      .map(|x| x.trim());
+
+    if let Some(&line) = target.and_then(|x| self.anchors.get(x)) {
+      self.jumps.push(self.offset);
+      self.offset = line.min(self.total_lines.saturating_sub(self.height));
+    }
+  }
+
+  /// Lists the chapters with the one the screen is in selected, `j` and `k`
+  /// move the selection, Enter jumps to it and any other key closes the
+  /// list.
+  fn show_chapters(
+    &mut self,
+    stdout: &mut io::Stdout,
+  ) -> Result<(), Box<dyn std::error::Error>> {
+    if self.chapters.is_empty() {
+      self.status = Some("no chapters".to_string());
+      return Ok(());
+    }
+    if !std::io::stdout().is_terminal() {
+      return Ok(());
+    }
+
+    let titles = chapter_list(&self.chapters);
+    let mut selected = self.current_chapter().unwrap_or(0);
+    let mut top = 0;
+    loop {
+      top = top.min(selected).max((selected + 1).saturating_sub(self.height));
+
+      execute!(stdout, Clear(ClearType::All))?;
+      if let Some(images) = &self.images {
+        images.clear(stdout)?;
+      }
+      let center_offset =
+        if self.width > self.col { (self.width / 2) - self.col / 2 } else { 0 };
+      for (i, title) in titles.iter().enumerate().skip(top).take(self.height) {
+        let marker = if i == selected { "> " } else { "  " };
+        execute!(stdout, MoveTo(center_offset as u16, (i - top) as u16))?;
+        print!("{marker}{title}");
+      }
+      stdout.flush()?;
+
+      if let CEvent::Key(key_event) = event::read()? {
+        match key_event.code {
+          KeyCode::Char('j') | KeyCode::Down if selected + 1 < titles.len() => {
+            selected += 1;
+          }
+          KeyCode::Char('k') | KeyCode::Up if selected > 0 => {
+            selected -= 1;
+          }
+          KeyCode::Char('j' | 'k') | KeyCode::Down | KeyCode::Up => {}
+          KeyCode::Enter => {
+            self.jump_to_chapter(selected);
+            break;
+          }
+          _ => break,
+        }
+      }
+    }
+
+    execute!(stdout, Clear(ClearType::All))?;
+    Ok(())
+  }
+
+  /// The last chapter starting at or above the top of the screen.
+  fn current_chapter(&self) -> Option<usize> {
+    let next = self.chapters.partition_point(|x| x.1 <= self.offset);
+    next.checked_sub(1)
+  }
+
+  /// Scrolls the chapter to the top, `:b` returns to where the screen was.
+  fn jump_to_chapter(&mut self, index: usize) {
+    self.jumps.push(self.offset);
+    let line = self.chapters[index].1;
+    self.offset = line.min(self.total_lines.saturating_sub(self.height));
+  }
+
+  /// Scrolls the next figure below the top of the screen, or the previous
+  /// one above it, to the top.
+  fn jump_to_figure(&mut self, forward: bool) {
+    let line = match forward {
+      true => self.figure_lines.iter().find(|x| x.0 > self.offset),
+      false => self.figure_lines.iter().rev().find(|x| x.0 < self.offset),
+    };
+
+    match line {
+      Some(&(line, _)) => {
+        self.offset = line.min(self.total_lines.saturating_sub(self.height));
+      }
+      None if self.figure_lines.is_empty() => {
+        self.status = Some("no figures".to_string());
+      }
+      None => {
+        self.status = Some(match forward {
+          true => "no figure below".to_string(),
+          false => "no figure above".to_string(),
+        });
+      }
+    }
+  }
+
+  /// Opens the image of the first figure on the screen in the image viewer.
+  fn open_figure(&mut self) {
+    let screen = self.offset..self.offset + self.height;
+    let figure = self
+      .figure_lines
+      .iter()
+      .find(|x| screen.contains(&x.0))
+      .map(|x| &self.figures[x.1]);
+
+    self.status = match figure {
+      None => Some("no figure on the screen".to_string()),
+      Some(Figure { image: None, .. }) => {
+        Some("the image of this figure was not saved".to_string())
+      }
+      Some(Figure { image: Some(image), label }) => {
+        match open_image(&self.image_viewer, image) {
+          Ok(()) => Some(format!("opened {label}")),
+          Err(e) => Some(e),
+        }
+      }
+    };
+  }
+
+  /// The styled parts of line `index`, the search match is drawn over the
+  /// highlights.
+  fn line_styles(
+    &self,
+    index: usize,
+    len: usize,
+  ) -> Vec<(usize, usize, Style)> {
+    let mut styles: Vec<(usize, usize, Style)> = self
+      .highlight_spans
+      .iter()
+      .filter(|(line, _, _)| *line == index)
+      .map(|(_, range, x)| match self.highlights[*x].underline {
+        true => (range.start, range.end, Style::Underline),
+        false => (range.start, range.end, Style::Highlight),
+      })
+      .collect();
+
+    if let Some((line_idx, start, end)) = self.editor_state.current_match {
+      if line_idx == index {
+        styles.push((start, end, Style::Match));
+      }
+    }
+
+    styles.retain(|(start, end, _)| start < end && *end <= len);
+    styles
+  }
+
+  /// The note of the first highlight on the screen that has one.
+  fn visible_note(&self) -> Option<&str> {
+    let screen = self.offset..self.offset + self.height;
+    let mut visible: Vec<_> =
+      self.highlight_spans.iter().filter(|x| screen.contains(&x.0)).collect();
+    visible.sort_by_key(|(line, range, _)| (*line, range.start));
+
+    visible
+      .into_iter()
+      .find_map(|(_, _, x)| self.highlights[*x].note.as_deref())
+  }
+
   fn find_next_match(&mut self, forward: bool) {
     if self.editor_state.search_query.is_empty() {
       return;
@@ -441,7 +1107,7 @@ impl Editor {
     };
 
     let find_in_line = |line: &str, query: &str| -> Option<(usize, usize)> {
-      line.to_lowercase().find(&query).map(|start| (start, start + query.len()))
+      line.to_lowercase().find(query).map(|start| (start, start + query.len()))
     };
 
     if forward {
@@ -492,6 +1158,59 @@ impl Editor {
   }
 }
 
+/// How a part of a line is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+  Plain,
+  Highlight,
+  Underline,
+  Match,
+}
+
+/// Prints the characters of a line in their visual `order`, each with its
+/// byte offset in the line, with the styled byte ranges of the line. Later
+/// ranges are drawn over earlier ones.
+fn print_styled(
+  stdout: &mut io::Stdout,
+  order: &[(usize, char)],
+  styles: &[(usize, usize, Style)],
+) -> io::Result<()> {
+  let style_at = |at: usize| {
+    styles
+      .iter()
+      .rev()
+      .find(|(start, end, _)| (*start..*end).contains(&at))
+      .map_or(Style::Plain, |x| x.2)
+  };
+
+  for run in order.chunk_by(|a, b| style_at(a.0) == style_at(b.0)) {
+    let style = style_at(run[0].0);
+    let text: String = run.iter().map(|x| x.1).collect();
+
+    match style {
+      Style::Plain => {}
+      Style::Highlight => execute!(
+        stdout,
+        SetBackgroundColor(Color::Rgb { r: 90, g: 80, b: 20 })
+      )?,
+      Style::Underline => {
+        execute!(stdout, SetAttribute(Attribute::Underlined))?
+      }
+      Style::Match => execute!(
+        stdout,
+        SetBackgroundColor(Color::Yellow),
+        SetForegroundColor(Color::Black)
+      )?,
+    }
+    print!("{}", text);
+    if (style != Style::Plain) {
+      execute!(stdout, SetAttribute(Attribute::Reset), ResetColor)?;
+    }
+  }
+
+  Ok(())
+}
+
 pub fn handle_command(command: &str, show_highlighter: &mut bool) -> bool {
   match command.trim() {
     "q" => true,
@@ -503,3 +1222,133 @@ pub fn handle_command(command: &str, show_highlighter: &mut bool) -> bool {
     _ => false,
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn editor(lines: &[&str]) -> Editor {
+    let lines = lines
+      .iter()
+      .map(|x| cli_justify::Line { text: x.to_string(), ..Default::default() })
+      .collect();
+
+    Editor::new(lines, 40)
+  }
+
+  fn figure(label: &str) -> Figure {
+    Figure { label: label.to_string(), image: None }
+  }
+
+  #[test]
+  fn test_figures_with_the_same_placeholder_are_placed_in_turn() {
+    let mut editor = editor(&[
+      "[Figure: 1×1]",
+      "A page with two figures",
+      "[Figure: 1×1] and [Figure: 1×1]",
+      "[Figure: 1×1]",
+    ]);
+
+    editor.place_figures(vec![
+      (0..1, figure("[Figure: 1×1]")),
+      (1..3, figure("[Figure: 1×1]")),
+      (1..3, figure("[Figure: 1×1]")),
+      // Not in its piece, the next line belongs to another one
+      (1..3, figure("[Figure: 1×1]")),
+      (3..4, figure("[Figure: 2×2]")),
+    ]);
+
+    assert_eq!(editor.figure_lines, vec![(0, 0), (2, 1), (2, 2)]);
+    assert_eq!(editor.figures.len(), 3);
+  }
+
+  #[test]
+  fn test_jumping_to_a_chapter_returns_with_b() {
+    let lines: Vec<String> = (0..100).map(|i| format!("line {i}")).collect();
+    let mut editor =
+      editor(&lines.iter().map(|x| x.as_str()).collect::<Vec<_>>());
+    editor.height = 10;
+    let chapter = |title: &str| Chapter { title: title.to_string(), level: 0 };
+    editor.chapters =
+      vec![(chapter("One"), 0), (chapter("Two"), 40), (chapter("End"), 95)];
+
+    editor.offset = 39;
+    assert_eq!(editor.current_chapter(), Some(0));
+    editor.offset = 40;
+    assert_eq!(editor.current_chapter(), Some(1));
+
+    // The last chapter is scrolled as far as the end of the document allows
+    editor.jump_to_chapter(2);
+    assert_eq!(editor.offset, 90);
+    assert_eq!(editor.jumps, [40]);
+  }
+
+  #[test]
+  fn test_highlights_are_placed_in_their_piece() {
+    let mut editor = editor(&["the word", "", "the word again"]);
+    let highlight =
+      |text: &str| Highlight { text: text.to_string(), ..Default::default() };
+
+    editor.place_highlights(vec![
+      (2..3, highlight("word")),
+      (2..3, highlight("word")),
+      (0..1, highlight("missing")),
+    ]);
+
+    assert_eq!(editor.highlight_spans, vec![(2, 4..8, 0)]);
+    assert_eq!(editor.highlights.len(), 1);
+  }
+
+  const TEXT: &str = "Hyphenation breaks extraordinarily long words, \
+    like incomprehensibilities, between their syllables.\n\n\
+    | well-known | table |\n| a | b |\n\n\
+    A last paragraph with well-known words.";
+
+  fn laid_out(width: usize) -> Editor {
+    let settings = cli_justify::Settings {
+      hyphenation: Some(cli_justify::Hyphenation::new("en").unwrap()),
+      ..Default::default()
+    };
+
+    Editor::new(cli_justify::layout(TEXT, width, &settings), width)
+  }
+
+  #[test]
+  fn test_the_document_hash_does_not_depend_on_the_width() {
+    let narrow = laid_out(16);
+    let wide = laid_out(60);
+
+    assert!((0..narrow.lines.len()).any(|i| narrow.is_hyphenated(i)));
+    assert!((0..narrow.lines.len()).any(|i| narrow.is_table_row(i)));
+    assert_eq!(narrow.document_hash, wide.document_hash);
+    assert_ne!(narrow.hash_lines(), wide.hash_lines());
+  }
+
+  #[test]
+  fn test_progress_is_restored_to_the_line_of_its_source_offset() {
+    let mut narrow = laid_out(16);
+    let wide = laid_out(60);
+    let last = TEXT.find("A last").unwrap();
+
+    narrow.offset = cli_justify::find_line(
+      &(0..narrow.lines.len()).map(|i| narrow.line(i)).collect::<Vec<_>>(),
+      last,
+    )
+    .unwrap();
+    let progress = Progress {
+      document_hash: narrow.document_hash,
+      offset: narrow.offset,
+      total_lines: narrow.total_lines,
+      percentage: 0.0,
+      source: narrow.top_source(),
+    };
+
+    let line = wide.progress_offset(&progress);
+    assert!(wide.lines[line].starts_with("A last"));
+
+    // Progress saved without a source offset is restored by its percentage
+    let old = Progress { source: None, percentage: 50.0, ..progress };
+    let half = (wide.total_lines as f64 / 2.0).round() as usize;
+    assert_eq!(wide.progress_offset(&old), half);
+  }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// An image of the document, shown as its placeholder in the text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Figure {
+  /// The placeholder in the text like `[Figure: alt text, 640×480]`,
+  /// matched word by word like a highlight
+  pub label: String,
+  /// The image file to open in a viewer, `None` when it was not saved
+  pub image: Option<PathBuf>,
+}
+
+/// The viewer used when `IMAGE_VIEWER` is not configured.
+pub(crate) fn default_viewer() -> &'static str {
+  if cfg!(target_os = "macos") {
+    return "open";
+  }
+  if cfg!(windows) {
+    return "explorer";
+  }
+
+  "xdg-open"
+}
+
+/// Opens the image with `viewer`, a program optionally followed by its
+/// arguments like `feh --fullscreen`. The viewer runs on after the reader
+/// exits.
+pub(crate) fn open_image(viewer: &str, image: &Path) -> Result<(), String> {
+  let mut words = viewer.split_whitespace();
+  let program = words.next().ok_or("IMAGE_VIEWER is empty")?;
+
+  let mut child = Command::new(program)
+    .args(words)
+    .arg(image)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|e| format!("{program}: {e}"))?;
+
+  // Reaped in the background, so it does not linger as a zombie
+  std::thread::spawn(move || child.wait());
+
+  Ok(())
+}
@@ -0,0 +1,439 @@
+use base64::Engine;
+use image::{imageops::FilterType, DynamicImage, ImageFormat, RgbaImage};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+/// Images are transmitted to kitty at most this many pixels wide or high,
+/// kitty scales them to the cells they are placed in.
+const MAX_TRANSMIT_SIZE: u32 = 2048;
+
+/// Kitty reads the image data in chunks of at most this many bytes.
+const KITTY_CHUNK_LEN: usize = 4096;
+
+/// The size of a cell in pixels when the terminal does not report it.
+const DEFAULT_CELL_SIZE: (f64, f64) = (8.0, 16.0);
+
+/// Decoded images kept for redrawing, the least recently drawn are decoded
+/// again when they are needed.
+const MAX_DECODED: usize = 8;
+
+/// How the terminal draws images.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Protocol {
+  Kitty,
+  Sixel,
+}
+
+/// The protocol for the `IMAGE_PROTOCOL` setting `auto`, `kitty`, `sixel`
+/// or `none`, detected from the terminal when it is not set.
+pub(crate) fn protocol(setting: Option<&str>) -> Option<Protocol> {
+  match setting.map(|x| x.trim().to_lowercase()).as_deref() {
+    None | Some("auto") | Some("") => detect(),
+    Some("kitty") => Some(Protocol::Kitty),
+    Some("sixel") => Some(Protocol::Sixel),
+    Some(_) => None,
+  }
+}
+
+/// Guesses the protocol from the environment, the terminals answer no
+/// query that tells reliably. Multiplexers pass neither protocol through
+/// by default.
+fn detect() -> Option<Protocol> {
+  detect_from(|name| std::env::var(name).ok())
+}
+
+/// Like `detect`, with the environment variables `env` looks up.
+fn detect_from(env: impl Fn(&str) -> Option<String>) -> Option<Protocol> {
+  let var = |name: &str| env(name).unwrap_or_default();
+  let is_set = |name: &str| env(name).is_some();
+  let term = var("TERM");
+  let program = var("TERM_PROGRAM");
+
+  if (is_set("TMUX") || term.starts_with("screen") || term.starts_with("tmux"))
+  {
+    return None;
+  }
+
+  if (is_set("KITTY_WINDOW_ID")
+    || term.contains("kitty")
+    || term.contains("ghostty")
+    || matches!(program.as_str(), "WezTerm" | "ghostty"))
+  {
+    return Some(Protocol::Kitty);
+  }
+
+  if (is_set("WT_SESSION")
+    || term.contains("sixel")
+    || term == "foot"
+    || term.starts_with("foot-")
+    || term.starts_with("mlterm")
+    || matches!(program.as_str(), "iTerm.app" | "mintty"))
+  {
+    return Some(Protocol::Sixel);
+  }
+
+  None
+}
+
+/// The size of a cell in pixels.
+pub(crate) fn cell_size() -> (f64, f64) {
+  match crossterm::terminal::window_size() {
+    Ok(x) if x.width > 0 && x.height > 0 && x.columns > 0 && x.rows > 0 => {
+      (x.width as f64 / x.columns as f64, x.height as f64 / x.rows as f64)
+    }
+    _ => DEFAULT_CELL_SIZE,
+  }
+}
+
+/// Draws the images of figures inline, the figures of images that can not be
+/// decoded show only their placeholder.
+pub(crate) struct Images {
+  protocol: Protocol,
+  /// The images drawn last, the most recently drawn at the end
+  decoded: Vec<(PathBuf, DynamicImage)>,
+  /// The files that could not be read or decoded
+  failed: HashSet<PathBuf>,
+  /// Why files could not be decoded, until the reader shows it
+  errors: Vec<String>,
+  /// The kitty image id of each transmitted file
+  transmitted: HashMap<PathBuf, u32>,
+  /// The sixel data of each file by its size in cells
+  sixels: HashMap<(PathBuf, u16, u16), String>,
+}
+
+impl Images {
+  pub(crate) fn new(protocol: Protocol) -> Self {
+    Self {
+      protocol,
+      decoded: Vec::new(),
+      failed: HashSet::new(),
+      errors: Vec::new(),
+      transmitted: HashMap::new(),
+      sixels: HashMap::new(),
+    }
+  }
+
+  fn decode(&mut self, path: &Path) -> Option<&DynamicImage> {
+    if self.failed.contains(path) {
+      return None;
+    }
+
+    match self.decoded.iter().position(|x| x.0 == path) {
+      Some(i) => {
+        let image = self.decoded.remove(i);
+        self.decoded.push(image);
+      }
+      None => match read_image(path) {
+        Ok(image) => {
+          self.decoded.push((path.to_path_buf(), image));
+          if (self.decoded.len() > MAX_DECODED) {
+            self.decoded.remove(0);
+          }
+        }
+        Err(e) => {
+          let name = path.file_name().unwrap_or_default().to_string_lossy();
+          self.errors.push(format!("can not show {name}: {e}, :o opens it"));
+          self.failed.insert(path.to_path_buf());
+          return None;
+        }
+      },
+    }
+
+    self.decoded.last().map(|x| &x.1)
+  }
+
+  /// Why the images drawn since the last call could not be decoded.
+  pub(crate) fn take_errors(&mut self) -> Vec<String> {
+    std::mem::take(&mut self.errors)
+  }
+
+  /// The size in cells of the image at `path` shrunk to fit into
+  /// `max_cols` and `max_rows`, `None` when it can not be drawn.
+  pub(crate) fn size(
+    &mut self,
+    path: &Path,
+    max_cols: u16,
+    max_rows: u16,
+    cell: (f64, f64),
+  ) -> Option<(u16, u16)> {
+    let image = self.decode(path)?;
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    if (width == 0.0 || height == 0.0 || max_cols == 0 || max_rows == 0) {
+      return None;
+    }
+
+    let scale = (max_cols as f64 * cell.0 / width)
+      .min(max_rows as f64 * cell.1 / height)
+      .min(1.0);
+    let cols = (width * scale / cell.0).round().clamp(1.0, max_cols as f64);
+    let rows = (height * scale / cell.1).ceil().clamp(1.0, max_rows as f64);
+
+    Some((cols as u16, rows as u16))
+  }
+
+  /// Draws the image at the cursor, `cols` by `rows` cells, the cursor is
+  /// left where it was for kitty and below the image for sixel.
+  pub(crate) fn draw(
+    &mut self,
+    out: &mut impl Write,
+    path: &Path,
+    (cols, rows): (u16, u16),
+    cell: (f64, f64),
+  ) -> io::Result<()> {
+    match self.protocol {
+      Protocol::Kitty => {
+        let id = match self.transmitted.get(path) {
+          Some(&id) => id,
+          None => {
+            let id = self.transmitted.len() as u32 + 1;
+            let Some(image) = self.decode(path) else { return Ok(()) };
+            let png = encode_png(image)?;
+            transmit_kitty(out, id, &png)?;
+            self.transmitted.insert(path.to_path_buf(), id);
+            id
+          }
+        };
+
+        write!(out, "\x1b_Ga=p,i={id},c={cols},r={rows},C=1,q=2\x1b\\")
+      }
+      Protocol::Sixel => {
+        let key = (path.to_path_buf(), cols, rows);
+        if !self.sixels.contains_key(&key) {
+          let Some(image) = self.decode(path) else { return Ok(()) };
+          let scale = (cols as f64 * cell.0 / image.width() as f64)
+            .min(rows as f64 * cell.1 / image.height() as f64);
+          let width = (image.width() as f64 * scale).round().max(1.0) as u32;
+          let height = (image.height() as f64 * scale).round().max(1.0) as u32;
+          let resized = image.resize_exact(width, height, FilterType::Triangle);
+          self.sixels.insert(key.clone(), encode_sixel(&resized.to_rgba8()));
+        }
+
+        out.write_all(self.sixels[&key].as_bytes())
+      }
+    }
+  }
+
+  /// Removes the drawn images from the screen before it is redrawn, kitty
+  /// keeps them over the text otherwise. Sixel images are cleared with the
+  /// text.
+  pub(crate) fn clear(&self, out: &mut impl Write) -> io::Result<()> {
+    match self.protocol {
+      Protocol::Kitty => write!(out, "\x1b_Ga=d,d=a,q=2\x1b\\"),
+      Protocol::Sixel => Ok(()),
+    }
+  }
+
+  /// Frees the images transmitted to the terminal.
+  pub(crate) fn release(&self, out: &mut impl Write) -> io::Result<()> {
+    match self.protocol {
+      Protocol::Kitty => write!(out, "\x1b_Ga=d,d=A,q=2\x1b\\"),
+      Protocol::Sixel => Ok(()),
+    }
+  }
+}
+
+/// Decodes the PNG, JPEG or PNM image, the format is told by its content.
+fn read_image(path: &Path) -> image::ImageResult<DynamicImage> {
+  image::ImageReader::open(path)?.with_guessed_format()?.decode()
+}
+
+fn encode_png(image: &DynamicImage) -> io::Result<Vec<u8>> {
+  let image = match image.width().max(image.height()) > MAX_TRANSMIT_SIZE {
+    true => image.thumbnail(MAX_TRANSMIT_SIZE, MAX_TRANSMIT_SIZE),
+    false => image.clone(),
+  };
+
+  let mut png = Vec::new();
+  image
+    .to_rgba8()
+    .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+    .map_err(io::Error::other)?;
+
+  Ok(png)
+}
+
+/// Sends the PNG to kitty as image `id` without showing it.
+fn transmit_kitty(out: &mut impl Write, id: u32, png: &[u8]) -> io::Result<()> {
+  let data = base64::engine::general_purpose::STANDARD.encode(png);
+  let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_LEN).collect();
+
+  for (i, chunk) in chunks.iter().enumerate() {
+    let more = (i + 1 < chunks.len()) as u8;
+    match i {
+      0 => write!(out, "\x1b_Ga=t,f=100,i={id},q=2,m={more};")?,
+      _ => write!(out, "\x1b_Gm={more};")?,
+    }
+    out.write_all(chunk)?;
+    write!(out, "\x1b\\")?;
+  }
+
+  Ok(())
+}
+
+/// Encodes the image as sixels with a palette of 6 levels per channel,
+/// transparent pixels are left out.
+fn encode_sixel(image: &RgbaImage) -> String {
+  let (width, height) = image.dimensions();
+  let level = |x: u8| (x as usize * 5 + 127) / 255;
+
+  let mut sixel = format!("\x1bP0;1;0q\"1;1;{width};{height}");
+  for i in 0..216 {
+    let (r, g, b) = (i / 36, i / 6 % 6, i % 6);
+    let _ = write!(sixel, "#{i};2;{};{};{}", r * 20, g * 20, b * 20);
+  }
+
+  for top in (0..height).step_by(6) {
+    // The bits of each color in the band of six rows, column by column
+    let mut colors: Vec<(usize, Vec<u8>)> = Vec::new();
+    for y in top..(top + 6).min(height) {
+      for x in 0..width {
+        let pixel = image.get_pixel(x, y);
+        if (pixel[3] < 128) {
+          continue;
+        }
+
+        let color =
+          level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2]);
+        let i = match colors.iter().position(|x| x.0 == color) {
+          Some(i) => i,
+          None => {
+            colors.push((color, vec![0; width as usize]));
+            colors.len() - 1
+          }
+        };
+        colors[i].1[x as usize] |= 1 << (y - top);
+      }
+    }
+
+    for (color, bits) in colors {
+      let _ = write!(sixel, "#{color}");
+      for run in bits.chunk_by(|a, b| a == b) {
+        let c = (63 + run[0]) as char;
+        match run.len() {
+          1..=3 => sixel.extend(std::iter::repeat_n(c, run.len())),
+          n => {
+            let _ = write!(sixel, "!{n}{c}");
+          }
+        }
+      }
+      sixel.push('$');
+    }
+    sixel.push('-');
+  }
+
+  sixel.push_str("\x1b\\");
+  sixel
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::Rgba;
+
+  fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+      .join(format!("cli-text-reader-{}-{name}", std::process::id()))
+  }
+
+  fn write_png(name: &str, width: u32, height: u32) -> PathBuf {
+    let path = temp_path(name);
+    RgbaImage::new(width, height)
+      .save_with_format(&path, ImageFormat::Png)
+      .unwrap();
+    path
+  }
+
+  #[test]
+  fn test_protocol_setting() {
+    assert_eq!(protocol(Some("kitty")), Some(Protocol::Kitty));
+    assert_eq!(protocol(Some(" Sixel ")), Some(Protocol::Sixel));
+    assert_eq!(protocol(Some("none")), None);
+    assert_eq!(protocol(Some("iterm")), None);
+  }
+
+  #[test]
+  fn test_detect() {
+    let detect = |vars: &[(&str, &str)]| {
+      let vars: HashMap<String, String> =
+        vars.iter().map(|(x, y)| (x.to_string(), y.to_string())).collect();
+      detect_from(|name| vars.get(name).cloned())
+    };
+
+    assert_eq!(detect(&[("TERM", "xterm-kitty")]), Some(Protocol::Kitty));
+    assert_eq!(detect(&[("TERM_PROGRAM", "WezTerm")]), Some(Protocol::Kitty));
+    assert_eq!(detect(&[("TERM", "foot")]), Some(Protocol::Sixel));
+    assert_eq!(detect(&[("WT_SESSION", "")]), Some(Protocol::Sixel));
+    assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+    assert_eq!(detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")]), None);
+    assert_eq!(detect(&[("TERM", "screen-256color")]), None);
+  }
+
+  #[test]
+  fn test_encode_sixel() {
+    let mut image = RgbaImage::new(5, 2);
+    for x in 0..5 {
+      image.put_pixel(x, 0, Rgba([0, 0, 255, 255]));
+    }
+    // Red on both rows of the first column, transparent pixels are left out
+    image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+    image.put_pixel(0, 1, Rgba([255, 0, 0, 255]));
+
+    let sixel = encode_sixel(&image);
+
+    assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;5;2#0;2;0;0;0"));
+    assert!(sixel.contains("#215;2;100;100;100"));
+    assert!(sixel.ends_with("#180B!4?$#5?!4@$-\x1b\\"), "{sixel:?}");
+  }
+
+  #[test]
+  fn test_size_shrinks_images_to_fit() {
+    let path = write_png("size.png", 160, 320);
+    let mut images = Images::new(Protocol::Kitty);
+    let cell = (8.0, 16.0);
+
+    assert_eq!(images.size(&path, 80, 40, cell), Some((20, 20)));
+    assert_eq!(images.size(&path, 10, 40, cell), Some((10, 10)));
+    assert_eq!(images.size(&path, 80, 5, cell), Some((5, 5)));
+    assert_eq!(images.size(&path, 0, 5, cell), None);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_images_that_can_not_be_decoded_are_reported_once() {
+    let path = temp_path("figure.gif");
+    std::fs::write(&path, b"GIF89a\x01\x00\x01\x00").unwrap();
+    let mut images = Images::new(Protocol::Sixel);
+
+    assert_eq!(images.size(&path, 80, 40, (8.0, 16.0)), None);
+    let errors = images.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("figure.gif"), "{errors:?}");
+
+    assert_eq!(images.size(&path, 80, 40, (8.0, 16.0)), None);
+    assert!(images.take_errors().is_empty());
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_decoded_images_are_bounded() {
+    let paths: Vec<PathBuf> = (0..MAX_DECODED + 2)
+      .map(|i| write_png(&format!("decoded-{i}.png"), 1, 1))
+      .collect();
+    let mut images = Images::new(Protocol::Kitty);
+
+    for path in &paths {
+      images.decode(path).unwrap();
+    }
+    images.decode(&paths[2]).unwrap();
+
+    let decoded: Vec<&PathBuf> = images.decoded.iter().map(|x| &x.0).collect();
+    assert_eq!(decoded.len(), MAX_DECODED);
+    assert!(!decoded.contains(&&paths[0]));
+    assert_eq!(decoded.last(), Some(&&paths[2]));
+    for path in paths {
+      std::fs::remove_file(path).unwrap();
+    }
+  }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Text another viewer highlighted in the document, shown highlighted in
+/// the reader along with its note.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Highlight {
+  /// The highlighted text, matched word by word since the lines are
+  /// justified differently than in the document
+  pub text: String,
+  pub note: Option<String>,
+  /// Underlined instead of highlighted
+  pub underline: bool,
+}
+
+/// Finds the words of `text` in the lines from byte `from.1` of line
+/// `from.0` on, returns the byte ranges they cover line by line. The first
+/// and the last word may be cut off, a highlight does not have to start or
+/// end at a word boundary.
+pub(crate) fn find_words(
+  lines: &[String],
+  from: (usize, usize),
+  text: &str,
+) -> Option<Vec<(usize, Range<usize>)>> {
+  let wanted: Vec<&str> = text.split_whitespace().collect();
+  if wanted.is_empty() {
+    return None;
+  }
+
+  let words: Vec<(usize, Range<usize>)> = lines
+    .iter()
+    .enumerate()
+    .skip(from.0)
+    .flat_map(|(i, line)| words(line).map(move |x| (i, x)))
+    .filter(|(i, x)| *i > from.0 || x.start >= from.1)
+    .collect();
+
+  let matches = |start: usize| {
+    wanted.iter().enumerate().all(|(j, want)| {
+      let Some((line, range)) = words.get(start + j) else {
+        return false;
+      };
+      let word = &lines[*line][range.clone()];
+      let (first, last) = (j == 0, j == wanted.len() - 1);
+
+      match (first, last) {
+        (true, true) => word.contains(want),
+        (true, false) => word.ends_with(want),
+        (false, true) => word.starts_with(want),
+        (false, false) => word == *want,
+      }
+    })
+  };
+
+  let start = (0..words.len()).find(|&x| matches(x))?;
+
+  let mut spans: Vec<(usize, Range<usize>)> = Vec::new();
+  for (j, (line, range)) in
+    words[start..start + wanted.len()].iter().enumerate()
+  {
+    let word = &lines[*line][range.clone()];
+    let want = wanted[j];
+    let range = match (j == 0, j == wanted.len() - 1) {
+      (true, true) => {
+        let at = range.start + word.find(want).unwrap_or(0);
+        at..at + want.len()
+      }
+      (true, false) => range.end - want.len()..range.end,
+      (false, true) => range.start..range.start + want.len(),
+      (false, false) => range.clone(),
+    };
+
+    match spans.last_mut() {
+      Some((previous, span)) if previous == line => span.end = range.end,
+      _ => spans.push((*line, range)),
+    }
+  }
+
+  Some(spans)
+}
+
+/// The byte ranges of the whitespace separated words of the line.
+fn words(line: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+  line.split_whitespace().map(move |word| {
+    let start = word.as_ptr() as usize - line.as_ptr() as usize;
+    start..start + word.len()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|x| x.to_string()).collect()
+  }
+
+  #[test]
+  fn test_find_words_across_lines() {
+    let lines = lines(&["The quick brown", "fox jumps over", "the lazy dog"]);
+
+    assert_eq!(
+      find_words(&lines, (0, 0), "brown fox jumps over the"),
+      Some(vec![(0, 10..15), (1, 0..14), (2, 0..3)])
+    );
+    assert_eq!(find_words(&lines, (0, 0), "lazy"), Some(vec![(2, 4..8)]));
+  }
+
+  #[test]
+  fn test_find_words_cut_off_at_the_ends() {
+    let lines = lines(&["The quick brown", "fox jumps over"]);
+
+    assert_eq!(find_words(&lines, (0, 0), "ick"), Some(vec![(0, 6..9)]));
+    assert_eq!(
+      find_words(&lines, (0, 0), "own fox ju"),
+      Some(vec![(0, 12..15), (1, 0..6)])
+    );
+    // Only the first and the last word may be cut off
+    assert_eq!(find_words(&lines, (0, 0), "quick row fox"), None);
+  }
+
+  #[test]
+  fn test_find_words_from() {
+    let lines = lines(&["a word", "a word again"]);
+
+    assert_eq!(find_words(&lines, (0, 0), "word"), Some(vec![(0, 2..6)]));
+    assert_eq!(find_words(&lines, (0, 6), "word"), Some(vec![(1, 2..6)]));
+    assert_eq!(find_words(&lines, (1, 0), "word"), Some(vec![(1, 2..6)]));
+    assert_eq!(find_words(&lines, (1, 6), "word"), None);
+  }
+
+  #[test]
+  fn test_find_words_not_found() {
+    let lines = lines(&["The quick brown"]);
+
+    assert_eq!(find_words(&lines, (0, 0), "quick fox"), None);
+    assert_eq!(find_words(&lines, (0, 0), "brown fox"), None);
+    assert_eq!(find_words(&lines, (0, 0), " "), None);
+    assert_eq!(find_words(&[], (0, 0), "quick"), None);
+  }
+}
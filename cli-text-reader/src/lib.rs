@@ -1,14 +1,39 @@
+mod chapters;
 mod config;
 mod editor;
+mod figures;
+mod graphics;
+mod highlights;
+mod loading;
 mod progress;
 mod tutorial;
 
 use editor::Editor;
+use std::sync::mpsc;
 
+pub use chapters::Chapter;
+pub use figures::Figure;
+pub use highlights::Highlight;
+pub use loading::LineChunk;
+
+/// Shows the lines, aligned like `alignment` or the `ALIGNMENT` setting when
+/// it is `None`.
 pub fn run_cli_text_reader(
-  lines: Vec<String>,
+  lines: Vec<cli_justify::Line>,
   col: usize,
+  alignment: Option<cli_justify::Alignment>,
 ) -> Result<(), Box<dyn std::error::Error>> {
   let mut editor = Editor::new(lines, col);
-  editor.run()
+  editor.run(alignment)
+}
+
+/// Opens the reader right away and appends the lines as they arrive, the
+/// document is complete once every sender is dropped.
+pub fn run_cli_text_reader_streaming(
+  lines: mpsc::Receiver<LineChunk>,
+  col: usize,
+  alignment: Option<cli_justify::Alignment>,
+) -> Result<(), Box<dyn std::error::Error>> {
+  let mut editor = Editor::with_source(lines, col);
+  editor.run(alignment)
 }
@@ -0,0 +1,31 @@
+use crate::chapters::Chapter;
+use crate::figures::Figure;
+use crate::highlights::Highlight;
+
+/// Lines of a document that is still being converted, sent to the reader as
+/// they become available.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineChunk {
+  /// Appended after the lines received so far, they are aligned by the
+  /// reader
+  pub lines: Vec<cli_justify::Line>,
+  /// How much of the document has been converted, from 0.0 to 1.0
+  pub progress: f64,
+  /// The document is being recognized with OCR, which takes much longer
+  /// than converting a text layer
+  pub recognizing: bool,
+  /// Places a reference can lead to, by name, with the line they start at
+  /// counted from the start of the document. A reference `[n] name` in the
+  /// text is followed with `:l n`.
+  pub anchors: Vec<(String, usize)>,
+  /// The table of contents, with the line each chapter starts at counted
+  /// like the anchors. `:toc` lists them.
+  pub chapters: Vec<(Chapter, usize)>,
+  /// Highlights of the document, with the lines of the piece of the text
+  /// they are in. They are sent once every line of the piece has been.
+  pub highlights: Vec<(std::ops::Range<usize>, Highlight)>,
+  /// Images of the document, with the lines of the piece of the text their
+  /// placeholder is in, sent like the highlights. `]` and `[` jump between
+  /// them and `:o` opens one.
+  pub figures: Vec<(std::ops::Range<usize>, Figure)>,
+}
@@ -48,9 +48,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     handle.join().unwrap();
   }
 
-  let lines = cli_justify::justify(&lines_vec.join("\n"), col);
+  let settings = cli_justify::Settings::default();
+  let lines = cli_justify::layout(&lines_vec.join("\n"), col, &settings);
 
-  cli_text_reader::run_cli_text_reader(lines, col)?;
+  cli_text_reader::run_cli_text_reader(lines, col, None)?;
 
   return Ok(());
 }
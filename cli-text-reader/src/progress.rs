@@ -13,6 +13,10 @@ pub struct Progress {
   pub offset: usize,
   pub total_lines: usize,
   pub percentage: f64,
+  /// The byte offset in the source text of the top line, found again
+  /// whatever width the text is justified to. `None` when saved before it
+  /// was recorded.
+  pub source: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -23,6 +27,8 @@ enum Event {
     offset: usize,
     total_lines: usize,
     percentage: f64,
+    #[serde(default)]
+    source: Option<usize>,
   },
 }
 
@@ -45,6 +51,7 @@ pub fn save_progress(
   document_hash: u64,
   offset: usize,
   total_lines: usize,
+  source: Option<usize>,
 ) -> Result<(), Box<dyn std::error::Error>> {
   let percentage = (offset as f64 / total_lines as f64) * 100.0;
   let event = Event::UpdateProgress {
@@ -53,6 +60,7 @@ pub fn save_progress(
     offset,
     total_lines,
     percentage,
+    source,
   };
   let serialized = serde_json::to_string(&event)?;
   let progress_file_path = get_progress_file_path()?;
@@ -74,22 +82,23 @@ pub fn load_progress(
   for line in reader.lines() {
     let line = line?;
     let event: Event = serde_json::from_str(&line)?;
-    if let Event::UpdateProgress {
+    let Event::UpdateProgress {
       document_hash: hash,
       offset,
       total_lines,
       percentage,
+      source,
       ..
-    } = event
-    {
-      if hash == document_hash {
-        latest_progress = Some(Progress {
-          document_hash: hash,
-          offset,
-          total_lines,
-          percentage,
-        });
-      }
+    } = event;
+
+    if hash == document_hash {
+      latest_progress = Some(Progress {
+        document_hash: hash,
+        offset,
+        total_lines,
+        percentage,
+        source,
+      });
     }
   }
 
@@ -7,6 +7,7 @@ pub fn get_tutorial_text() -> Vec<String> {
     "k or ↑ = scroll up".to_string(),
     "PageDown = scroll down one page".to_string(),
     "PageUp = scroll up one page".to_string(),
+    "] or [ = next or previous figure".to_string(),
     "".to_string(),
     "Search:".to_string(),
     "/ = search forward".to_string(),
@@ -19,6 +20,12 @@ pub fn get_tutorial_text() -> Vec<String> {
     "q = quit".to_string(),
     "z = toggle line highlighter".to_string(),
     "p = toggle progress".to_string(),
+    "l n = follow reference [n] on the screen".to_string(),
+    "toc = list the chapters, Enter jumps to the selected one".to_string(),
+    "b = go back after following a reference or jumping to a chapter"
+      .to_string(),
+    "o = open the first figure on the screen in an image viewer".to_string(),
+    "align left, right, center or justified = align the lines".to_string(),
     "help or tutorial = show this tutorial".to_string(),
     "".to_string(),
     "Press any key to continue...".to_string(),
@@ -0,0 +1,472 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{Piece, TextStream};
+
+/// Converted documents are evicted, least recently opened first, once the
+/// text cache grows past this size.
+const MAX_TEXT_CACHE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// The recognized pages of documents are evicted, least recently opened
+/// first, once the OCR cache grows past this size.
+const MAX_OCR_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The hygg directory in the user cache directory.
+pub fn cache_dir() -> Option<PathBuf> {
+  dirs::cache_dir().map(|x| x.join("hygg"))
+}
+
+/// Where `cli_pdf_to_text` keeps recognized pages.
+pub fn ocr_dir() -> Option<PathBuf> {
+  cache_dir().map(|x| x.join("ocr"))
+}
+
+/// Where the images of the figures of the entry at `path` are saved, they
+/// are evicted along with it.
+pub fn image_dir(path: &Path) -> Option<PathBuf> {
+  let name = path.file_stem()?;
+  let dir = path.parent()?.parent()?;
+
+  Some(dir.join("images").join(name))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  /// The pages or chapters of the document, in reading order
+  pieces: Vec<Piece>,
+}
+
+/// The cache file in `dir` of `file` converted with `ocr`. The key is the
+/// hash of the content, so a moved or renamed document is still found,
+/// combined with the version of hygg, the format versions of its converters
+/// and the OCR settings, since those change the text.
+pub fn entry_path(
+  dir: &Path,
+  file: &str,
+  ocr: Option<&cli_pdf_to_text::Ocr>,
+) -> Option<PathBuf> {
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut std::fs::File::open(file).ok()?, &mut hasher).ok()?;
+
+  let ocr = match ocr {
+    Some(x) if x.all_pages => format!("ocr-all-{}", x.engine.cache_key()),
+    Some(x) => format!("ocr-{}", x.engine.cache_key()),
+    None => "no-ocr".to_string(),
+  };
+  let variant = format!(
+    "{}-pdf{}-epub{}-{ocr}",
+    env!("CARGO_PKG_VERSION"),
+    cli_pdf_to_text::FORMAT_VERSION,
+    cli_epub_to_text::FORMAT_VERSION,
+  );
+  let variant: String = variant
+    .chars()
+    .map(|x| if x.is_ascii_alphanumeric() || x == '-' { x } else { '_' })
+    .collect();
+  let name = format!("{:x}-{variant}.json", hasher.finalize());
+
+  Some(dir.join("text").join(name))
+}
+
+/// The cached text at `path`, which counts as a use for eviction.
+pub fn load(path: &Path) -> Option<TextStream> {
+  let entry: Entry = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+
+  if let Ok(file) = std::fs::File::options().write(true).open(path) {
+    let _ = file.set_modified(SystemTime::now());
+  }
+
+  Some(TextStream {
+    count: entry.pieces.len(),
+    pieces: Box::new(entry.pieces.into_iter()),
+    protected: false,
+  })
+}
+
+/// Passes the stream through and stores it at `path` once the last piece
+/// was converted, a document that is closed while loading or that has a
+/// piece that failed to convert is not stored. The images its figures were
+/// saved to in `images` are kept in the image directory of the entry when it
+/// is stored, so the images of a conversion that is not stored go away with
+/// `images`.
+pub fn record(
+  stream: TextStream,
+  path: PathBuf,
+  images: Option<&Path>,
+) -> TextStream {
+  let mut pieces = stream.pieces;
+  let mut recorded = Vec::new();
+  let mut path = Some(path);
+  let images = images.map(Path::to_path_buf);
+
+  let pieces = std::iter::from_fn(move || match pieces.next() {
+    Some(piece) => {
+      if piece.failed {
+        path = None;
+      }
+      recorded.push(piece.clone());
+      Some(piece)
+    }
+    None => {
+      if let Some(path) = path.take() {
+        let mut entry = Entry { pieces: std::mem::take(&mut recorded) };
+        if let (Some(from), Some(to)) = (&images, image_dir(&path)) {
+          keep_images(&mut entry, from, &to);
+        }
+        if store(&path, &entry).is_ok() {
+          if let Some(dir) = path.parent() {
+            let _ = evict(dir, MAX_TEXT_CACHE_SIZE, image_dir);
+          }
+        }
+      }
+      None
+    }
+  });
+
+  TextStream { pieces: Box::new(pieces), ..stream }
+}
+
+/// Links or copies the images of the entry's figures that are in `from`
+/// into `to` and refers to them there, the figures of an image that can not
+/// be kept show only their placeholder.
+fn keep_images(entry: &mut Entry, from: &Path, to: &Path) {
+  let figures = entry.pieces.iter_mut().flat_map(|x| &mut x.figures);
+
+  for figure in figures {
+    let Some(image) = figure.image.take() else { continue };
+    let Some(name) = image.file_name().filter(|_| image.starts_with(from))
+    else {
+      figure.image = Some(image);
+      continue;
+    };
+
+    let kept = to.join(name);
+    let result = std::fs::create_dir_all(to)
+      .and_then(|_| std::fs::hard_link(&image, &kept))
+      .or_else(|_| std::fs::copy(&image, &kept).map(|_| ()));
+    figure.image = result.ok().map(|_| kept);
+  }
+}
+
+/// Writes next to `path` and renames, so a hygg opening the same document
+/// meanwhile never reads a partial entry.
+fn store(path: &Path, entry: &Entry) -> std::io::Result<()> {
+  let dir = path.parent().unwrap_or(Path::new("."));
+  std::fs::create_dir_all(dir)?;
+
+  let mut file = tempfile::NamedTempFile::new_in(dir)?;
+  serde_json::to_writer(&mut file, entry)?;
+  file.persist(path).map_err(|e| e.error)?;
+
+  Ok(())
+}
+
+/// Removes the pages of the least recently opened documents from the OCR
+/// cache until it fits in its size.
+pub fn evict_ocr() -> std::io::Result<()> {
+  match ocr_dir() {
+    Some(dir) if dir.exists() => evict(&dir, MAX_OCR_CACHE_SIZE, |_| None),
+    _ => Ok(()),
+  }
+}
+
+/// Removes the least recently used entries of the cache `dir`, files or
+/// directories, until they fit in `max_size` bytes along with the
+/// directory `attached` to each, like the images of a text entry.
+fn evict(
+  dir: &Path,
+  max_size: u64,
+  attached: impl Fn(&Path) -> Option<PathBuf>,
+) -> std::io::Result<()> {
+  let mut entries = Vec::new();
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    let path = entry.path();
+    let len = size(&path) + attached(&path).map_or(0, |x| size(&x));
+    entries.push((entry.metadata()?.modified()?, len, path));
+  }
+  entries.sort();
+
+  let mut total: u64 = entries.iter().map(|x| x.1).sum();
+  for (_, len, path) in entries {
+    if (total <= max_size) {
+      break;
+    }
+    match path.is_dir() {
+      true => std::fs::remove_dir_all(&path)?,
+      false => std::fs::remove_file(&path)?,
+    }
+    if let Some(attached) = attached(&path).filter(|x| x.exists()) {
+      std::fs::remove_dir_all(attached)?;
+    }
+    total -= len;
+  }
+
+  Ok(())
+}
+
+/// The size of the file, or of the files in the directory and below it.
+fn size(path: &Path) -> u64 {
+  match std::fs::read_dir(path) {
+    Ok(entries) => entries.flatten().map(|x| size(&x.path())).sum(),
+    Err(_) => std::fs::metadata(path).map(|x| x.len()).unwrap_or(0),
+  }
+}
+
+/// The number of documents in a cache directory and their total size.
+fn usage(dir: &Path) -> (usize, u64) {
+  let count = std::fs::read_dir(dir).map(|x| x.count()).unwrap_or(0);
+
+  (count, size(dir))
+}
+
+/// Runs `hygg cache clear|stats` on the cache directory `dir`.
+pub fn run_command(
+  dir: &Path,
+  command: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+  match command {
+    "clear" => {
+      if dir.exists() {
+        std::fs::remove_dir_all(dir)?;
+      }
+      println!("cleared {}", dir.display());
+    }
+    "stats" => {
+      println!("{}", dir.display());
+      for name in ["text", "ocr", "images"] {
+        let (count, size) = usage(&dir.join(name));
+        let size = size as f64 / (1024.0 * 1024.0);
+        println!("{name:<6}{count:>6} documents{size:>10.1} MiB");
+      }
+    }
+    x => {
+      return Err(
+        format!("invalid cache command '{x}', expected clear or stats").into(),
+      )
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn piece(text: &str) -> Piece {
+    Piece::from(text.to_string())
+  }
+
+  fn stream(pieces: Vec<Piece>) -> TextStream {
+    TextStream {
+      count: pieces.len(),
+      pieces: Box::new(pieces.into_iter()),
+      protected: false,
+    }
+  }
+
+  fn texts(stream: TextStream) -> Vec<String> {
+    stream.pieces.map(|x| x.text).collect()
+  }
+
+  #[test]
+  fn test_entry_path_is_keyed_by_content_and_ocr() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, text: &str| {
+      let path = dir.path().join(name);
+      std::fs::write(&path, text).unwrap();
+      path.to_string_lossy().to_string()
+    };
+    let (a, moved, b) =
+      (write("a.txt", "text"), write("b.txt", "text"), write("c.txt", "other"));
+    let ocr = cli_pdf_to_text::Ocr::new(cli_pdf_to_text::FakeOcr::new(""));
+    let all = cli_pdf_to_text::Ocr { all_pages: true, ..ocr.clone() };
+
+    let path = entry_path(dir.path(), &a, None).unwrap();
+    assert_eq!(path.parent(), Some(dir.path().join("text").as_path()));
+    assert_eq!(entry_path(dir.path(), &moved, None), Some(path.clone()));
+    assert_ne!(entry_path(dir.path(), &b, None), Some(path.clone()));
+
+    let with_ocr = entry_path(dir.path(), &a, Some(&ocr)).unwrap();
+    assert_ne!(with_ocr, path);
+    assert_ne!(entry_path(dir.path(), &a, Some(&all)), Some(with_ocr));
+
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let version = format!("-pdf{}-", cli_pdf_to_text::FORMAT_VERSION);
+    assert!(name.contains(&version));
+    assert_eq!(entry_path(dir.path(), "missing", None), None);
+  }
+
+  #[test]
+  fn test_record_stores_complete_conversions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("text").join("entry.json");
+    let pieces = vec![piece("one"), piece("two")];
+
+    let recorded = record(stream(pieces), path.clone(), None);
+    assert!(!path.exists());
+    assert_eq!(texts(recorded), vec!["one", "two"]);
+
+    let loaded = load(&path).unwrap();
+    assert_eq!(loaded.count, 2);
+    assert_eq!(texts(loaded), vec!["one", "two"]);
+  }
+
+  #[test]
+  fn test_record_skips_failed_and_unfinished_conversions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("text").join("entry.json");
+
+    let failed = Piece { failed: true, ..piece("page 2") };
+    let pieces = vec![piece("page 1"), failed];
+    assert_eq!(texts(record(stream(pieces), path.clone(), None)).len(), 2);
+    assert!(!path.exists());
+
+    let pieces = vec![piece("one"), piece("two")];
+    let mut recorded = record(stream(pieces), path.clone(), None);
+    recorded.pieces.next();
+    drop(recorded);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn test_record_keeps_the_images_of_stored_conversions() {
+    let dir = tempfile::tempdir().unwrap();
+    let staged = tempfile::tempdir().unwrap();
+    let image = staged.path().join("page-1-1.png");
+    std::fs::write(&image, "png").unwrap();
+    let figure = |image: &Path| cli_text_reader::Figure {
+      label: "[Figure: 1×1]".to_string(),
+      image: Some(image.to_path_buf()),
+    };
+
+    let path = dir.path().join("text").join("failed.json");
+    let failed = Piece { failed: true, ..piece("page 2") };
+    let pieces = vec![Piece { figures: vec![figure(&image)], ..piece("1") }];
+    record(
+      stream([pieces, vec![failed]].concat()),
+      path.clone(),
+      Some(staged.path()),
+    )
+    .pieces
+    .for_each(drop);
+    assert!(!image_dir(&path).unwrap().exists());
+
+    let path = dir.path().join("text").join("entry.json");
+    let pieces = vec![Piece { figures: vec![figure(&image)], ..piece("1") }];
+    record(stream(pieces), path.clone(), Some(staged.path()))
+      .pieces
+      .for_each(drop);
+    drop(staged);
+
+    let kept = image_dir(&path).unwrap().join("page-1-1.png");
+    let loaded: Vec<Piece> = load(&path).unwrap().pieces.collect();
+    assert_eq!(loaded[0].figures[0].image, Some(kept.clone()));
+    assert_eq!(std::fs::read_to_string(kept).unwrap(), "png");
+  }
+
+  #[test]
+  fn test_evict_counts_the_images() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = dir.path().join("text");
+    std::fs::create_dir_all(&text).unwrap();
+
+    let now = SystemTime::now();
+    for (name, age, images) in
+      [("old", 30, 0), ("used", 20, 300), ("new", 10, 0)]
+    {
+      let path = text.join(format!("{name}.json"));
+      std::fs::write(&path, vec![b'x'; 100]).unwrap();
+      std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(now - std::time::Duration::from_secs(age))
+        .unwrap();
+
+      let dir = image_dir(&path).unwrap();
+      std::fs::create_dir_all(&dir).unwrap();
+      std::fs::write(dir.join("page-1-1.png"), vec![b'x'; images]).unwrap();
+    }
+
+    // The text alone fits, with the images the two oldest entries have to go
+    evict(&text, 450, image_dir).unwrap();
+
+    assert!(!text.join("old.json").exists());
+    assert!(!text.join("used.json").exists());
+    assert!(!dir.path().join("images").join("used").exists());
+    assert!(text.join("new.json").exists());
+  }
+
+  #[test]
+  fn test_evict_removes_least_recently_used_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = dir.path().join("text");
+    std::fs::create_dir_all(&text).unwrap();
+
+    let now = SystemTime::now();
+    for (name, age) in [("old", 30), ("used", 20), ("new", 10)] {
+      let path = text.join(format!("{name}.json"));
+      std::fs::write(&path, vec![b'x'; 100]).unwrap();
+      let modified = now - std::time::Duration::from_secs(age);
+      std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+      let images = image_dir(&path).unwrap();
+      std::fs::create_dir_all(&images).unwrap();
+    }
+
+    evict(&text, 200, image_dir).unwrap();
+
+    assert!(!text.join("old.json").exists());
+    assert!(!dir.path().join("images").join("old").exists());
+    assert!(text.join("used.json").exists());
+    assert!(dir.path().join("images").join("new").exists());
+  }
+
+  #[test]
+  fn test_evict_removes_the_pages_of_least_recently_opened_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let ocr = dir.path().join("ocr");
+
+    let now = SystemTime::now();
+    for (hash, age) in [("old", 30), ("used", 20), ("new", 10)] {
+      let pages = ocr.join(hash).join("tesseract-eng");
+      std::fs::create_dir_all(&pages).unwrap();
+      std::fs::write(pages.join("1.txt"), vec![b'x'; 100]).unwrap();
+      std::fs::File::open(ocr.join(hash))
+        .unwrap()
+        .set_modified(now - std::time::Duration::from_secs(age))
+        .unwrap();
+    }
+
+    evict(&ocr, 200, |_| None).unwrap();
+
+    assert!(!ocr.join("old").exists());
+    assert!(ocr.join("used").join("tesseract-eng").join("1.txt").exists());
+    assert!(ocr.join("new").exists());
+  }
+
+  #[test]
+  fn test_run_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("hygg");
+    std::fs::create_dir_all(cache.join("text")).unwrap();
+    std::fs::write(cache.join("text").join("entry.json"), "{}").unwrap();
+
+    assert_eq!(usage(&cache.join("text")), (1, 2));
+    run_command(&cache, "stats").unwrap();
+    assert!(cache.exists());
+
+    run_command(&cache, "clear").unwrap();
+    assert!(!cache.exists());
+    assert!(run_command(&cache, "purge").is_err());
+  }
+}
@@ -1,49 +1,574 @@
+mod cache;
+mod sandbox;
+
 use cli_justify;
 use cli_pdf_to_text;
 use cli_text_reader;
 use redirect_stderr;
 
-use std::{env, fmt::format};
+use std::path::{Path, PathBuf};
+use std::{env, io::IsTerminal, sync::mpsc};
 
 use getopts;
 
-pub fn which(binary: &str) -> Option<std::path::PathBuf> {
-  if let Ok(paths) = env::var("PATH") {
-    for path in env::split_paths(&paths) {
-      let full_path = path.join(binary);
-      if full_path.is_file() {
-        return Some(full_path);
+pub fn print_help_menu(args: Vec<String>, opts: getopts::Options) {
+  let brief =
+    format!("Usage: {0} FILE [options]\n       {0} cache clear|stats", args[0]);
+  print!("{}", opts.usage(&brief));
+}
+
+/// Opens the pdf, `PdfError::Encrypted` when it needs a password and none
+/// was given. The images of the figures are saved to `image_dir`.
+pub fn pdf_to_text_stream(
+  file: &str,
+  password: Option<&str>,
+  ocr: Option<cli_pdf_to_text::Ocr>,
+  image_dir: Option<PathBuf>,
+) -> Result<TextStream, cli_pdf_to_text::PdfError> {
+  let stream =
+    cli_pdf_to_text::pdf_to_page_stream_with_ocr(file, password, ocr)?;
+  let protected = password.is_some();
+
+  let pieces = stream.pages.into_iter().map(move |x| Piece {
+    highlights: page_highlights(&x),
+    figures: x
+      .figures
+      .iter()
+      .enumerate()
+      .map(|(i, figure)| {
+        let image = figure.image.as_ref().and_then(|image| {
+          let name = format!("page-{}-{}.{}", x.number, i + 1, image.extension);
+          save_image(image_dir.as_deref()?, &name, &image.data)
+        });
+
+        cli_text_reader::Figure { label: figure.placeholder(), image }
+      })
+      .collect(),
+    failed: x.failed,
+    recognized: x.recognized,
+    anchor: Some(cli_pdf_to_text::LinkTarget::Page(x.number).to_string()),
+    chapters: x
+      .outline
+      .iter()
+      .map(|entry| cli_text_reader::Chapter {
+        title: entry.title.clone(),
+        level: entry.level,
+      })
+      .collect(),
+    text: match x.failed {
+      true => {
+        let reason = x.warnings.last().map(|x| x.message.as_str());
+        format!("\nhygg: page {}: {}\n", x.number, reason.unwrap_or_default())
       }
+      false => x.text,
+    },
+  });
+
+  Ok(TextStream {
+    count: stream.page_count,
+    pieces: Box::new(pieces),
+    protected,
+  })
+}
+
+/// The highlights and comments of the page that could be placed on its
+/// text.
+fn page_highlights(
+  page: &cli_pdf_to_text::Page,
+) -> Vec<cli_text_reader::Highlight> {
+  page
+    .annotations
+    .iter()
+    .filter_map(|x| {
+      let text = page.text.get(x.range.clone()?)?;
+
+      Some(cli_text_reader::Highlight {
+        text: text.to_string(),
+        note: x.note.clone(),
+        underline: x.kind == cli_pdf_to_text::AnnotationKind::Underline,
+      })
+    })
+    .collect()
+}
+
+/// Writes the image of a figure to `dir`, `None` when it could not be
+/// written.
+fn save_image(dir: &Path, name: &str, data: &[u8]) -> Option<PathBuf> {
+  let path = dir.join(name);
+  std::fs::create_dir_all(dir).ok()?;
+  std::fs::write(&path, data).ok()?;
+
+  Some(path)
+}
+
+/// A page or chapter of a document.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Piece {
+  pub text: String,
+  /// The text was recognized with OCR
+  pub recognized: bool,
+  /// The name links to the start of the piece use, like `page 5`
+  #[serde(default)]
+  pub anchor: Option<String>,
+  /// The entries of the table of contents that start with the piece
+  #[serde(default)]
+  pub chapters: Vec<cli_text_reader::Chapter>,
+  /// What was highlighted in the piece with another viewer
+  #[serde(default)]
+  pub highlights: Vec<cli_text_reader::Highlight>,
+  /// The images of the piece, their placeholders are in the text
+  #[serde(default)]
+  pub figures: Vec<cli_text_reader::Figure>,
+  /// The piece could not be converted, or the conversion stopped here
+  /// before the end of the document, the text says why
+  #[serde(default)]
+  pub failed: bool,
+}
+
+impl From<String> for Piece {
+  fn from(text: String) -> Self {
+    Self {
+      text,
+      recognized: false,
+      anchor: None,
+      chapters: Vec::new(),
+      highlights: Vec::new(),
+      figures: Vec::new(),
+      failed: false,
     }
   }
-  return None;
 }
 
-pub fn print_help_menu(args: Vec<String>, opts: getopts::Options) {
-  let brief = format!("Usage: {} FILE [options]", args[0]);
-  print!("{}", opts.usage(&brief));
+/// The text of a document in reading order, piece by piece as the pages or
+/// chapters are converted in the background.
+pub struct TextStream {
+  /// The expected number of pieces, for the loading indicator
+  pub count: usize,
+  pub pieces: Box<dyn Iterator<Item = Piece> + Send>,
+  /// The document was opened with a password, its text is not cached so
+  /// it can not be read without the password
+  pub protected: bool,
+}
+
+/// Opens the file with the first converter that recognizes its format, files
+/// that are neither EPUB nor PDF are read as plain UTF-8 text. EPUBs whose
+/// files unpack to more than `max_size` bytes are rejected. The images of
+/// the figures are saved to `image_dir`.
+pub fn file_to_text_stream(
+  file: &str,
+  password: Option<&str>,
+  ocr: Option<cli_pdf_to_text::Ocr>,
+  max_size: u64,
+  image_dir: Option<PathBuf>,
+) -> Result<TextStream, Box<dyn std::error::Error>> {
+  std::fs::metadata(file).map_err(|e| format!("{file}: {e}"))?;
+
+  match cli_epub_to_text::epub_to_chapter_stream_with_limit(file, max_size) {
+    Err(cli_epub_to_text::EpubError::NotEpub) => {}
+    result => {
+      let stream = result?;
+
+      // The chapters are separated by a newline like `epub_to_text` joins
+      // them, chapters that fail to convert say why instead
+      let dir = image_dir.clone();
+      let pieces =
+        stream.chapters.into_iter().enumerate().map(move |(i, chapter)| {
+          let chapter = match chapter {
+            Ok(chapter) => chapter,
+            Err(e) => {
+              let text = format!("\nhygg: {e}\n");
+              return Piece { failed: true, ..Piece::from(text) };
+            }
+          };
+          let figures = chapter
+            .figures
+            .iter()
+            .enumerate()
+            .map(|(j, figure)| {
+              let image =
+                figure.image.as_ref().and_then(|(data, extension)| {
+                  let name = format!("chapter-{}-{}.{extension}", i + 1, j + 1);
+                  save_image(dir.as_deref()?, &name, data)
+                });
+
+              cli_text_reader::Figure { label: figure.placeholder(), image }
+            })
+            .collect();
+          let text = match i {
+            0 => chapter.text,
+            _ => format!("\n{}", chapter.text),
+          };
+
+          Piece { figures, ..Piece::from(text) }
+        });
+
+      return Ok(TextStream {
+        count: stream.chapter_count,
+        pieces: Box::new(pieces),
+        protected: false,
+      });
+    }
+  }
+
+  match pdf_to_text_stream(file, password, ocr, image_dir) {
+    Err(cli_pdf_to_text::PdfError::NotPdf) => {}
+    result => return Ok(result?),
+  }
+
+  match String::from_utf8(std::fs::read(file)?) {
+    Ok(text) => {
+      let pieces = Some(Piece::from(text)).into_iter();
+      Ok(TextStream { count: 1, pieces: Box::new(pieces), protected: false })
+    }
+    Err(_) => Err(
+      format!("{file}: unsupported format, expected EPUB, PDF or plain text")
+        .into(),
+    ),
+  }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Cleans up the unicode of every piece as it arrives.
+pub fn normalize_stream(
+  stream: TextStream,
+  normalization: normalize_text::Normalization,
+) -> TextStream {
+  let pieces = stream.pieces.map(move |x| Piece {
+    text: normalize_text::normalize(&x.text, &normalization),
+    highlights: x
+      .highlights
+      .into_iter()
+      .map(|highlight| cli_text_reader::Highlight {
+        text: normalize_text::normalize(&highlight.text, &normalization),
+        ..highlight
+      })
+      .collect(),
+    figures: x
+      .figures
+      .into_iter()
+      .map(|figure| cli_text_reader::Figure {
+        label: normalize_text::normalize(&figure.label, &normalization),
+        ..figure
+      })
+      .collect(),
+    chapters: x
+      .chapters
+      .into_iter()
+      .map(|chapter| cli_text_reader::Chapter {
+        title: normalize_text::normalize(&chapter.title, &normalization),
+        ..chapter
+      })
+      .collect(),
+    ..x
+  });
+
+  TextStream { pieces: Box::new(pieces), ..stream }
+}
+
+/// Justifies the pieces on a background thread and sends the lines to the
+/// reader as soon as their paragraphs are complete.
+pub fn justify_stream(
+  stream: TextStream,
+  col: usize,
+  settings: cli_justify::Settings,
+) -> mpsc::Receiver<cli_text_reader::LineChunk> {
+  let (sender, receiver) = mpsc::channel();
+
+  std::thread::spawn(move || {
+    let mut justifier = cli_justify::Justifier::with_settings(col, settings);
+    let count = stream.count.max(1) as f64;
+
+    // The source text of each line sent so far
+    let mut sent: Vec<std::ops::Range<usize>> = Vec::new();
+    // Where the next piece starts in the text pushed so far
+    let mut offset = 0;
+    // What the pieces lead to, with the source offset of their piece, until
+    // the line the piece starts on is sent
+    let mut anchors: Vec<(usize, String)> = Vec::new();
+    let mut chapters: Vec<(usize, cli_text_reader::Chapter)> = Vec::new();
+    // The highlights and figures with the source text of their piece, until
+    // every line of the piece is sent
+    let mut highlights: Vec<(
+      std::ops::Range<usize>,
+      cli_text_reader::Highlight,
+    )> = Vec::new();
+    let mut figures: Vec<(std::ops::Range<usize>, cli_text_reader::Figure)> =
+      Vec::new();
+
+    for (i, piece) in stream.pieces.enumerate() {
+      let source = offset..offset + piece.text.len();
+      anchors.extend(piece.anchor.into_iter().map(|x| (offset, x)));
+      chapters.extend(piece.chapters.into_iter().map(|x| (offset, x)));
+      highlights
+        .extend(piece.highlights.into_iter().map(|x| (source.clone(), x)));
+      figures.extend(piece.figures.into_iter().map(|x| (source.clone(), x)));
+      offset = source.end;
+
+      let lines = justifier.push_lines(&piece.text);
+      extend_sent(&mut sent, &lines);
+      let place = |source| first_line(&sent, source);
+      let lines_of = |source| piece_lines(&sent, source);
+      let chunk = cli_text_reader::LineChunk {
+        progress: ((i + 1) as f64 / count).min(1.0),
+        recognizing: piece.recognized,
+        anchors: take_placed(&mut anchors, place)
+          .into_iter()
+          .map(|(line, name)| (name, line))
+          .collect(),
+        chapters: take_placed(&mut chapters, place)
+          .into_iter()
+          .map(|(line, chapter)| (chapter, line))
+          .collect(),
+        highlights: take_placed(&mut highlights, lines_of),
+        figures: take_placed(&mut figures, lines_of),
+        lines,
+      };
+
+      if sender.send(chunk).is_err() {
+        return;
+      }
+    }
+
+    // Pieces without text after them lead to the end
+    let lines = justifier.finish_lines();
+    extend_sent(&mut sent, &lines);
+    let end = sent.len().saturating_sub(1);
+    let place = |source| first_line(&sent, source).or(Some(end));
+    let lines_of = |source: std::ops::Range<usize>| {
+      Some(first_line(&sent, source.start).unwrap_or(sent.len())..sent.len())
+    };
+    let _ = sender.send(cli_text_reader::LineChunk {
+      progress: 1.0,
+      recognizing: false,
+      anchors: take_placed(&mut anchors, place)
+        .into_iter()
+        .map(|(line, name)| (name, line))
+        .collect(),
+      chapters: take_placed(&mut chapters, place)
+        .into_iter()
+        .map(|(line, chapter)| (chapter, line))
+        .collect(),
+      highlights: take_placed(&mut highlights, lines_of),
+      figures: take_placed(&mut figures, lines_of),
+      lines,
+    });
+  });
+
+  receiver
+}
+
+/// Appends the source text of each of `lines` to `sent`, the blank lines
+/// between paragraphs have none where the line before them ends.
+fn extend_sent(
+  sent: &mut Vec<std::ops::Range<usize>>,
+  lines: &[cli_justify::Line],
+) {
+  for line in lines {
+    let previous = sent.last().map_or(0, |x| x.end);
+    let source = match (line.spans.first(), line.spans.last()) {
+      (Some(&(_, start)), Some(&(at, last))) => {
+        start..last + line.text.len() - at
+      }
+      _ => previous..previous,
+    };
+    sent.push(source);
+  }
+}
+
+/// The first line sent with text at or after byte `source` of the text, a
+/// piece starting there starts on it.
+fn first_line(sent: &[std::ops::Range<usize>], source: usize) -> Option<usize> {
+  let line = sent.partition_point(|x| x.end <= source);
+  return (line < sent.len()).then_some(line);
+}
+
+/// The lines of the piece with the source text `source`, once a line after
+/// it is sent and it can not continue on later lines.
+fn piece_lines(
+  sent: &[std::ops::Range<usize>],
+  source: std::ops::Range<usize>,
+) -> Option<std::ops::Range<usize>> {
+  if (sent.last().is_none_or(|x| x.start < source.end)) {
+    return None;
+  }
+
+  let start = sent.partition_point(|x| x.end <= source.start);
+  let end = sent.partition_point(|x| x.start < source.end);
+  Some(start..end.max(start))
+}
+
+/// Takes the items `place` finds a place for from where they come from in
+/// the source text, with the place, the others are left waiting for later
+/// lines.
+fn take_placed<S: Clone, P, T>(
+  pending: &mut Vec<(S, T)>,
+  place: impl Fn(S) -> Option<P>,
+) -> Vec<(P, T)> {
+  let mut placed = Vec::new();
+
+  for (source, item) in std::mem::take(pending) {
+    match place(source.clone()) {
+      Some(line) => placed.push((line, item)),
+      None => pending.push((source, item)),
+    }
+  }
+
+  placed
+}
+
+/// The OCR engine for the `--ocr` and `--ocr-lang` options.
+pub fn ocr_settings(
+  ocr: Option<&str>,
+  ocr_lang: Option<&str>,
+) -> Result<Option<cli_pdf_to_text::Ocr>, Box<dyn std::error::Error>> {
+  let detected = cli_pdf_to_text::Ocr::detect(ocr_lang)
+    .map(|x| cli_pdf_to_text::Ocr { cache_dir: cache::ocr_dir(), ..x });
+
+  match ocr {
+    None | Some("auto") => Ok(detected),
+    Some("true") => Ok(Some(cli_pdf_to_text::Ocr {
+      all_pages: true,
+      ..detected.ok_or("--ocr needs ocrmypdf or tesseract to be installed")?
+    })),
+    Some("false") => Ok(None),
+    Some(x) => {
+      Err(format!("invalid --ocr '{x}', expected auto, true or false").into())
+    }
+  }
+}
+
+/// The value of a limit option, `None` when it was not given.
+fn limit_option(
+  matches: &getopts::Matches,
+  name: &str,
+) -> Result<Option<u64>, String> {
+  let Some(value) = matches.opt_str(name) else {
+    return Ok(None);
+  };
+
+  match value.parse() {
+    Ok(x) if x > 0 => Ok(Some(x)),
+    _ => Err(format!("invalid --{name} '{value}', expected a positive number")),
+  }
+}
+
+fn main() {
+  if (env::args().nth(1).as_deref() == Some(sandbox::WORKER_ARG)) {
+    // stderr is read by hygg to tell why a conversion failed
+    if let Err(e) = sandbox::run_worker() {
+      eprintln!("hygg: {e}");
+      std::process::exit(1);
+    }
+    return;
+  }
+
   redirect_stderr::redirect_stderr().expect("Failed to redirect stderr");
 
-  let args: Vec<String> = env::args().collect();
+  if let Err(e) = run() {
+    // stderr is silenced while reading, the error would be lost otherwise
+    let _ = redirect_stderr::restore_stderr();
+    eprintln!("hygg: {e}");
+    std::process::exit(1);
+  }
+}
+
+/// The document to read, options may come after it and take values.
+fn file_argument(free: &[String]) -> Result<&str, String> {
+  match free {
+    [file] => Ok(file),
+    [] => Err("no file given, see --help".to_string()),
+    [_, extra, ..] => Err(format!("unexpected argument '{extra}'")),
+  }
+}
+
+fn options() -> getopts::Options {
   let mut opts = getopts::Options::new();
 
   opts.optflag("h", "help", "print this help menu");
 
   opts.optopt("c", "col", "set the column, defaults to 110", "NUMBER");
+  opts.optopt(
+    "",
+    "align",
+    "justified, left, right or center, defaults to the ALIGNMENT setting",
+    "ALIGNMENT",
+  );
+  opts.optopt(
+    "",
+    "algorithm",
+    "greedy or optimal, which spaces whole paragraphs evenly, defaults to greedy",
+    "NAME",
+  );
+  opts.optopt(
+    "",
+    "hyphenate",
+    "break words between syllables with the patterns of a language, e.g. en",
+    "LANG",
+  );
   opts.optopt(
     "o",
     "ocr",
     "
-    use ocr to extract text from scanned pdf documents,
-    depends on ocrmypdf and a tesseract-ocr lang e.g.
-    sudo apt install ocrmypdf tesseract-ocr-eng
+    auto, the default, recognizes the pages of pdf documents
+    without a text layer with ocr, true recognizes every page
+    and false none, depends on ocrmypdf or tesseract and a
+    tesseract-ocr lang e.g. sudo apt install ocrmypdf
+    tesseract-ocr-eng
+  ",
+    "auto|true|false",
+  );
+  opts.optopt(
+    "",
+    "ocr-lang",
+    "tesseract language of scanned pages, e.g. deu+eng, defaults to eng",
+    "LANG",
+  );
+  opts.optopt(
+    "",
+    "normalize",
+    "
+    expands ligatures like \u{FB01} and removes invisible characters like soft
+    hyphens by default, ascii also replaces smart quotes and dashes and
+    false keeps the text as extracted
   ",
     "BOOLEAN",
   );
+  opts.optopt(
+    "p",
+    "password",
+    "password for encrypted pdf documents",
+    "PASSWORD",
+  );
+  opts.optopt(
+    "",
+    "password-file",
+    "read the password for encrypted pdf documents from the first line of FILE",
+    "FILE",
+  );
+  opts.optopt(
+    "",
+    "time-limit",
+    "stop converting when the document takes longer, defaults to 600",
+    "SECONDS",
+  );
+  opts.optopt(
+    "",
+    "memory-limit",
+    "memory converting a document may use, defaults to 4096",
+    "MIB",
+  );
+  opts.optopt(
+    "",
+    "size-limit",
+    "size the files of an epub may unpack to, defaults to 1024",
+    "MIB",
+  );
 
+  opts
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+  let args: Vec<String> = env::args().collect();
+  let opts = options();
   let matches = opts.parse(&args[1..])?;
 
   if (matches.opt_present("h") || args.len() < 2) {
@@ -51,47 +576,218 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     return Ok(());
   }
 
+  if let [command, subcommand] = matches.free.as_slice() {
+    if (command == "cache") {
+      let dir =
+        cache::cache_dir().ok_or("unable to find the cache directory")?;
+      return cache::run_command(&dir, subcommand);
+    }
+  }
+
   let col: usize = match matches.opt_str("c") {
-    Some(x) => x.parse().unwrap_or(110),
+    Some(x) => cli_justify::parse_width(&x)?,
     None => 110,
   };
 
-  let ocr: bool = match matches.opt_str("ocr") {
-    Some(x) => x.parse().unwrap_or(false),
-    None => false,
+  let hyphenation = match matches.opt_str("hyphenate") {
+    Some(x) => Some(cli_justify::Hyphenation::new(&x)?),
+    None => None,
+  };
+  let algorithm = match matches.opt_str("algorithm") {
+    Some(x) => cli_justify::parse_algorithm(&x)?,
+    None => cli_justify::Algorithm::Greedy,
   };
+  let alignment = match matches.opt_str("align") {
+    Some(x) => Some(cli_justify::parse_alignment(&x)?),
+    None => None,
+  };
+  let settings =
+    cli_justify::Settings { hyphenation, algorithm, ..Default::default() };
 
-  let file = std::env::args().last().unwrap();
-  let temp_file = format!("{file}-{}", uuid::Uuid::new_v4());
+  let ocr_option = matches.opt_str("ocr");
+  let ocr_lang = matches.opt_str("ocr-lang");
+  let ocr = ocr_settings(ocr_option.as_deref(), ocr_lang.as_deref())?;
 
-  let content = if (ocr && which("ocrmypdf").is_some()) {
-    let output = std::process::Command::new("ocrmypdf")
-      .arg("--force-ocr")
-      .arg(&file)
-      .arg(&temp_file)
-      .output()
-      .map_err(|e| e.to_string())?;
+  let defaults = sandbox::Limits::default();
+  let limits = sandbox::Limits {
+    time: limit_option(&matches, "time-limit")?.unwrap_or(defaults.time),
+    memory: limit_option(&matches, "memory-limit")?
+      .map_or(defaults.memory, |x| x.saturating_mul(sandbox::MIB)),
+    size: limit_option(&matches, "size-limit")?
+      .map_or(defaults.size, |x| x.saturating_mul(sandbox::MIB)),
+  };
 
-    #[allow(unused_variables)]
-    let result = (String::from_utf8_lossy(&output.stdout)
-      + String::from_utf8_lossy(&output.stderr))
-    .to_string();
+  let normalization = match matches.opt_str("normalize").as_deref() {
+    None => normalize_text::Normalization::default(),
+    Some(x) => normalize_text::Normalization::parse(x).ok_or_else(|| {
+      format!("invalid --normalize '{x}', expected true, false or ascii")
+    })?,
+  };
 
-    // println!("{result}");
+  let password = match matches.opt_str("password-file") {
+    Some(x) => Some(cli_pdf_to_text::read_password_file(&x)?),
+    None => matches.opt_str("password"),
+  };
 
-    cli_pdf_to_text::pdf_to_text(&temp_file)?
-  } else {
-    cli_epub_to_text::epub_to_text(&file)
-      .or(cli_pdf_to_text::pdf_to_text(&file))?
+  let file = file_argument(&matches.free)?.to_string();
+  let cache_path = cache::cache_dir()
+    .and_then(|dir| cache::entry_path(&dir, &file, ocr.as_ref()));
+  // The images of the figures are shown from here until the reader is
+  // closed, those of a document that is cached are kept with its text
+  let images = tempfile::Builder::new().prefix("hygg-images-").tempdir().ok();
+  let stream = match cache_path.as_deref().and_then(cache::load) {
+    Some(stream) => stream,
+    None => {
+      let stream = sandbox::convert(
+        &file,
+        password.as_deref(),
+        ocr_option.as_deref(),
+        ocr_lang.as_deref(),
+        images.as_ref().map(|x| x.path().to_path_buf()),
+        limits,
+      )?;
+      match cache_path {
+        Some(path) if !stream.protected => {
+          cache::record(stream, path, images.as_ref().map(|x| x.path()))
+        }
+        _ => stream,
+      }
+    }
   };
 
-  let lines = cli_justify::justify(&content, col);
+  cli_text_reader::run_cli_text_reader_streaming(
+    justify_stream(normalize_stream(stream, normalization), col, settings),
+    col,
+    alignment,
+  )?;
 
-  cli_text_reader::run_cli_text_reader(lines, col)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_file_argument_is_not_an_option_value() {
+    let matches = options()
+      .parse(["book.pdf", "--password", "secret", "-c", "80"])
+      .unwrap();
+    assert_eq!(file_argument(&matches.free), Ok("book.pdf"));
 
-  if std::path::Path::new(&temp_file).exists() {
-    std::fs::remove_file(&temp_file)?;
+    let matches = options().parse(["--col", "80"]).unwrap();
+    assert!(file_argument(&matches.free).is_err());
+    let matches = options().parse(["a.pdf", "b.pdf"]).unwrap();
+    assert_eq!(
+      file_argument(&matches.free),
+      Err("unexpected argument 'b.pdf'".to_string())
+    );
   }
 
-  Ok(())
+  #[test]
+  fn test_anchors_lead_to_the_line_their_piece_starts_on() {
+    let texts = ["Intro\n\nfirst page", " ends here", "\n\nSecond page"];
+    let pieces: Vec<Piece> = texts
+      .iter()
+      .enumerate()
+      .map(|(i, text)| Piece {
+        anchor: Some(format!("page {}", i + 1)),
+        ..Piece::from(text.to_string())
+      })
+      .collect();
+    let stream = TextStream {
+      count: 3,
+      pieces: Box::new(pieces.into_iter()),
+      protected: false,
+    };
+
+    let chunks: Vec<cli_text_reader::LineChunk> =
+      justify_stream(stream, 40, Default::default()).into_iter().collect();
+    let lines: Vec<String> =
+      chunks.iter().flat_map(|x| &x.lines).map(|x| x.text.clone()).collect();
+    let anchors: Vec<(String, usize)> =
+      chunks.into_iter().flat_map(|x| x.anchors).collect();
+
+    assert_eq!(
+      lines,
+      vec!["Intro", "", "first page ends here", "", "Second page", ""]
+    );
+    assert_eq!(
+      anchors,
+      vec![
+        ("page 1".to_string(), 0),
+        ("page 2".to_string(), 2),
+        ("page 3".to_string(), 4),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_chapters_start_on_the_line_their_piece_starts_on() {
+    let chapter = |title: &str, level| cli_text_reader::Chapter {
+      title: title.to_string(),
+      level,
+    };
+    let pieces = vec![
+      Piece {
+        chapters: vec![chapter("Intro", 0)],
+        ..Piece::from("Intro\n\nfirst page".to_string())
+      },
+      Piece::from(" ends here".to_string()),
+      Piece {
+        chapters: vec![chapter("Second", 0), chapter("Section", 1)],
+        ..Piece::from("\n\nSecond page".to_string())
+      },
+    ];
+    let stream = TextStream {
+      count: 3,
+      pieces: Box::new(pieces.into_iter()),
+      protected: false,
+    };
+
+    let chapters: Vec<(cli_text_reader::Chapter, usize)> =
+      justify_stream(stream, 40, Default::default())
+        .into_iter()
+        .flat_map(|x| x.chapters)
+        .collect();
+
+    assert_eq!(
+      chapters,
+      vec![
+        (chapter("Intro", 0), 0),
+        (chapter("Second", 0), 4),
+        (chapter("Section", 1), 4),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_highlights_come_with_the_lines_of_their_piece() {
+    let texts = ["Intro\n\nfirst page", " ends here", "\n\nSecond page"];
+    let pieces: Vec<Piece> = texts
+      .iter()
+      .map(|text| Piece {
+        highlights: vec![cli_text_reader::Highlight {
+          text: text.trim().to_string(),
+          ..Default::default()
+        }],
+        ..Piece::from(text.to_string())
+      })
+      .collect();
+    let stream = TextStream {
+      count: 3,
+      pieces: Box::new(pieces.into_iter()),
+      protected: false,
+    };
+
+    let chunks: Vec<cli_text_reader::LineChunk> =
+      justify_stream(stream, 40, Default::default()).into_iter().collect();
+    let highlights: Vec<std::ops::Range<usize>> = chunks
+      .into_iter()
+      .flat_map(|x| x.highlights)
+      .map(|(lines, _)| lines)
+      .collect();
+
+    assert_eq!(highlights, vec![0..3, 2..3, 4..6]);
+  }
 }
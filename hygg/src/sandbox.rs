@@ -0,0 +1,466 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::{Piece, TextStream};
+
+/// hygg runs itself with this argument to convert a document in a child
+/// process.
+pub const WORKER_ARG: &str = "--convert-worker";
+
+pub const MIB: u64 = 1024 * 1024;
+
+/// Only the end of the worker's stderr is kept, it says why it failed.
+const MAX_STDERR_LEN: usize = 4096;
+
+/// What converting a document may take, a document that needs more is
+/// malformed or malicious, like a zip bomb.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Limits {
+  /// Seconds the whole conversion may take
+  pub time: u64,
+  /// Bytes of memory the worker may allocate, only enforced on unix
+  pub memory: u64,
+  /// Bytes the files of an EPUB may unpack to
+  pub size: u64,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Self { time: 600, memory: 4096 * MIB, size: 1024 * MIB }
+  }
+}
+
+/// The document the worker converts, sent on its stdin so the password does
+/// not show up in the process list.
+#[derive(Serialize, Deserialize)]
+struct Request {
+  file: String,
+  password: Option<String>,
+  /// The `--ocr` and `--ocr-lang` options
+  ocr: Option<String>,
+  ocr_lang: Option<String>,
+  /// Where the images of the figures are saved, unless the document needs
+  /// a password
+  image_dir: Option<PathBuf>,
+  limits: Limits,
+}
+
+/// What the worker writes to its stdout, one message per line.
+#[derive(Serialize, Deserialize)]
+enum Message {
+  Opened {
+    count: usize,
+    protected: bool,
+  },
+  Piece(Piece),
+  /// The pdf is encrypted and no password was given
+  Encrypted,
+  Failed(String),
+}
+
+/// Converts the document in a child process, so a document that hangs the
+/// converters, exhausts the memory or crashes them stops the conversion and
+/// not hygg. Asks for the password on the terminal when the pdf is encrypted
+/// and no password was given.
+pub fn convert(
+  file: &str,
+  password: Option<&str>,
+  ocr: Option<&str>,
+  ocr_lang: Option<&str>,
+  image_dir: Option<PathBuf>,
+  limits: Limits,
+) -> Result<TextStream, Box<dyn std::error::Error>> {
+  let mut request = Request {
+    file: file.to_string(),
+    password: password.map(str::to_string),
+    ocr: ocr.map(str::to_string),
+    ocr_lang: ocr_lang.map(str::to_string),
+    image_dir,
+    limits,
+  };
+
+  let mut worker = Worker::spawn(worker_command()?, &request)?;
+  let mut first = worker.next_message();
+
+  if (matches!(first, Ok(Some(Message::Encrypted)))
+    && password.is_none()
+    && std::io::stdin().is_terminal())
+  {
+    let password =
+      rpassword::prompt_password(format!("Password for {file}: "))?;
+    request.password = Some(password);
+
+    worker = Worker::spawn(worker_command()?, &request)?;
+    first = worker.next_message();
+  }
+
+  match first.map_err(|e| format!("{file}: {e}"))? {
+    Some(Message::Opened { count, protected }) => {
+      Ok(TextStream { count, pieces: Box::new(worker.pieces()), protected })
+    }
+    Some(Message::Encrypted) => {
+      Err(cli_pdf_to_text::PdfError::Encrypted.into())
+    }
+    Some(Message::Failed(reason)) => Err(reason.into()),
+    Some(Message::Piece(_)) | None => {
+      Err(format!("{file}: the conversion stopped before it started").into())
+    }
+  }
+}
+
+/// hygg itself, run as a worker.
+fn worker_command() -> std::io::Result<Command> {
+  let mut command = Command::new(std::env::current_exe()?);
+  command.arg(WORKER_ARG);
+
+  Ok(command)
+}
+
+/// The child process converting a document.
+struct Worker {
+  child: Child,
+  messages: mpsc::Receiver<Message>,
+  stderr: Option<JoinHandle<String>>,
+  limits: Limits,
+  /// When the conversion is stopped, `None` when the limit is too far off
+  /// to be told apart from no limit
+  deadline: Option<Instant>,
+}
+
+impl Worker {
+  fn spawn(
+    mut command: Command,
+    request: &Request,
+  ) -> Result<Self, Box<dyn std::error::Error>> {
+    let mut child = command
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()?;
+
+    // A worker that exits before reading the request is explained by its
+    // exit status
+    if let Some(mut stdin) = child.stdin.take() {
+      let _ = serde_json::to_writer(&mut stdin, request);
+    }
+
+    let (sender, messages) = mpsc::channel();
+    let stdout = child.stdout.take().ok_or("the worker has no stdout")?;
+    std::thread::spawn(move || {
+      // Lines that are not messages were printed by the converters
+      for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { return };
+        let Ok(message) = serde_json::from_str(&line) else { continue };
+
+        if sender.send(message).is_err() {
+          return;
+        }
+      }
+    });
+
+    let mut stderr = child.stderr.take().ok_or("the worker has no stderr")?;
+    let stderr = std::thread::spawn(move || {
+      let mut tail: Vec<u8> = Vec::new();
+      let mut buffer = [0; 4096];
+
+      while let Ok(n @ 1..) = stderr.read(&mut buffer) {
+        tail.extend_from_slice(&buffer[..n]);
+        tail.drain(..tail.len().saturating_sub(MAX_STDERR_LEN));
+      }
+
+      String::from_utf8_lossy(&tail).to_string()
+    });
+
+    let deadline =
+      Instant::now().checked_add(Duration::from_secs(request.limits.time));
+
+    Ok(Self {
+      child,
+      messages,
+      stderr: Some(stderr),
+      limits: request.limits,
+      deadline,
+    })
+  }
+
+  /// The next message, `None` when the worker is done, or why it stopped
+  /// early. The time limit counts from when the worker started, a worker
+  /// that keeps sending pages is stopped all the same.
+  fn next_message(&mut self) -> Result<Option<Message>, String> {
+    let message = match self.deadline {
+      Some(deadline) => self
+        .messages
+        .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+      None => self.messages.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+
+    match message {
+      Ok(message) => Ok(Some(message)),
+      Err(RecvTimeoutError::Timeout) => {
+        let _ = self.child.kill();
+        Err(format!(
+          "the conversion took longer than {} seconds, see --time-limit",
+          self.limits.time
+        ))
+      }
+      Err(RecvTimeoutError::Disconnected) => match self.exit_reason() {
+        Some(reason) => Err(reason),
+        None => Ok(None),
+      },
+    }
+  }
+
+  /// Why the worker exited, `None` when it finished the document.
+  fn exit_reason(&mut self) -> Option<String> {
+    let status = self.child.wait().ok()?;
+    let stderr = match self.stderr.take() {
+      Some(x) => x.join().unwrap_or_default(),
+      None => String::new(),
+    };
+
+    // A worker that finished may still have printed what it recovered from
+    if status.success() {
+      return None;
+    }
+
+    if stderr.contains("memory allocation of") {
+      return Some(format!(
+        "the conversion needed more than {} MiB of memory, see --memory-limit",
+        self.limits.memory / MIB
+      ));
+    }
+
+    // A panic prints the location on one line and the message on the next
+    let mut lines = stderr.lines().skip_while(|x| !x.contains("panicked at"));
+    if let Some(message) = lines.nth(1) {
+      return Some(format!("the conversion crashed: {message}"));
+    }
+
+    match stderr.lines().rev().find(|x| !x.trim().is_empty()) {
+      Some(line) => Some(format!("the conversion stopped: {line}")),
+      None => Some(format!("the conversion stopped, {status}")),
+    }
+  }
+
+  /// The pieces as they arrive. When the worker stops early, a last piece
+  /// says why.
+  fn pieces(mut self) -> impl Iterator<Item = Piece> {
+    let mut done = false;
+
+    std::iter::from_fn(move || loop {
+      if done {
+        return None;
+      }
+
+      match self.next_message() {
+        Ok(Some(Message::Piece(piece))) => return Some(piece),
+        Ok(Some(_)) => continue,
+        Ok(None) => done = true,
+        Err(reason) => {
+          done = true;
+          return Some(Piece {
+            failed: true,
+            ..Piece::from(format!("\nhygg: {reason}"))
+          });
+        }
+      }
+    })
+  }
+}
+
+impl Drop for Worker {
+  fn drop(&mut self) {
+    // The document was closed before it was converted
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+/// Runs in the child process, converts the document of the request on stdin
+/// and writes the messages to stdout.
+pub fn run_worker() -> Result<(), Box<dyn std::error::Error>> {
+  let request: Request = serde_json::from_reader(std::io::stdin().lock())?;
+  limit_memory(request.limits.memory)?;
+
+  // Caught panics are sent as failed pages, printed they would read as a
+  // crash of the worker
+  cli_pdf_to_text::silence_caught_panics();
+
+  let mut stdout = std::io::stdout().lock();
+  let mut send = |message: &Message| -> std::io::Result<()> {
+    serde_json::to_writer(&mut stdout, message)?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()
+  };
+
+  let stream =
+    crate::ocr_settings(request.ocr.as_deref(), request.ocr_lang.as_deref())
+      .and_then(|ocr| {
+        crate::file_to_text_stream(
+          &request.file,
+          request.password.as_deref(),
+          ocr,
+          request.limits.size,
+          // The images of an encrypted pdf are not saved, like its text
+          request.image_dir.clone().filter(|_| request.password.is_none()),
+        )
+      });
+
+  let stream = match stream {
+    Ok(stream) => stream,
+    Err(e) => {
+      let message = match e.downcast_ref::<cli_pdf_to_text::PdfError>() {
+        Some(cli_pdf_to_text::PdfError::Encrypted) => Message::Encrypted,
+        _ => Message::Failed(e.to_string()),
+      };
+      send(&message)?;
+      return Ok(());
+    }
+  };
+
+  send(&Message::Opened { count: stream.count, protected: stream.protected })?;
+  for piece in stream.pieces {
+    send(&Message::Piece(piece))?;
+  }
+
+  // Once the document is recognized, its pages are the last to go
+  if (request.ocr.as_deref() != Some("false")) {
+    let _ = crate::cache::evict_ocr();
+  }
+
+  Ok(())
+}
+
+/// Allocations past `bytes` fail, which aborts the worker.
+#[cfg(unix)]
+fn limit_memory(bytes: u64) -> std::io::Result<()> {
+  let limit = libc::rlimit {
+    rlim_cur: bytes as libc::rlim_t,
+    rlim_max: bytes as libc::rlim_t,
+  };
+
+  if unsafe { libc::setrlimit(libc::RLIMIT_DATA, &limit) } != 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn limit_memory(_bytes: u64) -> std::io::Result<()> {
+  Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+
+  fn request(time: u64) -> Request {
+    Request {
+      file: "book.epub".to_string(),
+      password: None,
+      ocr: None,
+      ocr_lang: None,
+      image_dir: None,
+      limits: Limits { time, ..Limits::default() },
+    }
+  }
+
+  /// A worker that runs `script` with the request on its stdin.
+  fn script(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.args(["-c", script]);
+
+    command
+  }
+
+  const OPENED: &str = r#"echo '{"Opened":{"count":2,"protected":false}}'"#;
+
+  #[test]
+  fn test_worker_without_progress_is_stopped() {
+    let mut worker =
+      Worker::spawn(script("cat > /dev/null; exec sleep 10"), &request(1))
+        .unwrap();
+
+    let error = worker.next_message().err().unwrap();
+
+    assert!(error.contains("longer than 1 seconds"), "{error}");
+    assert!(error.contains("--time-limit"), "{error}");
+  }
+
+  #[test]
+  fn test_time_limit_is_for_the_whole_conversion() {
+    let worker = Worker::spawn(
+      script(&format!(
+        "cat > /dev/null; {OPENED}; \
+         while true; do echo '{{\"Piece\":{{\"text\":\"page\",\"recognized\":false}}}}'; \
+         sleep 0.5; done"
+      )),
+      &request(2),
+    )
+    .unwrap();
+
+    let start = Instant::now();
+    let pieces: Vec<Piece> = worker.pieces().collect();
+
+    assert!(start.elapsed() < Duration::from_secs(4));
+    assert!(pieces.len() > 1);
+    let last = pieces.last().unwrap();
+    assert!(last.failed);
+    assert!(last.text.contains("longer than 2 seconds"), "{}", last.text);
+  }
+
+  #[test]
+  fn test_panicking_worker_ends_with_a_failed_piece() {
+    let worker = Worker::spawn(
+      script(&format!(
+        "{OPENED}; echo \"thread 'main' panicked at src/lib.rs:1:1:\" >&2; \
+         echo 'index out of bounds' >&2; exit 101"
+      )),
+      &request(10),
+    )
+    .unwrap();
+
+    let pieces: Vec<Piece> = worker.pieces().collect();
+
+    assert_eq!(pieces.len(), 1);
+    assert!(pieces[0].failed);
+    assert_eq!(
+      pieces[0].text,
+      "\nhygg: the conversion crashed: index out of bounds"
+    );
+  }
+
+  #[test]
+  fn test_worker_that_finishes_is_not_a_crash() {
+    let worker = Worker::spawn(
+      script(&format!(
+        "{OPENED}; echo \"thread 'main' panicked at src/lib.rs:1:1:\" >&2; \
+         echo 'caught and sent as a failed page' >&2"
+      )),
+      &request(10),
+    )
+    .unwrap();
+
+    assert_eq!(worker.pieces().count(), 0);
+  }
+
+  #[test]
+  fn test_worker_that_stops_says_why() {
+    let worker = Worker::spawn(
+      script(&format!("{OPENED}; echo 'Killed' >&2; exit 9")),
+      &request(10),
+    )
+    .unwrap();
+
+    let pieces: Vec<Piece> = worker.pieces().collect();
+
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].text, "\nhygg: the conversion stopped: Killed");
+  }
+}
@@ -0,0 +1,121 @@
+use unicode_normalization::char::decompose_compatible;
+use unicode_normalization::UnicodeNormalization;
+
+/// Which clean ups `normalize` applies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+  /// Replace ligatures like `ﬁ`, presentation forms, full width letters and
+  /// typographic spaces with the characters they stand for
+  pub expand_compatibility: bool,
+  /// Remove soft hyphens, zero width spaces, word joiners and byte order
+  /// marks
+  pub remove_invisible: bool,
+  /// Replace smart quotes, dashes and the ellipsis with plain ASCII
+  pub fold_punctuation: bool,
+}
+
+impl Normalization {
+  /// Leaves the text as it is.
+  pub const NONE: Self = Self {
+    expand_compatibility: false,
+    remove_invisible: false,
+    fold_punctuation: false,
+  };
+
+  /// Also folds the punctuation to ASCII.
+  pub const ASCII: Self = Self {
+    expand_compatibility: true,
+    remove_invisible: true,
+    fold_punctuation: true,
+  };
+
+  /// Parses `true`, `false` or `ascii`, the values of the `--normalize`
+  /// option.
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "true" => Some(Self::default()),
+      "false" => Some(Self::NONE),
+      "ascii" => Some(Self::ASCII),
+      _ => None,
+    }
+  }
+}
+
+/// Expands compatibility characters and removes invisible ones, the
+/// punctuation is kept.
+impl Default for Normalization {
+  fn default() -> Self {
+    Self { fold_punctuation: false, ..Self::ASCII }
+  }
+}
+
+/// Cleans up text extracted from a document so it can be searched and
+/// justified, `ﬁle` becomes `file`. Combining marks are composed (NFC) first,
+/// but unlike NFKC only the characters that are artifacts of typesetting are
+/// decomposed, superscripts, fractions and the like are kept.
+pub fn normalize(text: &str, normalization: &Normalization) -> String {
+  if (*normalization == Normalization::NONE) {
+    return text.to_string();
+  }
+
+  let mut result = String::with_capacity(text.len());
+
+  for c in text.nfc() {
+    if (normalization.remove_invisible && is_invisible(c)) {
+      continue;
+    }
+
+    if normalization.fold_punctuation {
+      if let Some(folded) = fold_punctuation(c) {
+        result.push_str(folded);
+        continue;
+      }
+    }
+
+    if (normalization.expand_compatibility && is_typesetting_form(c)) {
+      decompose_compatible(c, |x| result.push(x));
+      continue;
+    }
+
+    result.push(c);
+  }
+
+  return result;
+}
+
+/// The soft hyphen, the zero width space, the word joiner, the invisible
+/// math operators and the byte order mark.
+fn is_invisible(c: char) -> bool {
+  matches!(c, '\u{AD}' | '\u{200B}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}')
+}
+
+/// Characters whose compatibility decomposition is what the author wrote.
+fn is_typesetting_form(c: char) -> bool {
+  matches!(
+    c,
+    // Typographic spaces like the thin and the hair space
+    '\u{2000}'..='\u{200A}'
+    | '\u{202F}'
+    | '\u{205F}'
+    // Latin, Armenian and Hebrew ligatures and presentation forms
+    | '\u{FB00}'..='\u{FB4F}'
+    // Arabic presentation forms
+    | '\u{FB50}'..='\u{FDFF}'
+    | '\u{FE70}'..='\u{FEFE}'
+    // Full width ASCII
+    | '\u{FF01}'..='\u{FF5E}'
+  )
+}
+
+fn fold_punctuation(c: char) -> Option<&'static str> {
+  let folded = match c {
+    '\u{2018}'..='\u{201B}' | '\u{2032}' | '\u{2039}' | '\u{203A}' => "'",
+    '\u{201C}'..='\u{201F}' | '\u{2033}' | '\u{AB}' | '\u{BB}' => "\"",
+    '\u{2010}'..='\u{2013}' | '\u{2212}' => "-",
+    '\u{2014}' | '\u{2015}' => "--",
+    '\u{2026}' => "...",
+    _ => return None,
+  };
+
+  Some(folded)
+}
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+  use normalize_text::{normalize, Normalization};
+
+  #[test]
+  fn test_ligatures_are_expanded() {
+    let text = "\u{FB01}le \u{FB02}ow o\u{FB00} o\u{FB03}ce";
+
+    assert_eq!(
+      normalize(text, &Normalization::default()),
+      "file flow off office"
+    );
+  }
+
+  #[test]
+  fn test_invisible_characters_are_removed() {
+    let text = "\u{FEFF}hy\u{AD}phen\u{200B}ation\u{2060}";
+
+    assert_eq!(normalize(text, &Normalization::default()), "hyphenation");
+  }
+
+  #[test]
+  fn test_combining_marks_are_composed() {
+    let text = "cafe\u{301} \u{FF28}\u{FF59}\u{FF47}\u{FF47}\u{2009}x\u{B2}";
+
+    assert_eq!(
+      normalize(text, &Normalization::default()),
+      "caf\u{E9} Hygg x\u{B2}"
+    );
+  }
+
+  #[test]
+  fn test_punctuation_is_only_folded_when_asked() {
+    let text = "\u{201C}Don\u{2019}t\u{201D} \u{2013} wait\u{2026}";
+
+    assert_eq!(normalize(text, &Normalization::default()), text);
+    assert_eq!(normalize(text, &Normalization::ASCII), "\"Don't\" - wait...");
+  }
+
+  #[test]
+  fn test_none_leaves_the_text() {
+    let text = "\u{FB01}le\u{AD}";
+
+    assert_eq!(normalize(text, &Normalization::NONE), text);
+    assert_eq!(Normalization::parse("false"), Some(Normalization::NONE));
+    assert_eq!(Normalization::parse("maybe"), None);
+  }
+}